@@ -0,0 +1,427 @@
+//! The main loop's logic, from the boot sequence to standby.
+//!
+//! [`App`] owns everything the loop keeps track of: the boot sequence and
+//! the player showing its steps, the buttons, the settings screen, the idle
+//! dimming, the screensaver, standby and which way up the board is. The
+//! caller polls it with the time and whether each button is down, and it
+//! draws on the OLED and lights the LED matrix it was given. What only the
+//! board can do, like writing flash or reading the accelerometer, it asks
+//! for through [`Platform`], so the same loop runs on the micro:bit, in the
+//! simulator and in the host tests.
+
+use crate::{
+    boot::{Boot, State, Step},
+    brightness::{Dimmable, Dimmer},
+    burnin::{Due, Guard, Shifted},
+    display::Oled,
+    font,
+    input::{Button, Presses, DEBOUNCE_MS},
+    led::NonBlockingMatrix,
+    lsm303agr::Accel,
+    marquee::Marquee,
+    menu::Menu,
+    orientation::{Rotated, RotatedLeds, Tracker},
+    patterns::Pattern,
+    player::Player,
+    power::{Mode, Power, STANDBY_TICK_MS},
+    recovery::Recovery,
+    screens,
+    settings::Settings,
+    speed::{Fallback, Link, Metered, Speed},
+    timeout::Micros,
+};
+
+/// What [`App`] needs from the board beyond the OLED and the LED matrix.
+pub trait Platform {
+    /// Keep `settings` over a reset.
+    fn save(&mut self, settings: &Settings);
+
+    /// Run the OLED's I2C bus at `speed`, after too many failures at the one
+    /// before.
+    fn set_speed(&mut self, speed: Speed);
+
+    /// Hand the OLED's writes to an interrupt to send rather than waiting
+    /// for them, or go back to waiting. Nothing here waits by default.
+    fn set_background(&mut self, _background: bool) {}
+
+    /// The next step of the bus scanner, drawing what it found on `display`.
+    fn scan<D: Oled>(&mut self, display: &mut D) -> Step;
+
+    /// Which way the board is being pulled, or `None` if the reading
+    /// failed. Only asked for once [`App::with_accelerometer`] says there's
+    /// an accelerometer.
+    fn accel(&mut self) -> Option<Accel>;
+
+    /// Go into standby, once the OLED is off and the LED matrix blank:
+    /// stop whatever else keeps the board awake.
+    fn standby(&mut self);
+
+    /// Start it all again, before the OLED is turned back on.
+    fn wake(&mut self);
+
+    /// The bus the OLED is on at `speed`, for the link line.
+    fn link(&self, speed: Speed) -> Link {
+        Link::I2c(speed)
+    }
+}
+
+/// The OLED as [`App`] drives it: frames timed to show how the bus is
+/// doing, the brightness put back after every init, turned round with the
+/// board and moved about so it doesn't burn in.
+pub type Display<D, C> = Shifted<Rotated<Metered<Dimmable<D>, C>>>;
+
+/// The firmware's main loop, one [`poll`](App::poll) at a time.
+#[derive(Debug)]
+pub struct App<D, C, M> {
+    display: Display<D, C>,
+    leds: RotatedLeds<M>,
+    settings: Settings,
+    /// The settings as they were when the menu opened.
+    opened_with: Settings,
+    boot: Boot,
+    /// Whether A was held through reset, for the bus scanner.
+    scanning: bool,
+    player: Player,
+    fallback: Fallback,
+    presses: Presses,
+    /// Whether a button was down at the last poll.
+    held: bool,
+    menu: Menu,
+    dimmer: Dimmer,
+    guard: Guard,
+    power: Power,
+    /// Whether there's an accelerometer to say which way up the board is.
+    tilting: bool,
+    tracker: Tracker,
+    /// The greeting going round, where it's on one line or doesn't fit: by
+    /// the controller where it can, or else redrawn a column at a time.
+    greeting: Marquee<'static>,
+    /// The frame time on the link line.
+    frame_shown_us: Option<u32>,
+}
+
+impl<D: Oled, C: Micros, M: NonBlockingMatrix> App<D, C, M> {
+    /// Start the boot sequence on `display`, timing its flushes with
+    /// `clock`, and `leds`, going by `settings` from `now_ms`.
+    pub fn new(display: D, clock: C, leds: M, settings: Settings, now_ms: u32) -> Self {
+        Self {
+            display: Shifted::new(Rotated::new(Metered::new(Dimmable::new(display), clock))),
+            leds: RotatedLeds::new(leds),
+            settings,
+            opened_with: settings,
+            boot: Boot::new(),
+            scanning: false,
+            player: Player::new(),
+            fallback: Fallback::new(settings.i2c_speed.unwrap_or_default()),
+            presses: Presses::new(false, false, now_ms),
+            held: false,
+            menu: Menu::new(),
+            dimmer: Dimmer::new(
+                settings.brightness.unwrap_or_default(),
+                settings.night_mode.unwrap_or(false),
+                now_ms,
+            ),
+            guard: Guard::new(settings.saver.unwrap_or_default(), now_ms),
+            power: Power::new(settings.saver.unwrap_or_default(), now_ms),
+            tilting: false,
+            tracker: Tracker::new(now_ms),
+            greeting: Marquee::new(screens::GREETING, screens::GREETING_SCROLL),
+            frame_shown_us: None,
+        }
+    }
+
+    /// Report how the bus came up, as [`Boot::with_recovery`].
+    pub fn with_recovery(mut self, recovery: Recovery) -> Self {
+        self.boot = self.boot.with_recovery(recovery);
+        self
+    }
+
+    /// Turn the OLED and the LED patterns round with the board, if an
+    /// accelerometer was `found`, reading it through [`Platform::accel`].
+    pub fn with_accelerometer(mut self, found: bool) -> Self {
+        self.tilting = found;
+        self
+    }
+
+    /// Go by the buttons held through reset, as they were at `now_ms`: A
+    /// runs the bus scanner instead of the boot sequence, and B shows the
+    /// bus speed first, as a digit, once it's been moved on to the next one.
+    /// Neither counts as a press until it's let go and pressed again.
+    pub fn held_at_reset(mut self, a_down: bool, b_down: bool, now_ms: u32) -> Self {
+        self.scanning = a_down;
+        self.presses = Presses::new(a_down, b_down, now_ms);
+        if b_down {
+            // 1, 2 or 4 for 100, 250 or 400 kHz.
+            let khz = self.fallback.speed().khz();
+            let digit = char::from_digit(khz / 100, 10).unwrap();
+            self.player.queue(Step::Show {
+                pattern: font::glyph(digit).unwrap(),
+                duration_ms: 1000,
+            });
+        }
+        self
+    }
+
+    /// The OLED underneath the wrappers.
+    pub fn panel(&mut self) -> &mut D {
+        self.display.inner().inner().inner().inner()
+    }
+
+    /// The LED matrix underneath, lit the way up the board is.
+    pub fn leds(&mut self) -> &mut M {
+        self.leds.inner()
+    }
+
+    /// The settings, with whatever has been changed on the settings screen.
+    pub const fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Whether the greeting is up and the buttons work the settings screen.
+    pub fn is_running(&self) -> bool {
+        !self.scanning && matches!(self.boot.state(), State::Running)
+    }
+
+    /// What it's doing, for the simulator's status line.
+    pub fn mode(&self) -> Mode {
+        self.power.mode(!self.is_running())
+    }
+
+    /// How long the caller can sleep after a poll at `now_ms` before the
+    /// next one is due, if no button goes down meanwhile. A button held
+    /// down is looked at every [`DEBOUNCE_MS`] until it's let go; otherwise
+    /// it's the soonest of the step showing on the LEDs, the dimming, the
+    /// screensaver, standby, the accelerometer and the greeting going round.
+    pub fn sleep_ms(&self, now_ms: u32) -> u32 {
+        if self.held {
+            return DEBOUNCE_MS;
+        }
+        if self.power.is_standby() {
+            return STANDBY_TICK_MS;
+        }
+        let running = self.is_running();
+        let greeting = running && !self.menu.is_open() && !self.guard.is_saving();
+        [
+            self.player.due_in_ms(now_ms),
+            self.dimmer.due_in_ms(now_ms),
+            running.then(|| self.guard.due_in_ms(now_ms)),
+            running.then(|| self.power.due_in_ms(now_ms)).flatten(),
+            self.tilting.then(|| self.tracker.due_in_ms(now_ms)),
+            greeting
+                .then(|| self.greeting.due_in_ms(&self.display, now_ms))
+                .flatten(),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(STANDBY_TICK_MS, u32::min)
+    }
+
+    /// Do whatever is due at `now_ms`, with A and B down or not.
+    pub fn poll<P: Platform>(&mut self, now_ms: u32, a_down: bool, b_down: bool, platform: &mut P) {
+        // In standby nothing is checked or shown until it wakes. The next
+        // step is worked out (and the OLED talked to) while the current
+        // pattern is showing.
+        if !self.power.is_standby() {
+            self.player.poll(now_ms, &mut self.leds);
+            if self.player.wants_step() {
+                self.step(now_ms, platform);
+            }
+        }
+
+        self.held = a_down || b_down;
+        if let Some(button) = self.presses.update(now_ms, a_down, b_down) {
+            self.press(button, now_ms, platform);
+        }
+
+        let running = self.is_running();
+        if running && self.power.poll(now_ms) {
+            self.close_menu(platform);
+            self.greeting.stop(&mut self.display).ok();
+            self.display.set_display_on(false).ok();
+            // Blank, so nothing is left lit when the matrix stops.
+            self.leds.inner().set(Pattern::BLANK);
+            platform.standby();
+        }
+        if self.power.is_standby() {
+            return;
+        }
+
+        if running {
+            match self.guard.poll(now_ms) {
+                Due::Nothing => {}
+                Due::Redraw => {
+                    self.display.set(self.guard.offset(), self.guard.inverted());
+                    self.redraw(platform, now_ms);
+                }
+                Due::Saver => {
+                    self.close_menu(platform);
+                    self.display.set(self.guard.offset(), self.guard.inverted());
+                    let (saver, frames) = (self.guard.saver(), self.guard.frames());
+                    screens::saver(&mut self.display, saver, frames, now_ms).ok();
+                    // Only the first frame replaces the whole screen.
+                    if frames == 0 {
+                        self.display.flush().ok();
+                    } else {
+                        self.flush_in_background(platform);
+                    }
+                }
+            }
+        }
+        if self.tilting && self.tracker.is_due(now_ms) {
+            let turned = match platform.accel() {
+                Some(reading) => self.tracker.update(now_ms, reading),
+                None => {
+                    self.tracker.missed(now_ms);
+                    None
+                }
+            };
+            if let Some(orientation) = turned {
+                self.leds.set_orientation(orientation);
+                self.display.inner().set(orientation);
+                // The screensaver's next frame comes out turned anyway.
+                if running && !self.guard.is_saving() {
+                    self.redraw(platform, now_ms);
+                }
+            }
+        }
+        if running
+            && !self.menu.is_open()
+            && !self.guard.is_saving()
+            && self.greeting.poll(&mut self.display, now_ms)
+        {
+            self.flush_in_background(platform);
+        }
+        if let Some(drive) = self.dimmer.poll(now_ms) {
+            self.display.set_brightness(drive).ok();
+        }
+    }
+
+    /// Queue the next step of the boot sequence, or of the scanner, and
+    /// keep the screen up to date with it.
+    fn step<P: Platform>(&mut self, now_ms: u32, platform: &mut P) {
+        if self.scanning {
+            self.player.queue(platform.scan(&mut self.display));
+            return;
+        }
+        let was_running = self.is_running();
+        self.player.queue(self.boot.step(&mut self.display));
+        if let Some(speed) = self.fallback.observe(self.boot.state()) {
+            platform.set_speed(speed);
+        }
+        if !self.is_running() {
+            self.close_menu(platform);
+        }
+        // The panel came back with its last frame, which the error may have
+        // been drawn over. The screensaver draws a whole frame each time
+        // anyway.
+        let recovered = self.boot.take_recovered();
+        if recovered && !self.guard.is_saving() {
+            self.redraw(platform, now_ms);
+        }
+        let greeting = self.is_running() && !self.menu.is_open() && !self.guard.is_saving();
+        let frame_us = self.display.inner().inner().frame_us();
+        // Sending the link line while the controller scrolls would stop it
+        // and send a whole frame, timing it again, so it waits for a redraw.
+        if greeting && frame_us != self.frame_shown_us && !self.greeting.in_hardware() {
+            self.frame_shown_us = frame_us;
+            if let Some(frame_us) = frame_us {
+                let link = platform.link(self.fallback.speed());
+                screens::link(&mut self.display, link, frame_us).ok();
+                self.flush_in_background(platform);
+            }
+        }
+        // The greeting was just put up, with the link line under it if
+        // there's a frame time yet.
+        if greeting && !was_running && !recovered && screens::hello_goes_round(&self.display) {
+            self.greeting.start(&mut self.display, now_ms).ok();
+        }
+        // Only write flash when the panel turns up somewhere new.
+        let found = self.display.address();
+        if found.is_some() && found != self.settings.oled_address {
+            self.settings.oled_address = found;
+            platform.save(&self.settings);
+        }
+    }
+
+    /// Act on `button` going down at `now_ms`.
+    fn press<P: Platform>(&mut self, button: Button, now_ms: u32, platform: &mut P) {
+        let standby = self.power.input(now_ms);
+        let woken = self.dimmer.input(now_ms);
+        let saved = self.guard.input(now_ms);
+        if standby {
+            platform.wake();
+            self.display.set_display_on(true).ok();
+            self.leds.refresh();
+        }
+        if !self.is_running() {
+            return;
+        }
+        // A press that wakes the panel up or ends the screensaver does
+        // nothing else.
+        if standby || saved {
+            self.display.set(self.guard.offset(), self.guard.inverted());
+            self.frame_shown_us = self.display.inner().inner().frame_us();
+            self.redraw(platform, now_ms);
+        } else if !woken {
+            if !self.menu.is_open() {
+                self.opened_with = self.settings;
+            }
+            self.menu.press(button, &mut self.settings);
+            self.dimmer
+                .set_level(self.settings.brightness.unwrap_or_default());
+            self.dimmer
+                .set_night(self.settings.night_mode.unwrap_or(false));
+            self.guard
+                .set_saver(self.settings.saver.unwrap_or_default());
+            self.power
+                .set_saver(self.settings.saver.unwrap_or_default());
+            self.frame_shown_us = self.display.inner().inner().frame_us();
+            self.redraw(platform, now_ms);
+            if !self.menu.is_open() && self.settings != self.opened_with {
+                platform.save(&self.settings);
+                self.opened_with = self.settings;
+            }
+        }
+    }
+
+    /// Close the settings screen, if it's open, and keep what was changed.
+    fn close_menu<P: Platform>(&mut self, platform: &mut P) {
+        if !self.menu.is_open() {
+            return;
+        }
+        self.menu.close();
+        if self.settings != self.opened_with {
+            platform.save(&self.settings);
+            self.opened_with = self.settings;
+        }
+    }
+
+    /// Draw what should be showing once the greeting is up, and send it: the
+    /// settings screen if it's open, or else the greeting with the link line
+    /// under it, once there's a frame time. A greeting on one line, or too
+    /// long for the panel, is set going round. It may well be a whole frame, so it isn't sent in
+    /// the background, where it couldn't be timed.
+    fn redraw<P: Platform>(&mut self, platform: &P, now_ms: u32) {
+        if let Some(item) = self.menu.item() {
+            screens::settings(&mut self.display, item, &self.settings).ok();
+        } else {
+            screens::hello(&mut self.display).ok();
+            if let Some(frame_us) = self.frame_shown_us {
+                let link = platform.link(self.fallback.speed());
+                screens::link(&mut self.display, link, frame_us).ok();
+            }
+            if screens::hello_goes_round(&self.display) {
+                self.greeting.start(&mut self.display, now_ms).ok();
+                return;
+            }
+        }
+        self.display.flush().ok();
+    }
+
+    /// Flush without waiting for it to go out, where the platform can.
+    fn flush_in_background<P: Platform>(&mut self, platform: &mut P) {
+        platform.set_background(true);
+        self.display.flush().ok();
+        platform.set_background(false);
+    }
+}
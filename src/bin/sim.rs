@@ -0,0 +1,271 @@
+//! Runs the firmware's main loop on a PC, drawing the OLED and the LED
+//! matrix in the terminal.
+//!
+//! `cargo sim` to start it. Keys `a` and `b` are the micro:bit buttons, `t`
+//! turns the board upside down and back, and `q` or Esc quits. Once the
+//! greeting is up, the buttons work the settings screen as on the device;
+//! the terminal can't dim, so the status line says how bright the panel
+//! would be. It also says what the device would be doing and roughly what
+//! it would draw. Left alone, the panel dims, the screensaver starts and
+//! after half an hour it goes into standby, as on the device.
+
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    queue,
+    terminal::{self, ClearType},
+};
+use embedded_graphics::prelude::*;
+use microbit_oled::{
+    app::{App, Platform},
+    boot::Step,
+    brightness::{Drive, Level},
+    display::{Oled, PANEL_SIZE},
+    emulator::Framebuffer,
+    input::{Button, DEBOUNCE_MS},
+    led::RecordingMatrix,
+    lsm303agr::Accel,
+    patterns::Pattern,
+    settings::Settings,
+    speed::Speed,
+    timeout::Micros,
+};
+
+/// Puts the terminal back the way it was, even if we bail out with an error.
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        queue!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        io::stdout().flush()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        queue!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen).ok();
+        io::stdout().flush().ok();
+        terminal::disable_raw_mode().ok();
+    }
+}
+
+/// Microseconds since the simulator started, to time the flushes. The
+/// framebuffer takes next to no time over them, unlike any real bus.
+struct Elapsed(Instant);
+
+impl Micros for Elapsed {
+    fn now_us(&mut self) -> u32 {
+        self.0.elapsed().as_micros() as u32
+    }
+}
+
+type Sim = App<Framebuffer, Elapsed, RecordingMatrix>;
+
+/// How long a key holds its button down: long enough to count as a press.
+const PRESS_MS: u32 = 2 * DEBOUNCE_MS;
+
+/// The board around the app, as far as the terminal goes.
+struct Board {
+    start: Instant,
+    last_button: Option<Button>,
+    /// Until when each of A and B is held down by a key.
+    down_until_ms: [u32; 2],
+    upside_down: bool,
+}
+
+impl Board {
+    fn now_ms(&self) -> u32 {
+        self.start.elapsed().as_millis() as u32
+    }
+
+    /// Whether A and B are down at `now_ms`.
+    fn buttons(&self, now_ms: u32) -> (bool, bool) {
+        let down = |i: usize| now_ms < self.down_until_ms[i];
+        (down(0), down(1))
+    }
+
+    /// Handle keys for up to `timeout`, or until a button goes down, as
+    /// the firmware sleeps. Returns `false` once the user asks to quit.
+    fn keys(&mut self, timeout: Duration) -> io::Result<bool> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() || !event::poll(left)? {
+                return Ok(true);
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let button = match key.code {
+                KeyCode::Char('a') => Button::A,
+                KeyCode::Char('b') => Button::B,
+                KeyCode::Char('t') => {
+                    self.upside_down = !self.upside_down;
+                    continue;
+                }
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(false)
+                }
+                _ => continue,
+            };
+            self.last_button = Some(button);
+            self.down_until_ms[button as usize] = self.now_ms() + PRESS_MS;
+            return Ok(true);
+        }
+    }
+}
+
+impl Platform for Board {
+    /// Nothing outlasts the simulator.
+    fn save(&mut self, _: &Settings) {}
+
+    fn set_speed(&mut self, _: Speed) {}
+
+    fn scan<D: Oled>(&mut self, _: &mut D) -> Step {
+        unreachable!("the simulator never starts with A held")
+    }
+
+    /// Stood up, one way or the other.
+    fn accel(&mut self) -> Option<Accel> {
+        let y = if self.upside_down { -1000 } else { 1000 };
+        Some(Accel { x: 0, y, z: 0 })
+    }
+
+    fn standby(&mut self) {}
+
+    fn wake(&mut self) {}
+}
+
+/// Everything on screen, to tell when it wants drawing again.
+#[derive(Clone, Copy, PartialEq)]
+struct Shown {
+    flushes: usize,
+    display_on: bool,
+    drive: Option<Drive>,
+    leds: Pattern,
+    status: &'static str,
+    last_button: Option<Button>,
+    upside_down: bool,
+}
+
+impl Shown {
+    fn of(sim: &mut Sim, board: &Board) -> Self {
+        let leds = sim.leds().lit();
+        let status = sim.mode().name();
+        let oled = sim.panel();
+        Self {
+            flushes: oled.flushes(),
+            display_on: oled.is_display_on(),
+            drive: oled.brightness(),
+            leds,
+            status,
+            last_button: board.last_button,
+            upside_down: board.upside_down,
+        }
+    }
+}
+
+fn render(sim: &mut Sim, board: &Board) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    queue!(out, cursor::MoveTo(0, 0), terminal::Clear(ClearType::All))?;
+
+    let mode = sim.mode();
+    let leds = sim.leds().lit();
+    let oled = sim.panel();
+    let Size { width, height } = oled.size();
+    write!(out, "┌{}┐\r\n", "─".repeat(width as usize))?;
+    // Two pixel rows per character cell.
+    for y in (0..height as i32).step_by(2) {
+        write!(out, "│")?;
+        for x in 0..width as i32 {
+            let lit = |y| oled.is_display_on() && oled.pixel(Point::new(x, y));
+            let (top, bottom) = (lit(y), lit(y + 1));
+            let cell = match (top, bottom) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            };
+            write!(out, "{cell}")?;
+        }
+        write!(out, "│\r\n")?;
+    }
+    write!(out, "└{}┘\r\n\r\n", "─".repeat(width as usize))?;
+
+    for row in leds.rows() {
+        write!(out, "  ")?;
+        for led in row {
+            // The terminal has no brightness, so dim LEDs get a smaller dot.
+            let cell = match led {
+                0 => '·',
+                1..=4 => '•',
+                _ => '●',
+            };
+            write!(out, "{cell} ")?;
+        }
+        write!(out, "\r\n")?;
+    }
+
+    let button = match board.last_button {
+        Some(Button::A) => "A",
+        Some(Button::B) => "B",
+        None => "-",
+    };
+    let drive = oled.brightness().unwrap_or(Level::default().drive());
+    write!(
+        out,
+        "\r\n  last button: {button}  contrast: {:#04X}{}  {}{}: ~{:.2} mA    [a] button A  [b] button B  [t] turn over  [q] quit\r\n",
+        drive.contrast,
+        if drive == Drive::NIGHT { " (night)" } else { "" },
+        if board.upside_down { "upside down  " } else { "" },
+        mode.name(),
+        mode.estimated_ua() as f32 / 1000.0,
+    )?;
+    out.flush()
+}
+
+fn main() -> io::Result<()> {
+    let _terminal = RawTerminal::enter()?;
+    let start = Instant::now();
+    let mut board = Board {
+        start,
+        last_button: None,
+        down_until_ms: [0; 2],
+        upside_down: false,
+    };
+    let oled = Framebuffer::new(PANEL_SIZE);
+    let mut sim = App::new(
+        oled,
+        Elapsed(start),
+        RecordingMatrix::new(),
+        Settings::new(),
+        0,
+    )
+    .with_accelerometer(true);
+    let mut shown = None;
+    loop {
+        let now_ms = board.now_ms();
+        let (a_down, b_down) = board.buttons(now_ms);
+        sim.poll(now_ms, a_down, b_down, &mut board);
+        let now = Shown::of(&mut sim, &board);
+        if shown != Some(now) {
+            shown = Some(now);
+            render(&mut sim, &board)?;
+        }
+        let sleep_ms = sim.sleep_ms(board.now_ms());
+        if !board.keys(Duration::from_millis(sleep_ms.into()))? {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,320 @@
+//! The boot sequence as a state machine.
+//!
+//! The firmware calls [`Boot::step`] in a loop and plays the [`Step`] it
+//! returns on the LED matrix, so the order of events lives here and can be
+//! checked on the host without a board.
+//!
+//! The OLED is supervised rather than set up once: a failed init is retried
+//! with backoff while the error code blinks, and once running the panel is
+//! checked every step, so one that is unplugged, or loses power for a moment,
+//! is initialised again and sent the frame it was showing when it comes back.
+//! [`Boot::take_recovered`] tells whoever draws on it, in case that frame was
+//! lost too.
+
+use display_interface::DisplayError;
+
+use crate::{
+    display::{Health, Oled},
+    error::Error,
+    font,
+    led::Frame,
+    patterns::{self, Pattern},
+    recovery::Recovery,
+    screens,
+};
+
+/// Where the boot sequence has got to.
+#[derive(Clone, Debug)]
+pub enum State {
+    /// Nothing has happened yet.
+    Start,
+    /// The smiley has been shown; report how the bus came up.
+    BusCheck,
+    /// The OLED is next.
+    InitDisplay,
+    /// The OLED is up; show which address it answered on, then the greeting.
+    Address,
+    /// The OLED is up; the greeting is next.
+    Greeting,
+    /// The greeting is on screen; keep an eye on the OLED.
+    Running,
+    /// The OLED failed or went away; blink the error's code on the X and
+    /// retry now and then.
+    Failed {
+        /// What went wrong.
+        error: Error,
+        /// Blinks shown so far in this round.
+        blinks: u8,
+        /// Whether the X was the last thing shown.
+        lit: bool,
+        /// When to try the OLED again.
+        retry: Retry,
+    },
+}
+
+/// Backoff between attempts to bring the OLED back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Retry {
+    /// Attempts that have failed in a row.
+    pub failures: u32,
+    /// LED time played since the last attempt.
+    pub waited_ms: u32,
+}
+
+impl Retry {
+    /// How long to wait after the last failure: [`RETRY_MIN_MS`], doubling
+    /// with each failure up to [`RETRY_MAX_MS`].
+    pub const fn backoff_ms(&self) -> u32 {
+        let doublings = if self.failures > 5 {
+            4
+        } else {
+            self.failures.saturating_sub(1)
+        };
+        let backoff_ms = RETRY_MIN_MS << doublings;
+        if backoff_ms > RETRY_MAX_MS {
+            RETRY_MAX_MS
+        } else {
+            backoff_ms
+        }
+    }
+
+    /// Whether it's time for another attempt.
+    pub const fn is_due(&self) -> bool {
+        self.waited_ms >= self.backoff_ms()
+    }
+}
+
+/// How long the X stays lit for each blink of an error code.
+pub const BLINK_MS: u32 = 300;
+/// How long the matrix stays dark between blinks.
+pub const GAP_MS: u32 = 300;
+/// How long the matrix stays dark after the last blink, before the code repeats.
+pub const PAUSE_MS: u32 = 1500;
+/// Wait after the first failure before trying the OLED again.
+pub const RETRY_MIN_MS: u32 = 1000;
+/// Longest wait between attempts, however often they fail.
+pub const RETRY_MAX_MS: u32 = 16_000;
+/// How often a running OLED is checked.
+pub const CHECK_MS: u32 = 1000;
+
+/// What to do on the LED matrix before stepping again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// Show `pattern` for `duration_ms`, then clear the matrix.
+    Show { pattern: Pattern, duration_ms: u32 },
+    /// Leave the matrix dark for `duration_ms`.
+    Wait { duration_ms: u32 },
+}
+
+impl Step {
+    /// How long the step takes.
+    pub const fn duration_ms(self) -> u32 {
+        match self {
+            Step::Show { duration_ms, .. } | Step::Wait { duration_ms } => duration_ms,
+        }
+    }
+}
+
+/// A frame of a [`Scroll`](crate::scroll::Scroll), played like any other
+/// step.
+impl From<Frame> for Step {
+    fn from(frame: Frame) -> Self {
+        show(frame.pattern, frame.duration_ms)
+    }
+}
+
+/// Drives the smiley → OLED init → check → address → greeting → heart
+/// sequence, and keeps the OLED going afterwards.
+#[derive(Clone, Debug)]
+pub struct Boot {
+    state: State,
+    /// How the bus came up, before the TWIM took it.
+    recovery: Recovery,
+    /// Whether the greeting has been shown once, so a recovered OLED goes
+    /// straight back to what it was showing.
+    booted: bool,
+    /// Whether the OLED has come back since [`Boot::take_recovered`] was
+    /// last asked.
+    recovered: bool,
+}
+
+impl Default for Boot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Boot {
+    /// A boot sequence that has not started yet.
+    pub const fn new() -> Self {
+        Self {
+            state: State::Start,
+            recovery: Recovery::Idle,
+            booted: false,
+            recovered: false,
+        }
+    }
+
+    /// Report `recovery` after the smiley: an exclamation mark if the bus had
+    /// to be freed, the stuck-bus error code if it couldn't be.
+    pub const fn with_recovery(mut self, recovery: Recovery) -> Self {
+        self.recovery = recovery;
+        self
+    }
+
+    /// The current state.
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// Whether the OLED has been brought back since this was last asked.
+    /// It's sent the frame it was showing, but that may have been drawn
+    /// over with the error, or belong to a module since swapped for another,
+    /// so whatever should be on screen wants drawing again.
+    pub fn take_recovered(&mut self) -> bool {
+        core::mem::replace(&mut self.recovered, false)
+    }
+
+    /// Advance one step, talking to `display` if this step needs it.
+    pub fn step<D: Oled>(&mut self, display: &mut D) -> Step {
+        match &mut self.state {
+            State::Start => {
+                // Show a smiley to indicate program started
+                self.state = State::BusCheck;
+                show(patterns::SMILEY, 1000)
+            }
+            State::BusCheck => match self.recovery.error() {
+                // Nothing on a stuck bus would get through; go straight to
+                // the error code and let the retries find out when it frees up.
+                Some(error) => self.fail(display, error, 1),
+                None if self.recovery == Recovery::Idle => self.init(display, 0),
+                None => {
+                    self.state = State::InitDisplay;
+                    show(patterns::EXCLAMATION, 1000)
+                }
+            },
+            State::InitDisplay => self.init(display, 0),
+            // The last hex digit is enough to tell 0x3C from 0x3D.
+            State::Address => match display.address() {
+                Some(address) => {
+                    self.state = State::Greeting;
+                    show(font::hex_digit(address), 1000)
+                }
+                None => self.greet(display),
+            },
+            State::Greeting => self.greet(display),
+            State::Running => match display.check() {
+                Ok(Health::Ok) => Step::Wait {
+                    duration_ms: CHECK_MS,
+                },
+                // Power blipped: bring it back without making a fuss.
+                Ok(Health::Reset) => match display.init().and_then(|()| send(display)) {
+                    Ok(()) => {
+                        self.recovered = true;
+                        Step::Wait {
+                            duration_ms: CHECK_MS,
+                        }
+                    }
+                    Err(error) => self.fail(display, error, 1),
+                },
+                Err(error) => self.fail(display, error, 1),
+            },
+            State::Failed {
+                error,
+                blinks,
+                lit,
+                retry,
+            } => {
+                // Only retry between rounds, so a code is never cut short.
+                if *blinks == 0 && !*lit && retry.is_due() {
+                    let failures = retry.failures;
+                    return self.init(display, failures);
+                }
+                *lit = !*lit;
+                let step = if *lit {
+                    *blinks += 1;
+                    show(patterns::CROSS, BLINK_MS)
+                } else if *blinks == error.code() {
+                    *blinks = 0;
+                    Step::Wait {
+                        duration_ms: PAUSE_MS,
+                    }
+                } else {
+                    Step::Wait {
+                        duration_ms: GAP_MS,
+                    }
+                };
+                retry.waited_ms = retry.waited_ms.saturating_add(step.duration_ms());
+                step
+            }
+        }
+    }
+
+    /// Initialise the OLED after `failures` failed attempts, and carry on
+    /// from where it was lost.
+    fn init<D: Oled>(&mut self, display: &mut D, failures: u32) -> Step {
+        if let Err(error) = display.init() {
+            return self.fail(display, error, failures + 1);
+        }
+        if self.booted {
+            if let Err(error) = send(display) {
+                return self.fail(display, error, failures + 1);
+            }
+            self.recovered = true;
+            self.state = State::Running;
+        } else {
+            self.state = State::Address;
+        }
+        show(patterns::CHECK, 1000)
+    }
+
+    /// Put the greeting up for the first time.
+    fn greet<D: Oled>(&mut self, display: &mut D) -> Step {
+        // A failed draw only leaves a blank buffer; the flush is what matters.
+        screens::hello(display).ok();
+        if let Err(error) = send(display) {
+            return self.fail(display, error, 1);
+        }
+        self.booted = true;
+        self.state = State::Running;
+        show(patterns::HEART, 2000)
+    }
+
+    /// Start blinking `error`, after `failures` attempts in a row have failed.
+    fn fail<D: Oled>(&mut self, display: &mut D, error: Error, failures: u32) -> Step {
+        if error.can_show_text() {
+            screens::error(display, &error).ok();
+            display.flush().ok();
+        }
+        self.state = State::Failed {
+            error,
+            blinks: 1,
+            lit: true,
+            retry: Retry {
+                failures,
+                waited_ms: BLINK_MS,
+            },
+        };
+        show(patterns::CROSS, BLINK_MS)
+    }
+}
+
+/// Push the frame out, saying why if the panel didn't take it.
+fn send<D: Oled>(display: &mut D) -> Result<(), Error> {
+    display.flush().map_err(|e| lost(display, e))
+}
+
+/// The best explanation for a flush that failed with `error`.
+fn lost<D: Oled>(display: &mut D, error: DisplayError) -> Error {
+    match display.check() {
+        Err(error) => error,
+        Ok(_) => Error::DisplayInit(error),
+    }
+}
+
+const fn show(pattern: Pattern, duration_ms: u32) -> Step {
+    Step::Show {
+        pattern,
+        duration_ms,
+    }
+}
@@ -0,0 +1,300 @@
+//! How bright the OLED is.
+//!
+//! The SSD1306 sets brightness with two registers: contrast (`0x81`), the
+//! segment current, and the pre-charge period (`0xD9`), how long each pixel
+//! is charged before it's driven. [`Level`] maps five steps onto the two, the
+//! middle one being what the driver's init sequence sets anyway. Night mode
+//! goes below the lowest step, for a panel by the bed.
+//!
+//! [`Dimmer`] works out which to show: the chosen level, night mode if it's
+//! on, or the lowest level once nothing has been pressed for
+//! [`DIM_AFTER_MS`]. An init puts the registers back to the driver's
+//! defaults, so [`Dimmable`] sends the level again after every one.
+
+use display_interface::DisplayError;
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*, primitives::Rectangle, Pixel};
+
+use crate::{
+    display::{forward_oled, Oled},
+    error::Error,
+};
+
+/// What a brightness is sent to the panel as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Drive {
+    /// The `0x81` contrast register.
+    pub contrast: u8,
+    /// The pre-charge period in display clocks, 1 to 15: phase 2 of the
+    /// `0xD9` register. Panels only tell 1 and 2 apart.
+    pub precharge: u8,
+}
+
+impl Drive {
+    /// Night mode: no contrast and the shortest pre-charge, dimmer than any
+    /// level.
+    pub const NIGHT: Drive = Drive {
+        contrast: 0x00,
+        precharge: 1,
+    };
+}
+
+/// A brightness step, as picked on the settings screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Lowest,
+    Low,
+    /// What the SSD1306 driver's init sequence sets.
+    #[default]
+    Medium,
+    High,
+    Highest,
+}
+
+impl Level {
+    /// Every level, dimmest first.
+    pub const ALL: [Level; 5] = [
+        Level::Lowest,
+        Level::Low,
+        Level::Medium,
+        Level::High,
+        Level::Highest,
+    ];
+
+    /// The registers for this level.
+    pub const fn drive(self) -> Drive {
+        let (contrast, precharge) = match self {
+            Level::Lowest => (0x0F, 1),
+            Level::Low => (0x2F, 1),
+            Level::Medium => (0x5F, 2),
+            Level::High => (0x9F, 2),
+            Level::Highest => (0xFF, 2),
+        };
+        Drive {
+            contrast,
+            precharge,
+        }
+    }
+
+    /// The level's number on screen, 1 to 5.
+    pub const fn number(self) -> u8 {
+        self as u8 + 1
+    }
+
+    /// The next level up, going round to the dimmest after the brightest.
+    pub const fn next(self) -> Level {
+        match self {
+            Level::Lowest => Level::Low,
+            Level::Low => Level::Medium,
+            Level::Medium => Level::High,
+            Level::High => Level::Highest,
+            Level::Highest => Level::Lowest,
+        }
+    }
+
+    /// The level's position in [`ALL`](Level::ALL), as it's stored in flash.
+    pub const fn index(self) -> u8 {
+        self as u8
+    }
+
+    /// The level at `index` in [`ALL`](Level::ALL).
+    pub const fn from_index(index: u8) -> Option<Level> {
+        match index {
+            0 => Some(Level::Lowest),
+            1 => Some(Level::Low),
+            2 => Some(Level::Medium),
+            3 => Some(Level::High),
+            4 => Some(Level::Highest),
+            _ => None,
+        }
+    }
+}
+
+/// How long without a button press before the panel dims.
+pub const DIM_AFTER_MS: u32 = 60_000;
+
+/// The level the panel dims to when left alone.
+pub const DIM_LEVEL: Level = Level::Lowest;
+
+/// Picks the brightness to show from the chosen level, night mode and how
+/// long it's been since anything was pressed.
+#[derive(Clone, Copy, Debug)]
+pub struct Dimmer {
+    level: Level,
+    night: bool,
+    last_input_ms: u32,
+    dimmed: bool,
+    /// What was last handed out by [`poll`](Dimmer::poll).
+    sent: Option<Drive>,
+}
+
+impl Dimmer {
+    /// Show `level`, or night mode if `night`, counting idle time from
+    /// `now_ms`.
+    pub const fn new(level: Level, night: bool, now_ms: u32) -> Self {
+        Self {
+            level,
+            night,
+            last_input_ms: now_ms,
+            dimmed: false,
+            sent: None,
+        }
+    }
+
+    /// The chosen level.
+    pub const fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Choose `level`.
+    pub fn set_level(&mut self, level: Level) {
+        self.level = level;
+    }
+
+    /// Whether night mode is on.
+    pub const fn night(&self) -> bool {
+        self.night
+    }
+
+    /// Turn night mode on or off.
+    pub fn set_night(&mut self, night: bool) {
+        self.night = night;
+    }
+
+    /// Whether the panel has dimmed for lack of input.
+    pub const fn is_dimmed(&self) -> bool {
+        self.dimmed
+    }
+
+    /// A button was pressed at `now_ms`. Returns whether that brightens a
+    /// dimmed panel, in which case the press should only wake it up.
+    pub fn input(&mut self, now_ms: u32) -> bool {
+        let before = self.drive();
+        self.last_input_ms = now_ms;
+        self.dimmed = false;
+        self.drive() != before
+    }
+
+    /// The registers the panel should be showing now.
+    pub fn drive(&self) -> Drive {
+        if self.night {
+            Drive::NIGHT
+        } else if self.dimmed {
+            self.level.min(DIM_LEVEL).drive()
+        } else {
+            self.level.drive()
+        }
+    }
+
+    /// How long until [`poll`](Self::poll) has new registers to send, if
+    /// nothing is pressed meanwhile, or `None` if it won't.
+    pub fn due_in_ms(&self, now_ms: u32) -> Option<u32> {
+        if self.sent != Some(self.drive()) {
+            Some(0)
+        } else if self.dimmed {
+            None
+        } else {
+            Some(DIM_AFTER_MS.saturating_sub(now_ms.wrapping_sub(self.last_input_ms)))
+        }
+    }
+
+    /// Check the time, and return the registers to send if they've changed
+    /// since the last call.
+    pub fn poll(&mut self, now_ms: u32) -> Option<Drive> {
+        if now_ms.wrapping_sub(self.last_input_ms) >= DIM_AFTER_MS {
+            self.dimmed = true;
+        }
+        let drive = self.drive();
+        if self.sent == Some(drive) {
+            return None;
+        }
+        self.sent = Some(drive);
+        Some(drive)
+    }
+}
+
+/// An OLED that keeps its brightness through an init, which sets the
+/// driver's default again, and stays off if it was turned off.
+#[derive(Debug)]
+pub struct Dimmable<D> {
+    display: D,
+    drive: Option<Drive>,
+    display_on: bool,
+}
+
+impl<D: Oled> Dimmable<D> {
+    /// `display`, left at the driver's brightness until told otherwise.
+    pub const fn new(display: D) -> Self {
+        Self {
+            display,
+            drive: None,
+            display_on: true,
+        }
+    }
+
+    /// The display underneath.
+    pub fn inner(&mut self) -> &mut D {
+        &mut self.display
+    }
+}
+
+impl<D: Oled> OriginDimensions for Dimmable<D> {
+    fn size(&self) -> Size {
+        self.display.bounding_box().size
+    }
+}
+
+impl<D: Oled> DrawTarget for Dimmable<D> {
+    type Color = BinaryColor;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.display.draw_iter(pixels)
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.display.fill_solid(area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.display.clear(color)
+    }
+}
+
+impl<D: Oled> Oled for Dimmable<D> {
+    fn init(&mut self) -> Result<(), Error> {
+        self.display.init()?;
+        if let Some(drive) = self.drive {
+            self.display
+                .set_brightness(drive)
+                .map_err(Error::DisplayInit)?;
+        }
+        if !self.display_on {
+            self.display
+                .set_display_on(false)
+                .map_err(Error::DisplayInit)?;
+        }
+        Ok(())
+    }
+
+    fn set_brightness(&mut self, drive: Drive) -> Result<(), DisplayError> {
+        self.drive = Some(drive);
+        self.display.set_brightness(drive)
+    }
+
+    fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        self.display_on = on;
+        self.display.set_display_on(on)
+    }
+
+    forward_oled!(
+        flush,
+        check,
+        address,
+        flushed_bytes,
+        start_scroll,
+        stop_scroll
+    );
+}
@@ -0,0 +1,339 @@
+//! Keeping a static screen from burning into the OLED.
+//!
+//! OLED pixels wear with the time they spend lit, so a greeting left up for
+//! days leaves its ghost behind. Three things spread the wear out:
+//!
+//! - The whole frame moves round [`SHIFTS`], a pixel at a time, every
+//!   [`SHIFT_MS`], so no edge sits on the same pixels for long.
+//! - Every [`INVERT_MS`] the frame swaps lit and dark, so the pixels that
+//!   were resting take a turn.
+//! - After [`SAVER_AFTER_MS`] without a button press a [`Saver`] takes over
+//!   the screen until the next press.
+//!
+//! [`Shifted`] does the moving and inverting as things are drawn, so it
+//! works the same on every controller and survives an init. [`Guard`] keeps
+//! the time and says when the screen needs drawing again.
+
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*, Pixel};
+
+use crate::{
+    display::{forward_oled, Oled},
+    error::Error,
+};
+
+/// Offsets the frame moves through, one step every [`SHIFT_MS`]: round the
+/// pixels next to where it was drawn, and back.
+pub const SHIFTS: [Point; 9] = [
+    Point::new(0, 0),
+    Point::new(1, 0),
+    Point::new(1, 1),
+    Point::new(0, 1),
+    Point::new(-1, 1),
+    Point::new(-1, 0),
+    Point::new(-1, -1),
+    Point::new(0, -1),
+    Point::new(1, -1),
+];
+
+/// How long the frame stays at each offset.
+pub const SHIFT_MS: u32 = 5 * 60_000;
+
+/// How long the frame stays the right way round, or inverted.
+pub const INVERT_MS: u32 = 60 * 60_000;
+
+/// How long without a button press before the screensaver starts.
+pub const SAVER_AFTER_MS: u32 = 10 * 60_000;
+
+/// What takes over the screen when it's left alone.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Saver {
+    /// Leave the screen up; shifting and inverting still go on.
+    Off,
+    /// Nothing lit.
+    Blank,
+    /// A heart bouncing round the screen.
+    #[default]
+    Logo,
+    /// Time since power-up, moving a pixel a second.
+    Clock,
+}
+
+impl Saver {
+    /// Every screensaver, in the order the settings screen goes through them.
+    pub const ALL: [Saver; 4] = [Saver::Off, Saver::Blank, Saver::Logo, Saver::Clock];
+
+    /// What the settings screen calls it.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Saver::Off => "off",
+            Saver::Blank => "blank",
+            Saver::Logo => "logo",
+            Saver::Clock => "clock",
+        }
+    }
+
+    /// How often it draws a new frame, for those that move.
+    pub const fn frame_ms(self) -> Option<u32> {
+        match self {
+            Saver::Off | Saver::Blank => None,
+            Saver::Logo => Some(50),
+            Saver::Clock => Some(1000),
+        }
+    }
+
+    /// The next one in [`ALL`](Saver::ALL), going round.
+    pub const fn next(self) -> Saver {
+        match self {
+            Saver::Off => Saver::Blank,
+            Saver::Blank => Saver::Logo,
+            Saver::Logo => Saver::Clock,
+            Saver::Clock => Saver::Off,
+        }
+    }
+
+    /// Its position in [`ALL`](Saver::ALL), as it's stored in flash.
+    pub const fn index(self) -> u8 {
+        self as u8
+    }
+
+    /// The screensaver at `index` in [`ALL`](Saver::ALL).
+    pub const fn from_index(index: u8) -> Option<Saver> {
+        match index {
+            0 => Some(Saver::Off),
+            1 => Some(Saver::Blank),
+            2 => Some(Saver::Logo),
+            3 => Some(Saver::Clock),
+            _ => None,
+        }
+    }
+}
+
+/// Where something moving a pixel a step across `room` spare pixels is after
+/// `step` steps, turning round at each end.
+pub const fn bounce(step: u32, room: u32) -> u32 {
+    if room == 0 {
+        return 0;
+    }
+    let t = step % (2 * room);
+    if t <= room {
+        t
+    } else {
+        2 * room - t
+    }
+}
+
+/// What [`Guard::poll`] says needs doing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Due {
+    Nothing,
+    /// The offset or inversion has changed: draw the screen again.
+    Redraw,
+    /// Draw the screensaver's next frame.
+    Saver,
+}
+
+/// Keeps the time for shifting, inverting and the screensaver.
+#[derive(Clone, Copy, Debug)]
+pub struct Guard {
+    saver: Saver,
+    last_input_ms: u32,
+    shift: usize,
+    shifted_ms: u32,
+    inverted: bool,
+    inverted_ms: u32,
+    saving: bool,
+    /// Frames of the screensaver drawn since it started.
+    frames: u32,
+    frame_ms: u32,
+}
+
+impl Guard {
+    /// Start the clocks at `now_ms`, with `saver` for when it's left alone.
+    pub const fn new(saver: Saver, now_ms: u32) -> Self {
+        Self {
+            saver,
+            last_input_ms: now_ms,
+            shift: 0,
+            shifted_ms: now_ms,
+            inverted: false,
+            inverted_ms: now_ms,
+            saving: false,
+            frames: 0,
+            frame_ms: now_ms,
+        }
+    }
+
+    /// The screensaver for when it's left alone.
+    pub const fn saver(&self) -> Saver {
+        self.saver
+    }
+
+    /// Use `saver` from the next time it's left alone.
+    pub fn set_saver(&mut self, saver: Saver) {
+        self.saver = saver;
+    }
+
+    /// Whether the screensaver is showing.
+    pub const fn is_saving(&self) -> bool {
+        self.saving
+    }
+
+    /// Frames of the screensaver drawn so far, counting the one due.
+    pub const fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Where the frame should be drawn from. The screensaver moves by
+    /// itself, so it isn't shifted.
+    pub const fn offset(&self) -> Point {
+        if self.saving {
+            Point::zero()
+        } else {
+            SHIFTS[self.shift]
+        }
+    }
+
+    /// Whether the frame should be drawn inverted. The screensaver never is:
+    /// a blank screen would light up.
+    pub const fn inverted(&self) -> bool {
+        self.inverted && !self.saving
+    }
+
+    /// A button was pressed at `now_ms`. Returns whether that ends the
+    /// screensaver, in which case the press should only bring the screen
+    /// back.
+    pub fn input(&mut self, now_ms: u32) -> bool {
+        self.last_input_ms = now_ms;
+        core::mem::take(&mut self.saving)
+    }
+
+    /// How long until [`poll`](Self::poll) has something to draw, if nothing
+    /// is pressed meanwhile.
+    pub fn due_in_ms(&self, now_ms: u32) -> u32 {
+        let left =
+            |since_ms: u32, period_ms: u32| period_ms.saturating_sub(now_ms.wrapping_sub(since_ms));
+        let shift = left(self.shifted_ms, SHIFT_MS);
+        let invert = left(self.inverted_ms, INVERT_MS);
+        let saver = match (self.saving, self.saver.frame_ms()) {
+            (true, Some(frame_ms)) => left(self.frame_ms, frame_ms),
+            (true, None) => u32::MAX,
+            (false, _) if self.saver == Saver::Off => u32::MAX,
+            (false, _) => left(self.last_input_ms, SAVER_AFTER_MS),
+        };
+        shift.min(invert).min(saver)
+    }
+
+    /// Check the time, and say what needs drawing.
+    pub fn poll(&mut self, now_ms: u32) -> Due {
+        let mut redraw = false;
+        if now_ms.wrapping_sub(self.shifted_ms) >= SHIFT_MS {
+            self.shift = (self.shift + 1) % SHIFTS.len();
+            self.shifted_ms = now_ms;
+            redraw = true;
+        }
+        if now_ms.wrapping_sub(self.inverted_ms) >= INVERT_MS {
+            self.inverted = !self.inverted;
+            self.inverted_ms = now_ms;
+            redraw = true;
+        }
+
+        if self.saving {
+            return match self.saver.frame_ms() {
+                Some(frame_ms) if now_ms.wrapping_sub(self.frame_ms) >= frame_ms => {
+                    self.frames += 1;
+                    self.frame_ms = now_ms;
+                    Due::Saver
+                }
+                _ => Due::Nothing,
+            };
+        }
+        if self.saver != Saver::Off && now_ms.wrapping_sub(self.last_input_ms) >= SAVER_AFTER_MS {
+            self.saving = true;
+            self.frames = 0;
+            self.frame_ms = now_ms;
+            return Due::Saver;
+        }
+        if redraw {
+            Due::Redraw
+        } else {
+            Due::Nothing
+        }
+    }
+}
+
+/// An OLED whose frame is drawn moved by an offset, and inverted if need
+/// be. Whatever is moved off one edge is lost, and the strip left at the
+/// other is the background colour. Changes show from the next drawing on.
+#[derive(Debug)]
+pub struct Shifted<D> {
+    display: D,
+    offset: Point,
+    inverted: bool,
+}
+
+impl<D: Oled> Shifted<D> {
+    /// `display`, drawn as it is until told otherwise.
+    pub const fn new(display: D) -> Self {
+        Self {
+            display,
+            offset: Point::zero(),
+            inverted: false,
+        }
+    }
+
+    /// Draw everything moved by `offset`, inverted if `inverted`.
+    pub fn set(&mut self, offset: Point, inverted: bool) {
+        self.offset = offset;
+        self.inverted = inverted;
+    }
+
+    /// The display underneath.
+    pub fn inner(&mut self) -> &mut D {
+        &mut self.display
+    }
+}
+
+impl<D: Oled> OriginDimensions for Shifted<D> {
+    fn size(&self) -> Size {
+        self.display.bounding_box().size
+    }
+}
+
+impl<D: Oled> DrawTarget for Shifted<D> {
+    type Color = BinaryColor;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let (offset, inverted) = (self.offset, self.inverted);
+        self.display
+            .draw_iter(pixels.into_iter().map(|Pixel(point, color)| {
+                let color = if inverted { color.invert() } else { color };
+                Pixel(point + offset, color)
+            }))
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        let color = if self.inverted { color.invert() } else { color };
+        self.display.clear(color)
+    }
+}
+
+impl<D: Oled> Oled for Shifted<D> {
+    fn init(&mut self) -> Result<(), Error> {
+        self.display.init()
+    }
+
+    forward_oled!(
+        flush,
+        check,
+        address,
+        flushed_bytes,
+        set_brightness,
+        start_scroll,
+        stop_scroll,
+        set_display_on
+    );
+}
@@ -0,0 +1,109 @@
+//! The I2C bus the OLED sits on, shared and diagnosed.
+//!
+//! The SSD1306 driver owns its interface and turns every bus error into a bare
+//! `BusWriteError`, which can't tell an unplugged module from a dead one.
+//! [`Shared`] lets the driver and the rest of the firmware take turns on the
+//! same bus, and [`Diagnose`] lets a bus say what its errors mean, so
+//! [`check`] can report an [`Error`] a technician can act on.
+
+use core::cell::RefCell;
+
+use embedded_hal::blocking::i2c::{Read, Write};
+
+use crate::error::Error;
+
+/// The addresses an SSD1306 module can be strapped to, the usual one first.
+pub const ADDRESSES: [u8; 2] = [0x3C, 0x3D];
+
+/// SSD1306 "no operation" command, harmless to send at any time.
+const NOP: u8 = 0xE3;
+
+/// What went wrong on an I2C transfer, as far as the bus can tell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusFault {
+    /// Nothing acknowledged the address.
+    AddressNack,
+    /// The address was acknowledged but a data byte was not.
+    DataNack,
+    /// The transfer did not finish in time.
+    Timeout,
+    /// SDA or SCL is held low.
+    Stuck,
+    /// Anything else the bus reported.
+    Other,
+}
+
+/// An I2C bus that can explain its errors.
+pub trait Diagnose: Write {
+    /// What `error`, returned by this bus, means.
+    fn diagnose(error: &<Self as Write>::Error) -> BusFault;
+}
+
+/// A bus borrowed from a `RefCell`, so the display driver can own a handle
+/// while others keep one too. Each transfer borrows the bus for its duration.
+#[derive(Debug)]
+pub struct Shared<'a, I>(pub &'a RefCell<I>);
+
+impl<I: Write> Write for Shared<'_, I> {
+    type Error = I::Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.0.borrow_mut().write(address, bytes)
+    }
+}
+
+/// The display controller, going by the status byte it reads back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Controller {
+    Ssd1306,
+    /// The 1.3" modules' controller: takes most SSD1306 commands but has
+    /// 132 columns and no horizontal addressing mode.
+    Sh1106,
+    /// Something that doesn't read back a status we recognise. Clones vary,
+    /// so this is not treated as an error.
+    Unknown,
+}
+
+impl Controller {
+    /// Identify a controller from its status byte. Bit 6 is display on/off
+    /// on both; the low nibble reads back 0x3, 0x6 or 0x7 on SSD1306 modules
+    /// and 0x8 on SH1106 ones.
+    pub const fn from_status(status: u8) -> Self {
+        match status & 0x0F {
+            0x03 | 0x06 | 0x07 => Controller::Ssd1306,
+            0x08 => Controller::Sh1106,
+            _ => Controller::Unknown,
+        }
+    }
+}
+
+/// The status byte a display controller reads back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Status(pub u8);
+
+impl Status {
+    /// Which controller sent it.
+    pub const fn controller(self) -> Controller {
+        Controller::from_status(self.0)
+    }
+
+    /// Whether the display is switched on. It comes up off after a reset, so
+    /// a display that was initialised and reads back off has lost power.
+    pub const fn is_display_on(self) -> bool {
+        self.0 & 0x40 == 0
+    }
+}
+
+/// Make sure a display controller is answering at `address`: address it with
+/// a no-op command (the same on the SSD1306 and SH1106), then read its status
+/// byte.
+pub fn check<I>(bus: &mut I, address: u8) -> Result<Status, Error>
+where
+    I: Diagnose + Read<Error = <I as Write>::Error>,
+{
+    let error = |e| Error::from_fault(I::diagnose(&e), address);
+    bus.write(address, &[0x00, NOP]).map_err(error)?;
+    let mut status = [0];
+    bus.read(address, &mut status).map_err(error)?;
+    Ok(Status(status[0]))
+}
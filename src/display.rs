@@ -0,0 +1,582 @@
+//! The OLED as seen by the rest of the crate.
+
+use core::cell::RefCell;
+
+use display_interface::{DisplayError, WriteOnlyDataCommand};
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+use embedded_hal::{
+    blocking::{
+        delay::DelayMs,
+        i2c::{Read, Write},
+        spi,
+    },
+    digital::v2::OutputPin,
+};
+use ssd1306::{mode::BufferedGraphicsMode, prelude::*, I2CDisplayInterface, Ssd1306};
+
+use crate::{
+    brightness::Drive,
+    bus::{self, Controller, Diagnose, Shared},
+    error::Error,
+    frame::FrameDriver,
+    marquee::Scroll,
+    sh1106::Sh1106,
+};
+
+/// The panel the firmware drives, picked with a `panel-*` cargo feature:
+/// 128x32 unless one of them is on. Turning on two won't compile.
+#[cfg(not(any(
+    feature = "panel-128x64",
+    feature = "panel-96x16",
+    feature = "panel-72x40",
+    feature = "panel-64x48"
+)))]
+pub type PanelSize = DisplaySize128x32;
+#[cfg(feature = "panel-128x64")]
+pub type PanelSize = DisplaySize128x64;
+#[cfg(feature = "panel-96x16")]
+pub type PanelSize = DisplaySize96x16;
+#[cfg(feature = "panel-72x40")]
+pub type PanelSize = DisplaySize72x40;
+#[cfg(feature = "panel-64x48")]
+pub type PanelSize = DisplaySize64x48;
+
+/// The size of [`PanelSize`] in pixels.
+pub const PANEL_SIZE: Size = Size::new(PanelSize::WIDTH as u32, PanelSize::HEIGHT as u32);
+
+/// The controller the firmware drives, if a `controller-*` cargo feature
+/// says; otherwise it goes by what the panel reports.
+#[cfg(not(any(feature = "controller-ssd1306", feature = "controller-sh1106")))]
+pub const CONTROLLER: Option<Controller> = None;
+#[cfg(feature = "controller-ssd1306")]
+pub const CONTROLLER: Option<Controller> = Some(Controller::Ssd1306);
+#[cfg(feature = "controller-sh1106")]
+pub const CONTROLLER: Option<Controller> = Some(Controller::Sh1106);
+
+/// A buffered monochrome display: draw into it, then `flush` to push the
+/// buffer out to the panel.
+pub trait Oled: DrawTarget<Color = BinaryColor> {
+    /// Send the controller's init sequence and clear the buffer.
+    fn init(&mut self) -> Result<(), Error>;
+
+    /// Push the parts of the buffer that changed since the last flush.
+    fn flush(&mut self) -> Result<(), DisplayError>;
+
+    /// Whether the panel is still there and still initialised. Displays that
+    /// can't tell always say [`Health::Ok`].
+    fn check(&mut self) -> Result<Health, Error> {
+        Ok(Health::Ok)
+    }
+
+    /// The I2C address the panel was found at, once it has been. Displays
+    /// that aren't looked for on a bus have none.
+    fn address(&self) -> Option<u8> {
+        None
+    }
+
+    /// Bytes of pixel data the last flush sent, for displays that count them.
+    fn flushed_bytes(&self) -> Option<usize> {
+        None
+    }
+
+    /// Set the panel's contrast and pre-charge. Displays without them do
+    /// nothing.
+    fn set_brightness(&mut self, drive: Drive) -> Result<(), DisplayError> {
+        let _ = drive;
+        Ok(())
+    }
+
+    /// Set the controller scrolling part of the panel by itself, and say
+    /// whether it is. Displays that can't return `false`, and whatever
+    /// should move has to be moved by redrawing it.
+    fn start_scroll(&mut self, scroll: Scroll) -> Result<bool, DisplayError> {
+        let _ = scroll;
+        Ok(false)
+    }
+
+    /// Stop the controller scrolling, leaving the panel as it got to. The
+    /// next flush sends the whole frame, as the scroll moved the controller's
+    /// RAM about.
+    fn stop_scroll(&mut self) -> Result<(), DisplayError> {
+        Ok(())
+    }
+
+    /// Turn the panel off, keeping what's in the controller's RAM, or back
+    /// on. Off, the controller stops its charge pump and draws next to
+    /// nothing. An init turns it on. Displays that can't do nothing.
+    fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        let _ = on;
+        Ok(())
+    }
+}
+
+/// The [`Oled`] methods of a wrapper that go straight through to the display
+/// in its `display` field: those listed, out of all but `init`, which every
+/// wrapper has its own reason to write out.
+macro_rules! forward_oled {
+    ($($method:ident),* $(,)?) => {
+        $(forward_oled!(@ $method);)*
+    };
+    (@ flush) => {
+        fn flush(&mut self) -> Result<(), display_interface::DisplayError> {
+            self.display.flush()
+        }
+    };
+    (@ check) => {
+        fn check(&mut self) -> Result<$crate::display::Health, $crate::error::Error> {
+            self.display.check()
+        }
+    };
+    (@ address) => {
+        fn address(&self) -> Option<u8> {
+            self.display.address()
+        }
+    };
+    (@ flushed_bytes) => {
+        fn flushed_bytes(&self) -> Option<usize> {
+            self.display.flushed_bytes()
+        }
+    };
+    (@ set_brightness) => {
+        fn set_brightness(
+            &mut self,
+            drive: $crate::brightness::Drive,
+        ) -> Result<(), display_interface::DisplayError> {
+            self.display.set_brightness(drive)
+        }
+    };
+    (@ start_scroll) => {
+        fn start_scroll(
+            &mut self,
+            scroll: $crate::marquee::Scroll,
+        ) -> Result<bool, display_interface::DisplayError> {
+            self.display.start_scroll(scroll)
+        }
+    };
+    (@ stop_scroll) => {
+        fn stop_scroll(&mut self) -> Result<(), display_interface::DisplayError> {
+            self.display.stop_scroll()
+        }
+    };
+    (@ set_display_on) => {
+        fn set_display_on(&mut self, on: bool) -> Result<(), display_interface::DisplayError> {
+            self.display.set_display_on(on)
+        }
+    };
+}
+pub(crate) use forward_oled;
+
+/// What [`Oled::check`] found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Health {
+    /// Answering and showing what was sent.
+    Ok,
+    /// Answering, but reset since it was initialised (it lost power for a
+    /// moment), so it needs `init` and a redraw.
+    Reset,
+}
+
+impl<DI, SIZE> Oled for Ssd1306<DI, SIZE, BufferedGraphicsMode<SIZE>>
+where
+    DI: WriteOnlyDataCommand,
+    SIZE: DisplaySize,
+{
+    fn init(&mut self) -> Result<(), Error> {
+        DisplayConfig::init(self).map_err(Error::DisplayInit)
+    }
+
+    fn flush(&mut self) -> Result<(), DisplayError> {
+        Ssd1306::flush(self)
+    }
+
+    fn set_brightness(&mut self, drive: Drive) -> Result<(), DisplayError> {
+        Ssd1306::set_brightness(self, Brightness::custom(drive.precharge, drive.contrast))
+    }
+}
+
+/// The SSD1306 driver type [`Panel`] wraps.
+pub type Driver<'a, I, SIZE> = FrameDriver<I2CInterface<Shared<'a, I>>, SIZE>;
+
+/// The SH1106 driver type [`Panel`] wraps.
+pub type Sh1106Driver<'a, I> = Sh1106<I2CInterface<Shared<'a, I>>>;
+
+/// The driver a [`Panel`] is using.
+// There's one panel and no heap to box a driver on, so the size difference
+// between the frame buffers is just RAM the panel always needs.
+#[allow(clippy::large_enum_variant)]
+enum Backend<'a, I, SIZE>
+where
+    SIZE: DisplaySize,
+{
+    Ssd1306(Driver<'a, I, SIZE>),
+    /// Always 128x64, whatever size the panel was built for.
+    Sh1106(Sh1106Driver<'a, I>),
+}
+
+impl<I: Write, SIZE: DisplaySize> Backend<'_, I, SIZE> {
+    fn controller(&self) -> Controller {
+        match self {
+            Backend::Ssd1306(_) => Controller::Ssd1306,
+            Backend::Sh1106(_) => Controller::Sh1106,
+        }
+    }
+}
+
+/// An OLED on a shared I2C bus. Unlike the bare drivers, it checks who is
+/// answering before sending the init sequence, so a failure comes back as a
+/// specific [`Error`] rather than a generic bus error. If nothing answers at
+/// its address it looks at the others in [`bus::ADDRESSES`] and moves to
+/// whichever does.
+///
+/// The status byte also says which controller it is, and the panel drives an
+/// SH1106 with [`Sh1106`] and anything else as an SSD1306, unless it has been
+/// told to expect one of them [`only`](Panel::only).
+pub struct Panel<'a, I, SIZE>
+where
+    SIZE: DisplaySize,
+{
+    bus: &'a RefCell<I>,
+    address: u8,
+    /// Whether something has answered at `address`.
+    found: bool,
+    /// Whether the panel was last turned on, so that off isn't taken for a
+    /// reset.
+    display_on: bool,
+    /// The controller it was told to expect, if any.
+    only: Option<Controller>,
+    size: SIZE,
+    backend: Backend<'a, I, SIZE>,
+}
+
+impl<'a, I, SIZE> Panel<'a, I, SIZE>
+where
+    I: Write,
+    SIZE: DisplaySize + Copy,
+{
+    /// A panel of `size` on `bus`, not yet initialised, that tries
+    /// `address` first.
+    pub fn new(bus: &'a RefCell<I>, address: u8, size: SIZE) -> Self {
+        Self {
+            bus,
+            address,
+            found: false,
+            display_on: true,
+            only: None,
+            backend: Backend::Ssd1306(driver(bus, address, size)),
+            size,
+        }
+    }
+
+    /// Only drive `controller`, and report anything else that answers as
+    /// [`Error::WrongController`]. `None` goes back to detecting it.
+    pub fn only(mut self, controller: Option<Controller>) -> Self {
+        self.only = controller;
+        if let Some(controller) = controller {
+            self.select(controller);
+        }
+        self
+    }
+
+    /// The controller being driven.
+    pub fn controller(&self) -> Controller {
+        self.backend.controller()
+    }
+
+    /// Drive `controller` at the current address from now on.
+    fn select(&mut self, controller: Controller) {
+        self.backend = match controller {
+            Controller::Sh1106 => Backend::Sh1106(Sh1106::new(
+                I2CDisplayInterface::new_custom_address(Shared(self.bus), self.address),
+            )),
+            _ => Backend::Ssd1306(driver(self.bus, self.address, self.size)),
+        };
+    }
+}
+
+impl<I, SIZE> Panel<'_, I, SIZE>
+where
+    I: Diagnose + Read<Error = <I as Write>::Error>,
+    SIZE: DisplaySize + Copy,
+{
+    /// Check the panel's address, falling back to the other addresses if
+    /// nothing answers there, and pick the driver for whatever did. An error
+    /// is for the address tried first.
+    fn find(&mut self) -> Result<bus::Status, Error> {
+        let mut checked = bus::check(&mut *self.bus.borrow_mut(), self.address);
+        if matches!(checked, Err(Error::AddressNack { .. })) {
+            for address in bus::ADDRESSES {
+                if address == self.address {
+                    continue;
+                }
+                let other = bus::check(&mut *self.bus.borrow_mut(), address);
+                if !matches!(other, Err(Error::AddressNack { .. })) {
+                    self.address = address;
+                    self.select(self.controller());
+                    checked = other;
+                    break;
+                }
+            }
+        }
+        self.found = !matches!(checked, Err(Error::AddressNack { .. }));
+        let status = checked?;
+        let wanted = self.wanted(status)?;
+        if wanted != self.controller() {
+            self.select(wanted);
+        }
+        Ok(status)
+    }
+
+    /// The controller to drive, going by `status`.
+    fn wanted(&self, status: bus::Status) -> Result<Controller, Error> {
+        match (self.only, status.controller()) {
+            // Clones vary, so an unrecognised status isn't held against it.
+            (Some(only), found) if found != only && found != Controller::Unknown => {
+                Err(Error::WrongController { status: status.0 })
+            }
+            (Some(only), _) => Ok(only),
+            (None, Controller::Sh1106) => Ok(Controller::Sh1106),
+            (None, _) => Ok(Controller::Ssd1306),
+        }
+    }
+}
+
+fn driver<I: Write, SIZE: DisplaySize>(
+    bus: &RefCell<I>,
+    address: u8,
+    size: SIZE,
+) -> Driver<'_, I, SIZE> {
+    FrameDriver::new(
+        I2CDisplayInterface::new_custom_address(Shared(bus), address),
+        size,
+    )
+}
+
+impl<I, SIZE> DrawTarget for Panel<'_, I, SIZE>
+where
+    I: Write,
+    SIZE: DisplaySize,
+{
+    type Color = BinaryColor;
+    type Error = DisplayError;
+
+    fn draw_iter<P>(&mut self, pixels: P) -> Result<(), Self::Error>
+    where
+        P: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        match &mut self.backend {
+            Backend::Ssd1306(driver) => driver.draw_iter(pixels),
+            Backend::Sh1106(driver) => driver.draw_iter(pixels),
+        }
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        match &mut self.backend {
+            Backend::Ssd1306(driver) => driver.clear(color),
+            Backend::Sh1106(driver) => driver.clear(color),
+        }
+    }
+}
+
+impl<I, SIZE> OriginDimensions for Panel<'_, I, SIZE>
+where
+    I: Write,
+    SIZE: DisplaySize,
+{
+    fn size(&self) -> Size {
+        match &self.backend {
+            Backend::Ssd1306(driver) => driver.size(),
+            Backend::Sh1106(driver) => driver.size(),
+        }
+    }
+}
+
+impl<I, SIZE> Oled for Panel<'_, I, SIZE>
+where
+    I: Diagnose + Read<Error = <I as Write>::Error>,
+    SIZE: DisplaySize + Copy,
+{
+    fn init(&mut self) -> Result<(), Error> {
+        let checked = self.find();
+        match checked {
+            Ok(_) => {}
+            // Worth initialising anyway so the error can go on screen.
+            Err(ref error) if error.can_show_text() => {}
+            Err(error) => return Err(error),
+        }
+        match &mut self.backend {
+            Backend::Ssd1306(driver) => Oled::init(driver)?,
+            Backend::Sh1106(driver) => Oled::init(driver)?,
+        }
+        self.display_on = true;
+        checked.map(drop)
+    }
+
+    fn flush(&mut self) -> Result<(), DisplayError> {
+        match &mut self.backend {
+            Backend::Ssd1306(driver) => driver.flush(),
+            Backend::Sh1106(driver) => driver.flush(),
+        }
+    }
+
+    fn check(&mut self) -> Result<Health, Error> {
+        let status = bus::check(&mut *self.bus.borrow_mut(), self.address)?;
+        // A different module plugged in needs initialising as itself.
+        if self.wanted(status)? != self.controller() || status.is_display_on() != self.display_on {
+            Ok(Health::Reset)
+        } else {
+            Ok(Health::Ok)
+        }
+    }
+
+    fn address(&self) -> Option<u8> {
+        self.found.then_some(self.address)
+    }
+
+    fn flushed_bytes(&self) -> Option<usize> {
+        match &self.backend {
+            Backend::Ssd1306(driver) => driver.flushed_bytes(),
+            Backend::Sh1106(driver) => driver.flushed_bytes(),
+        }
+    }
+
+    fn set_brightness(&mut self, drive: Drive) -> Result<(), DisplayError> {
+        match &mut self.backend {
+            Backend::Ssd1306(driver) => Oled::set_brightness(driver, drive),
+            Backend::Sh1106(driver) => Oled::set_brightness(driver, drive),
+        }
+    }
+
+    fn start_scroll(&mut self, scroll: Scroll) -> Result<bool, DisplayError> {
+        match &mut self.backend {
+            Backend::Ssd1306(driver) => Oled::start_scroll(driver, scroll),
+            Backend::Sh1106(driver) => Oled::start_scroll(driver, scroll),
+        }
+    }
+
+    fn stop_scroll(&mut self) -> Result<(), DisplayError> {
+        match &mut self.backend {
+            Backend::Ssd1306(driver) => Oled::stop_scroll(driver),
+            Backend::Sh1106(driver) => Oled::stop_scroll(driver),
+        }
+    }
+
+    fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        match &mut self.backend {
+            Backend::Ssd1306(driver) => Oled::set_display_on(driver, on)?,
+            Backend::Sh1106(driver) => Oled::set_display_on(driver, on)?,
+        }
+        self.display_on = on;
+        Ok(())
+    }
+}
+
+/// The SSD1306 driver type [`SpiPanel`] wraps.
+pub type SpiDriver<SPI, DC, CS, SIZE> = FrameDriver<SPIInterface<SPI, DC, CS>, SIZE>;
+
+/// An SSD1306 on SPI, with its reset line. Nothing on SPI acknowledges and
+/// the panel can't be read back, so unlike [`Panel`] it can't tell whether a
+/// panel is there or has lost its settings. Instead it pulses RST before
+/// every init, so each retry starts the controller from power-on.
+pub struct SpiPanel<SPI, DC, CS, RST, DL, SIZE>
+where
+    SIZE: DisplaySize,
+{
+    driver: SpiDriver<SPI, DC, CS, SIZE>,
+    reset: RST,
+    delay: DL,
+}
+
+impl<SPI, DC, CS, RST, DL, SIZE> SpiPanel<SPI, DC, CS, RST, DL, SIZE>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    CS: OutputPin,
+    RST: OutputPin,
+    DL: DelayMs<u8>,
+    SIZE: DisplaySize,
+{
+    /// A panel of `size` on `spi`, not yet initialised, with its D/C, chip
+    /// select and reset lines. `delay` times the reset pulse.
+    pub fn new(spi: SPI, dc: DC, cs: CS, reset: RST, delay: DL, size: SIZE) -> Self {
+        Self {
+            driver: FrameDriver::new(SPIInterface::new(spi, dc, cs), size),
+            reset,
+            delay,
+        }
+    }
+}
+
+impl<SPI, DC, CS, RST, DL, SIZE> DrawTarget for SpiPanel<SPI, DC, CS, RST, DL, SIZE>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    CS: OutputPin,
+    SIZE: DisplaySize,
+{
+    type Color = BinaryColor;
+    type Error = DisplayError;
+
+    fn draw_iter<P>(&mut self, pixels: P) -> Result<(), Self::Error>
+    where
+        P: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.driver.draw_iter(pixels)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.driver.clear(color)
+    }
+}
+
+impl<SPI, DC, CS, RST, DL, SIZE> OriginDimensions for SpiPanel<SPI, DC, CS, RST, DL, SIZE>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    CS: OutputPin,
+    SIZE: DisplaySize,
+{
+    fn size(&self) -> Size {
+        self.driver.size()
+    }
+}
+
+impl<SPI, DC, CS, RST, DL, SIZE> Oled for SpiPanel<SPI, DC, CS, RST, DL, SIZE>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    CS: OutputPin,
+    RST: OutputPin,
+    DL: DelayMs<u8>,
+    SIZE: DisplaySize + Copy,
+{
+    fn init(&mut self) -> Result<(), Error> {
+        self.driver
+            .reset(&mut self.reset, &mut self.delay)
+            .map_err(Error::DisplayInit)?;
+        Oled::init(&mut self.driver)
+    }
+
+    fn flush(&mut self) -> Result<(), DisplayError> {
+        self.driver.flush()
+    }
+
+    fn flushed_bytes(&self) -> Option<usize> {
+        self.driver.flushed_bytes()
+    }
+
+    fn set_brightness(&mut self, drive: Drive) -> Result<(), DisplayError> {
+        Oled::set_brightness(&mut self.driver, drive)
+    }
+
+    fn start_scroll(&mut self, scroll: Scroll) -> Result<bool, DisplayError> {
+        Oled::start_scroll(&mut self.driver, scroll)
+    }
+
+    fn stop_scroll(&mut self) -> Result<(), DisplayError> {
+        Oled::stop_scroll(&mut self.driver)
+    }
+
+    fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        Oled::set_display_on(&mut self.driver, on)
+    }
+}
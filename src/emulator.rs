@@ -0,0 +1,222 @@
+//! An in-memory stand-in for the SSD1306 in buffered graphics mode.
+//!
+//! [`Framebuffer`] keeps the same page-ordered buffer as the real driver and
+//! only shows what was drawn once it is flushed, so a screen that forgets to
+//! call `flush` comes out blank here too. The flushed frame can be dumped as
+//! ASCII art (handy for golden files), PBM or PNG.
+
+use std::{io, string::String, vec, vec::Vec};
+
+use display_interface::DisplayError;
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+
+use crate::{brightness::Drive, display::Oled, error::Error};
+
+/// The panel the firmware drives: 128x32.
+pub const DEFAULT_SIZE: Size = Size::new(128, 32);
+
+/// Character used for a lit pixel in [`Framebuffer::to_ascii`].
+pub const ASCII_ON: char = '#';
+/// Character used for a dark pixel in [`Framebuffer::to_ascii`].
+pub const ASCII_OFF: char = '.';
+
+/// A monochrome OLED living in memory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Framebuffer {
+    size: Size,
+    /// What has been drawn, laid out like SSD1306 GDDRAM: one byte per column
+    /// per 8-row page, least significant bit at the top.
+    buffer: Vec<u8>,
+    /// What the panel is showing, as of the last flush.
+    screen: Vec<u8>,
+    initialised: bool,
+    flushes: usize,
+    brightness: Option<Drive>,
+    display_on: bool,
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new(DEFAULT_SIZE)
+    }
+}
+
+impl Framebuffer {
+    /// A blank, uninitialised panel. The height is rounded up to whole pages.
+    pub fn new(size: Size) -> Self {
+        let len = size.width as usize * size.height.div_ceil(8) as usize;
+        Self {
+            size,
+            buffer: vec![0; len],
+            screen: vec![0; len],
+            initialised: false,
+            flushes: 0,
+            brightness: None,
+            display_on: false,
+        }
+    }
+
+    /// The drawing buffer, in GDDRAM byte order.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// What the panel showed after the last flush, in GDDRAM byte order.
+    pub fn screen(&self) -> &[u8] {
+        &self.screen
+    }
+
+    /// Whether `init` has been called.
+    pub fn is_initialised(&self) -> bool {
+        self.initialised
+    }
+
+    /// How many flushes have reached the panel.
+    pub fn flushes(&self) -> usize {
+        self.flushes
+    }
+
+    /// The brightness last set since `init`, or `None` for the driver's
+    /// default.
+    pub fn brightness(&self) -> Option<Drive> {
+        self.brightness
+    }
+
+    /// Whether the panel is on: initialised and not turned off since. Off,
+    /// it keeps what it was showing, but nothing is lit.
+    pub fn is_display_on(&self) -> bool {
+        self.display_on
+    }
+
+    /// Whether the pixel at `point` is lit on the panel. Off-screen points are dark.
+    pub fn pixel(&self, point: Point) -> bool {
+        self.index(point)
+            .is_some_and(|(idx, bit)| self.screen[idx] & (1 << bit) != 0)
+    }
+
+    /// The panel as text, one line per row, using [`ASCII_ON`] and [`ASCII_OFF`].
+    pub fn to_ascii(&self) -> String {
+        let mut out =
+            String::with_capacity((self.size.width as usize + 1) * self.size.height as usize);
+        for y in 0..self.size.height as i32 {
+            for x in 0..self.size.width as i32 {
+                out.push(if self.pixel(Point::new(x, y)) {
+                    ASCII_ON
+                } else {
+                    ASCII_OFF
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Write the panel as a binary (P4) PBM. Lit pixels are white, as on the OLED.
+    pub fn write_pbm<W: io::Write>(&self, mut out: W) -> io::Result<()> {
+        write!(out, "P4\n{} {}\n", self.size.width, self.size.height)?;
+        let mut row = vec![0u8; self.size.width.div_ceil(8) as usize];
+        for y in 0..self.size.height as i32 {
+            row.fill(0);
+            for x in 0..self.size.width as i32 {
+                // In PBM a set bit is black.
+                if !self.pixel(Point::new(x, y)) {
+                    row[x as usize / 8] |= 0x80 >> (x % 8);
+                }
+            }
+            out.write_all(&row)?;
+        }
+        Ok(())
+    }
+
+    /// Write the panel as an 8-bit greyscale PNG.
+    pub fn write_png<W: io::Write>(&self, out: W) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(out, self.size.width, self.size.height);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+
+        let mut data = Vec::with_capacity(self.size.width as usize * self.size.height as usize);
+        for y in 0..self.size.height as i32 {
+            for x in 0..self.size.width as i32 {
+                data.push(if self.pixel(Point::new(x, y)) {
+                    0xff
+                } else {
+                    0x00
+                });
+            }
+        }
+        writer.write_image_data(&data)
+    }
+
+    fn index(&self, point: Point) -> Option<(usize, u32)> {
+        let Point { x, y } = point;
+        if x < 0 || y < 0 || x >= self.size.width as i32 || y >= self.size.height as i32 {
+            return None;
+        }
+        let idx = (y as usize / 8) * self.size.width as usize + x as usize;
+        Some((idx, y as u32 % 8))
+    }
+}
+
+impl DrawTarget for Framebuffer {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if let Some((idx, bit)) = self.index(point) {
+                if color.is_on() {
+                    self.buffer[idx] |= 1 << bit;
+                } else {
+                    self.buffer[idx] &= !(1 << bit);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.buffer.fill(if color.is_on() { 0xff } else { 0x00 });
+        Ok(())
+    }
+}
+
+impl OriginDimensions for Framebuffer {
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl Oled for Framebuffer {
+    fn init(&mut self) -> Result<(), Error> {
+        // Like the driver, init keeps the buffer and leaves the panel alone
+        // until the next flush.
+        self.initialised = true;
+        self.brightness = None;
+        self.display_on = true;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), DisplayError> {
+        if !self.initialised {
+            // The controller is still off and ignores the data.
+            return Ok(());
+        }
+        self.screen.copy_from_slice(&self.buffer);
+        self.flushes += 1;
+        Ok(())
+    }
+
+    fn set_brightness(&mut self, drive: Drive) -> Result<(), DisplayError> {
+        self.brightness = Some(drive);
+        Ok(())
+    }
+
+    fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        self.display_on = on && self.initialised;
+        Ok(())
+    }
+}
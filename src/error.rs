@@ -0,0 +1,90 @@
+//! Errors that stop the boot sequence.
+//!
+//! Each one has a blink [`code`](Error::code) for the LED matrix, which works
+//! even with nothing on the bus, and a message and hint for the OLED when it
+//! can still show text.
+
+use core::fmt;
+
+use display_interface::DisplayError;
+
+use crate::bus::BusFault;
+
+/// Why the device could not finish booting.
+#[derive(Clone, Debug)]
+pub enum Error {
+    /// Nothing answered at the OLED's address: unplugged, miswired, or set
+    /// to the other address.
+    AddressNack { address: u8 },
+    /// The OLED answered its address but dropped out mid-transfer, usually a
+    /// brown-out or a loose connection.
+    DataNack,
+    /// SDA or SCL is held low: a short, missing pull-ups, or a device stuck
+    /// mid-transfer.
+    BusStuck,
+    /// A transfer never finished, usually because the module isn't powered.
+    BusTimeout,
+    /// Something answered, but it's not the controller the firmware was
+    /// built for.
+    WrongController {
+        /// The status byte it read back.
+        status: u8,
+    },
+    /// The OLED did not accept its initialisation sequence.
+    DisplayInit(DisplayError),
+}
+
+impl Error {
+    /// The error for a failed transfer to `address`.
+    pub const fn from_fault(fault: BusFault, address: u8) -> Self {
+        match fault {
+            BusFault::AddressNack => Error::AddressNack { address },
+            BusFault::DataNack => Error::DataNack,
+            BusFault::Timeout => Error::BusTimeout,
+            BusFault::Stuck => Error::BusStuck,
+            BusFault::Other => Error::DisplayInit(DisplayError::BusWriteError),
+        }
+    }
+
+    /// How many times the X blinks before each pause.
+    pub const fn code(&self) -> u8 {
+        match self {
+            Error::AddressNack { .. } => 1,
+            Error::DataNack => 2,
+            Error::BusStuck => 3,
+            Error::BusTimeout => 4,
+            Error::WrongController { .. } => 5,
+            Error::DisplayInit(_) => 6,
+        }
+    }
+
+    /// What to check first. Fits on one line of the OLED.
+    pub const fn hint(&self) -> &'static str {
+        match self {
+            Error::AddressNack { .. } => "check wiring/address",
+            Error::DataNack | Error::BusTimeout | Error::DisplayInit(_) => "check power supply",
+            Error::BusStuck => "check for shorts",
+            Error::WrongController { .. } => "check display type",
+        }
+    }
+
+    /// Whether the OLED might still show text after this error: it answered
+    /// and the bus works, it just didn't do what was expected.
+    pub const fn can_show_text(&self) -> bool {
+        matches!(self, Error::WrongController { .. } | Error::DisplayInit(_))
+    }
+}
+
+/// A short description that fits on one line of the OLED.
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::AddressNack { address } => write!(f, "no reply at 0x{address:02X}"),
+            Error::DataNack => f.write_str("data not acked"),
+            Error::BusStuck => f.write_str("SDA/SCL held low"),
+            Error::BusTimeout => f.write_str("bus timed out"),
+            Error::WrongController { status } => write!(f, "wrong controller 0x{status:02X}"),
+            Error::DisplayInit(_) => f.write_str("init failed"),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! A 5x5 font for the LED matrix: digits, capital letters and enough
+//! punctuation for status codes and sensor readings.
+//!
+//! Glyphs sit against the left edge of the pattern; [`width`] says how many
+//! columns they use, so text can be packed with a one-column gap.
+
+use crate::patterns::Pattern;
+
+/// `0` to `9`.
+pub const DIGITS: [Pattern; 10] = [
+    // 0
+    Pattern::from_rows([".##..", "#..#.", "#..#.", "#..#.", ".##.."]),
+    // 1
+    Pattern::from_rows([".#...", "##...", ".#...", ".#...", "###.."]),
+    // 2
+    Pattern::from_rows(["###..", "...#.", ".##..", "#....", "####."]),
+    // 3
+    Pattern::from_rows(["####.", "...#.", "..#..", "#..#.", ".##.."]),
+    // 4
+    Pattern::from_rows(["..##.", ".#.#.", "#..#.", "#####", "...#."]),
+    // 5
+    Pattern::from_rows(["#####", "#....", "####.", "....#", "####."]),
+    // 6
+    Pattern::from_rows(["...#.", "..#..", ".###.", "#...#", ".###."]),
+    // 7
+    Pattern::from_rows(["#####", "...#.", "..#..", ".#...", "#...."]),
+    // 8
+    Pattern::from_rows([".###.", "#...#", ".###.", "#...#", ".###."]),
+    // 9
+    Pattern::from_rows([".###.", "#...#", ".###.", "..#..", ".#..."]),
+];
+
+/// `A` to `Z`.
+pub const LETTERS: [Pattern; 26] = [
+    // A
+    Pattern::from_rows([".##..", "#..#.", "####.", "#..#.", "#..#."]),
+    // B
+    Pattern::from_rows(["###..", "#..#.", "###..", "#..#.", "###.."]),
+    // C
+    Pattern::from_rows([".###.", "#....", "#....", "#....", ".###."]),
+    // D
+    Pattern::from_rows(["###..", "#..#.", "#..#.", "#..#.", "###.."]),
+    // E
+    Pattern::from_rows(["####.", "#....", "###..", "#....", "####."]),
+    // F
+    Pattern::from_rows(["####.", "#....", "###..", "#....", "#...."]),
+    // G
+    Pattern::from_rows([".###.", "#....", "#..##", "#...#", ".###."]),
+    // H
+    Pattern::from_rows(["#..#.", "#..#.", "####.", "#..#.", "#..#."]),
+    // I
+    Pattern::from_rows(["###..", ".#...", ".#...", ".#...", "###.."]),
+    // J
+    Pattern::from_rows(["#####", "...#.", "...#.", "#..#.", ".##.."]),
+    // K
+    Pattern::from_rows(["#..#.", "#.#..", "##...", "#.#..", "#..#."]),
+    // L
+    Pattern::from_rows(["#....", "#....", "#....", "#....", "####."]),
+    // M
+    Pattern::from_rows(["#...#", "##.##", "#.#.#", "#...#", "#...#"]),
+    // N
+    Pattern::from_rows(["#...#", "##..#", "#.#.#", "#..##", "#...#"]),
+    // O
+    Pattern::from_rows([".##..", "#..#.", "#..#.", "#..#.", ".##.."]),
+    // P
+    Pattern::from_rows(["###..", "#..#.", "###..", "#....", "#...."]),
+    // Q
+    Pattern::from_rows([".##..", "#..#.", "#..#.", ".##..", "...##"]),
+    // R
+    Pattern::from_rows(["###..", "#..#.", "###..", "#.#..", "#..#."]),
+    // S
+    Pattern::from_rows([".###.", "#....", ".##..", "...#.", "###.."]),
+    // T
+    Pattern::from_rows(["#####", "..#..", "..#..", "..#..", "..#.."]),
+    // U
+    Pattern::from_rows(["#..#.", "#..#.", "#..#.", "#..#.", ".##.."]),
+    // V
+    Pattern::from_rows(["#...#", "#...#", "#...#", ".#.#.", "..#.."]),
+    // W
+    Pattern::from_rows(["#...#", "#...#", "#.#.#", "##.##", "#...#"]),
+    // X
+    Pattern::from_rows(["#..#.", "#..#.", ".##..", "#..#.", "#..#."]),
+    // Y
+    Pattern::from_rows(["#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    // Z
+    Pattern::from_rows(["####.", "...#.", "..#..", ".#...", "####."]),
+];
+
+/// Punctuation, in the order of [`PUNCTUATION_CHARS`].
+pub const PUNCTUATION: [Pattern; 12] = [
+    // ' '
+    Pattern::from_rows([".....", ".....", ".....", ".....", "....."]),
+    // '.'
+    Pattern::from_rows([".....", ".....", ".....", ".....", "#...."]),
+    // ','
+    Pattern::from_rows([".....", ".....", ".....", ".#...", "#...."]),
+    // ':'
+    Pattern::from_rows([".....", "#....", ".....", "#....", "....."]),
+    // '-'
+    Pattern::from_rows([".....", ".....", "###..", ".....", "....."]),
+    // '+'
+    Pattern::from_rows([".....", ".#...", "###..", ".#...", "....."]),
+    // '='
+    Pattern::from_rows([".....", "###..", ".....", "###..", "....."]),
+    // '_'
+    Pattern::from_rows([".....", ".....", ".....", ".....", "####."]),
+    // '/'
+    Pattern::from_rows(["....#", "...#.", "..#..", ".#...", "#...."]),
+    // '%'
+    Pattern::from_rows(["##..#", "##.#.", "..#..", ".#.##", "#..##"]),
+    // '!'
+    Pattern::from_rows(["#....", "#....", "#....", ".....", "#...."]),
+    // '?'
+    Pattern::from_rows(["###..", "...#.", ".##..", ".....", ".#..."]),
+];
+
+/// The characters [`PUNCTUATION`] draws.
+pub const PUNCTUATION_CHARS: &str = " .,:-+=_/%!?";
+
+/// The glyph for `c`. Lower-case letters use the capitals. `None` if the font
+/// has nothing for it.
+pub const fn glyph(c: char) -> Option<Pattern> {
+    match c {
+        '0'..='9' => Some(DIGITS[c as usize - '0' as usize]),
+        'A'..='Z' => Some(LETTERS[c as usize - 'A' as usize]),
+        'a'..='z' => Some(LETTERS[c as usize - 'a' as usize]),
+        _ => {
+            let chars = PUNCTUATION_CHARS.as_bytes();
+            let mut i = 0;
+            while i < chars.len() {
+                if chars[i] as char == c {
+                    return Some(PUNCTUATION[i]);
+                }
+                i += 1;
+            }
+            None
+        }
+    }
+}
+
+/// The glyph for the hex digit in the low four bits of `value`.
+pub const fn hex_digit(value: u8) -> Pattern {
+    let value = (value & 0x0F) as usize;
+    if value < 10 {
+        DIGITS[value]
+    } else {
+        LETTERS[value - 10]
+    }
+}
+
+/// How many columns `glyph` uses, counting from the left edge. Blank glyphs
+/// (the space) are given three columns so words stay apart.
+pub const fn width(glyph: Pattern) -> usize {
+    let mut width = 0;
+    let mut i = 0;
+    while i < 25 {
+        if glyph.is_lit(i % 5, i / 5) && i % 5 + 1 > width {
+            width = i % 5 + 1;
+        }
+        i += 1;
+    }
+    if width == 0 {
+        3
+    } else {
+        width
+    }
+}
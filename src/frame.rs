@@ -0,0 +1,353 @@
+//! Frame buffers that only send what changed.
+//!
+//! The `ssd1306` crate's buffered mode sends the bounding box of everything
+//! drawn since the last flush, so redrawing one line of text sends the full
+//! width of its pages, and a clear and redraw sends the whole frame even when
+//! the picture barely changed. [`Frame`] keeps a copy of what the panel is
+//! showing instead and, at flush time, compares the two page by page and
+//! sends only the runs of columns that differ. [`FrameDriver`] drives the
+//! SSD1306 that way, and [`Sh1106`](crate::sh1106::Sh1106) the SH1106.
+//! Both say how many bytes their last flush sent.
+
+use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*, Pixel};
+use embedded_hal::{blocking::delay::DelayMs, digital::v2::OutputPin};
+use ssd1306::{
+    command::{AddrMode, Command, HScrollDir, NFrames, Page, VHScrollDir},
+    mode::BasicMode,
+    prelude::*,
+    Ssd1306,
+};
+
+use crate::{
+    brightness::Drive,
+    display::Oled,
+    error::Error,
+    marquee::{Direction, Scroll},
+};
+
+/// Bytes in the largest frame, 128x64.
+pub const MAX_BYTES: usize = 128 * 64 / 8;
+
+/// Unchanged columns between two changed ones that are sent anyway rather
+/// than starting a new window, which costs about as much in commands.
+pub const MERGE_GAP: usize = 8;
+
+/// A frame buffer laid out like the controller's RAM: one byte per column
+/// for each 8-pixel page, least significant bit at the top.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    size: Size,
+    buffer: [u8; MAX_BYTES],
+    /// What the panel is showing, as far as this frame knows.
+    shown: [u8; MAX_BYTES],
+    /// The panel's RAM is unknown, so everything goes out next flush.
+    stale: bool,
+    flushed: usize,
+}
+
+impl Frame {
+    /// A blank frame for a panel of `size`, at most 128x64, whose height is
+    /// a whole number of pages.
+    pub const fn new(size: Size) -> Self {
+        Self {
+            size,
+            buffer: [0; MAX_BYTES],
+            shown: [0; MAX_BYTES],
+            stale: true,
+            flushed: 0,
+        }
+    }
+
+    /// Turn the pixel at `point` on or off. Points off the panel are ignored.
+    pub fn set_pixel(&mut self, point: Point, on: bool) {
+        if !self.bounding_box().contains(point) {
+            return;
+        }
+        let index = self.index(point.y as usize / 8, point.x as usize);
+        let bit = 1 << (point.y % 8);
+        if on {
+            self.buffer[index] |= bit;
+        } else {
+            self.buffer[index] &= !bit;
+        }
+    }
+
+    /// Turn every pixel on or off.
+    pub fn fill(&mut self, on: bool) {
+        self.buffer.fill(if on { 0xFF } else { 0x00 });
+    }
+
+    /// Forget what the panel is showing, after it has been reset or
+    /// reinitialised, so the next flush sends the whole frame.
+    pub fn invalidate(&mut self) {
+        self.stale = true;
+    }
+
+    /// Bytes of pixel data the last flush sent.
+    pub const fn flushed_bytes(&self) -> usize {
+        self.flushed
+    }
+
+    /// Whether the next flush has anything to send.
+    pub fn is_changed(&self) -> bool {
+        self.stale || self.buffer != self.shown
+    }
+
+    /// Hand each run of changed columns to `send` as its page, first column
+    /// and bytes, and note it as shown once it's gone. Runs that fail are
+    /// tried again next time.
+    pub fn flush<E>(
+        &mut self,
+        mut send: impl FnMut(u8, u8, &[u8]) -> Result<(), E>,
+    ) -> Result<(), E> {
+        self.flushed = 0;
+        for page in 0..self.size.height as usize / 8 {
+            let mut column = 0;
+            while let Some((start, end)) = self.run(page, column) {
+                let bytes = self.index(page, start)..self.index(page, end);
+                send(page as u8, start as u8, &self.buffer[bytes.clone()])?;
+                self.shown[bytes.clone()].copy_from_slice(&self.buffer[bytes]);
+                self.flushed += end - start;
+                column = end;
+            }
+        }
+        self.stale = false;
+        Ok(())
+    }
+
+    /// The first run of changed columns on `page` from `column` on, as a
+    /// range of columns, taking in gaps of up to [`MERGE_GAP`].
+    fn run(&self, page: usize, column: usize) -> Option<(usize, usize)> {
+        let width = self.size.width as usize;
+        let changed = |column| {
+            let index = self.index(page, column);
+            self.stale || self.buffer[index] != self.shown[index]
+        };
+        let start = (column..width).find(|&column| changed(column))?;
+        let mut end = start + 1;
+        for column in start + 1..width {
+            if changed(column) {
+                end = column + 1;
+            } else if column - end >= MERGE_GAP {
+                break;
+            }
+        }
+        Some((start, end))
+    }
+
+    fn index(&self, page: usize, column: usize) -> usize {
+        page * self.size.width as usize + column
+    }
+}
+
+impl OriginDimensions for Frame {
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+/// An SSD1306 drawn through a [`Frame`], sending each changed run through
+/// its own column and page window.
+///
+/// The `ssd1306` crate's driver owns its interface and has no way to send
+/// the scroll commands through it, so this keeps the interface and lends it
+/// to a driver made for each call instead.
+pub struct FrameDriver<DI, SIZE> {
+    interface: DI,
+    size: SIZE,
+    frame: Frame,
+    /// Whether the controller has been set scrolling.
+    scrolling: bool,
+}
+
+impl<DI, SIZE> FrameDriver<DI, SIZE>
+where
+    DI: WriteOnlyDataCommand,
+    SIZE: DisplaySize,
+{
+    /// A driver for a panel of `size` on `interface`, not yet initialised.
+    pub fn new(interface: DI, size: SIZE) -> Self {
+        Self {
+            interface,
+            size,
+            frame: Frame::new(Size::new(SIZE::WIDTH as u32, SIZE::HEIGHT as u32)),
+            scrolling: false,
+        }
+    }
+}
+
+impl<DI, SIZE> FrameDriver<DI, SIZE>
+where
+    DI: WriteOnlyDataCommand,
+    SIZE: DisplaySize + Copy,
+{
+    /// The `ssd1306` crate's driver, on the interface for as long as it's
+    /// borrowed.
+    fn display(&mut self) -> Ssd1306<Lent<'_, DI>, SIZE, BasicMode> {
+        Ssd1306::new(
+            Lent(&mut self.interface),
+            self.size,
+            DisplayRotation::Rotate0,
+        )
+    }
+
+    /// Send the init sequence. The frame is kept, and the panel is sent all
+    /// of it on the next flush, so a panel set up again after a reset gets
+    /// back what it was showing.
+    pub fn init(&mut self) -> Result<(), DisplayError> {
+        self.frame.invalidate();
+        self.scrolling = false;
+        self.display().init_with_addr_mode(AddrMode::Horizontal)
+    }
+
+    /// Send the runs of columns that changed since the last flush, stopping
+    /// the scroll first if there are any.
+    pub fn flush(&mut self) -> Result<(), DisplayError> {
+        if self.scrolling && self.frame.is_changed() {
+            self.stop_scroll()?;
+        }
+        let interface = &mut self.interface;
+        self.frame.flush(|page, column, bytes| {
+            let x = column + SIZE::OFFSETX;
+            let y = page * 8 + SIZE::OFFSETY;
+            Command::ColumnAddress(x, x + bytes.len() as u8 - 1).send(interface)?;
+            Command::PageAddress(Page::from(y), Page::from(y + 7)).send(interface)?;
+            interface.send_data(DataFormat::U8(bytes))
+        })
+    }
+
+    /// Set the controller scrolling `scroll`'s pages.
+    pub fn start_scroll(&mut self, scroll: Scroll) -> Result<(), DisplayError> {
+        // It has to be stopped to be set up.
+        self.stop_scroll()?;
+        let first = Page::from(scroll.first_page * 8 + SIZE::OFFSETY);
+        let last = Page::from(scroll.last_page * 8 + SIZE::OFFSETY);
+        let interval = NFrames::from(scroll.interval);
+        let interface = &mut self.interface;
+        match scroll.rows {
+            0 => {
+                let direction = match scroll.direction {
+                    Direction::Right => HScrollDir::LeftToRight,
+                    Direction::Left => HScrollDir::RightToLeft,
+                };
+                Command::HScrollSetup(direction, first, last, interval).send(interface)?;
+            }
+            rows => {
+                let direction = match scroll.direction {
+                    Direction::Right => VHScrollDir::VerticalRight,
+                    Direction::Left => VHScrollDir::VerticalLeft,
+                };
+                // The whole panel moves up.
+                Command::VScrollArea(0, SIZE::HEIGHT).send(interface)?;
+                Command::VHScrollSetup(direction, first, last, interval, rows % SIZE::HEIGHT)
+                    .send(interface)?;
+            }
+        }
+        Command::EnableScroll(true).send(interface)?;
+        self.scrolling = true;
+        Ok(())
+    }
+
+    /// Stop the controller scrolling. Once it has, what it shows is
+    /// unknown, so the next flush sends the whole frame.
+    pub fn stop_scroll(&mut self) -> Result<(), DisplayError> {
+        Command::EnableScroll(false).send(&mut self.interface)?;
+        if self.scrolling {
+            self.frame.invalidate();
+            self.scrolling = false;
+        }
+        Ok(())
+    }
+
+    /// Pulse the reset line `reset`, timed by `delay`.
+    pub fn reset<RST, DL>(&mut self, reset: &mut RST, delay: &mut DL) -> Result<(), DisplayError>
+    where
+        RST: OutputPin,
+        DL: DelayMs<u8>,
+    {
+        self.frame.invalidate();
+        self.scrolling = false;
+        self.display()
+            .reset(reset, delay)
+            .map_err(|_| DisplayError::RSError)
+    }
+}
+
+/// An interface lent to the `ssd1306` crate's driver.
+struct Lent<'a, DI>(&'a mut DI);
+
+impl<DI: WriteOnlyDataCommand> WriteOnlyDataCommand for Lent<'_, DI> {
+    fn send_commands(&mut self, cmd: DataFormat<'_>) -> Result<(), DisplayError> {
+        self.0.send_commands(cmd)
+    }
+
+    fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+        self.0.send_data(buf)
+    }
+}
+
+impl<DI, SIZE> OriginDimensions for FrameDriver<DI, SIZE> {
+    fn size(&self) -> Size {
+        self.frame.size()
+    }
+}
+
+impl<DI, SIZE> DrawTarget for FrameDriver<DI, SIZE>
+where
+    DI: WriteOnlyDataCommand,
+    SIZE: DisplaySize,
+{
+    type Color = BinaryColor;
+    type Error = DisplayError;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            self.frame.set_pixel(point, color.is_on());
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.frame.fill(color.is_on());
+        Ok(())
+    }
+}
+
+impl<DI, SIZE> Oled for FrameDriver<DI, SIZE>
+where
+    DI: WriteOnlyDataCommand,
+    SIZE: DisplaySize + Copy,
+{
+    fn init(&mut self) -> Result<(), Error> {
+        FrameDriver::init(self).map_err(Error::DisplayInit)
+    }
+
+    fn flush(&mut self) -> Result<(), DisplayError> {
+        FrameDriver::flush(self)
+    }
+
+    fn flushed_bytes(&self) -> Option<usize> {
+        Some(self.frame.flushed_bytes())
+    }
+
+    fn set_brightness(&mut self, drive: Drive) -> Result<(), DisplayError> {
+        self.display()
+            .set_brightness(Brightness::custom(drive.precharge, drive.contrast))
+    }
+
+    fn start_scroll(&mut self, scroll: Scroll) -> Result<bool, DisplayError> {
+        FrameDriver::start_scroll(self, scroll).map(|()| true)
+    }
+
+    fn stop_scroll(&mut self) -> Result<(), DisplayError> {
+        FrameDriver::stop_scroll(self)
+    }
+
+    fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        self.display().set_display_on(on)
+    }
+}
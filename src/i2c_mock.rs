@@ -0,0 +1,662 @@
+//! A fake SSD1306 on a fake I2C bus.
+//!
+//! [`MockSsd1306`] implements the blocking `embedded-hal` I2C `Write` trait, so
+//! it can be handed to `I2CDisplayInterface::new` exactly like the TWIM. It
+//! decodes the control bytes, commands and data the driver sends and keeps its
+//! own copy of GDDRAM and the controller registers, so tests can check both the
+//! pixels that end up on the panel and the command sequence that put them there.
+//!
+//! [`MockSsd1306::sh1106`] models an SH1106 instead: 132 columns of RAM with
+//! the panel starting at the third, and no horizontal or vertical addressing.
+//!
+//! It also answers `Read` with the controller's status byte, so the firmware's
+//! bus check can be run against it, and [`set_status`](MockSsd1306::set_status)
+//! lets a test pretend to be a different controller. As an [`Abortable`] bus
+//! it can also [hang](MockSsd1306::set_hung) mid-transfer, the way the TWIM
+//! does when something holds the bus.
+//!
+//! The same controller can be wired up on SPI instead: [`MockSsd1306::spi`]
+//! is the bus and [`MockSsd1306::line`] the D/C, chip select and reset lines.
+//!
+//! The handle is cheap to clone and every clone talks to the same controller:
+//! give one to the driver and keep one to look at.
+
+use std::{
+    cell::{Cell, Ref, RefCell},
+    convert::Infallible,
+    rc::Rc,
+    string::String,
+    vec,
+    vec::Vec,
+};
+
+use embedded_graphics::prelude::{Point, Size};
+use embedded_hal::{
+    blocking::{
+        i2c::{Read, Write},
+        spi,
+    },
+    digital::v2::OutputPin,
+};
+
+use crate::{
+    bus::{BusFault, Diagnose},
+    timeout::{Abortable, Timed},
+};
+
+/// The address `I2CDisplayInterface::new` talks to.
+pub const DEFAULT_ADDRESS: u8 = 0x3C;
+
+/// Columns of GDDRAM in the controller.
+pub const RAM_COLUMNS: usize = 128;
+/// Columns of RAM in an SH1106.
+pub const SH1106_RAM_COLUMNS: usize = 132;
+/// 8-pixel-high pages of GDDRAM in the controller.
+pub const RAM_PAGES: usize = 8;
+
+/// Why a write to the mock failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MockError {
+    /// Nothing acknowledged `address`.
+    Nack { address: u8 },
+}
+
+/// GDDRAM addressing mode, as set by command `0x20`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddrMode {
+    Horizontal,
+    Vertical,
+    Page,
+}
+
+/// The controller's memory and registers, as rebuilt from the bus traffic.
+#[derive(Clone, Debug)]
+pub struct Ssd1306State {
+    /// Modelling an SH1106 rather than an SSD1306.
+    sh1106: bool,
+    columns: usize,
+    gddram: Vec<u8>,
+    addr_mode: AddrMode,
+    column: usize,
+    column_start: usize,
+    column_end: usize,
+    page: usize,
+    page_start: usize,
+    page_end: usize,
+    contrast: u8,
+    precharge: u8,
+    multiplex: u8,
+    inverted: bool,
+    all_on: bool,
+    display_on: bool,
+    charge_pump: bool,
+    segment_remap: bool,
+    com_reversed: bool,
+    scrolling: bool,
+    commands: Vec<Vec<u8>>,
+    pending: Vec<u8>,
+    transactions: usize,
+    data_bytes: usize,
+}
+
+impl Default for Ssd1306State {
+    /// The controller straight after power-on reset.
+    fn default() -> Self {
+        Self::reset(false)
+    }
+}
+
+impl Ssd1306State {
+    /// An SSD1306, or with `sh1106` an SH1106, straight after power-on reset.
+    fn reset(sh1106: bool) -> Self {
+        let columns = if sh1106 {
+            SH1106_RAM_COLUMNS
+        } else {
+            RAM_COLUMNS
+        };
+        Self {
+            sh1106,
+            columns,
+            gddram: vec![0; columns * RAM_PAGES],
+            addr_mode: AddrMode::Page,
+            column: 0,
+            column_start: 0,
+            column_end: columns - 1,
+            page: 0,
+            page_start: 0,
+            page_end: RAM_PAGES - 1,
+            contrast: 0x7F,
+            precharge: 0x22,
+            multiplex: 63,
+            inverted: false,
+            all_on: false,
+            display_on: false,
+            charge_pump: false,
+            segment_remap: false,
+            com_reversed: false,
+            scrolling: false,
+            commands: Vec::new(),
+            pending: Vec::new(),
+            transactions: 0,
+            data_bytes: 0,
+        }
+    }
+
+    /// Raw GDDRAM, page by page, one byte per column.
+    pub fn gddram(&self) -> &[u8] {
+        &self.gddram
+    }
+
+    /// Every complete command received, opcode first, in order.
+    pub fn commands(&self) -> &[Vec<u8>] {
+        &self.commands
+    }
+
+    /// Just the opcodes of [`commands`](Self::commands).
+    pub fn opcodes(&self) -> Vec<u8> {
+        self.commands.iter().map(|c| c[0]).collect()
+    }
+
+    /// How many I2C write transactions were acknowledged.
+    pub fn transactions(&self) -> usize {
+        self.transactions
+    }
+
+    /// How many GDDRAM data bytes were received.
+    pub fn data_bytes(&self) -> usize {
+        self.data_bytes
+    }
+
+    /// The current addressing mode.
+    pub fn addr_mode(&self) -> AddrMode {
+        self.addr_mode
+    }
+
+    /// The `0x81` contrast register.
+    pub fn contrast(&self) -> u8 {
+        self.contrast
+    }
+
+    /// The raw `0xD9` pre-charge register: phase 2 in the high nibble.
+    pub fn precharge(&self) -> u8 {
+        self.precharge
+    }
+
+    /// Number of COM rows driven, from the multiplex ratio.
+    pub fn rows(&self) -> u32 {
+        self.multiplex as u32 + 1
+    }
+
+    /// Whether `0xA7` (inverse display) is in effect.
+    pub fn is_inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Whether `0xA5` (entire display on) is in effect.
+    pub fn is_all_on(&self) -> bool {
+        self.all_on
+    }
+
+    /// Whether the panel is switched on (`0xAF`).
+    pub fn is_display_on(&self) -> bool {
+        self.display_on
+    }
+
+    /// Whether the internal charge pump is enabled.
+    pub fn is_charge_pump_on(&self) -> bool {
+        self.charge_pump
+    }
+
+    /// Whether columns are mirrored (`0xA1`).
+    pub fn is_segment_remapped(&self) -> bool {
+        self.segment_remap
+    }
+
+    /// Whether rows are scanned bottom to top (`0xC8`).
+    pub fn is_com_reversed(&self) -> bool {
+        self.com_reversed
+    }
+
+    /// Whether hardware scrolling is active (`0x2F`).
+    pub fn is_scrolling(&self) -> bool {
+        self.scrolling
+    }
+
+    /// The GDDRAM bit for `point`, in RAM coordinates. Out of range is off.
+    pub fn ram_pixel(&self, point: Point) -> bool {
+        let (Ok(x), Ok(y)) = (usize::try_from(point.x), usize::try_from(point.y)) else {
+            return false;
+        };
+        if x >= self.columns || y >= RAM_PAGES * 8 {
+            return false;
+        }
+        self.gddram[(y / 8) * self.columns + x] & (1 << (y % 8)) != 0
+    }
+
+    /// Whether `point` would look lit, taking display on/off, entire display
+    /// on and inversion into account.
+    pub fn lit(&self, point: Point) -> bool {
+        if !self.display_on {
+            return false;
+        }
+        if self.all_on {
+            return true;
+        }
+        // An SH1106's panel starts two columns into its RAM.
+        let offset = if self.sh1106 { 2 } else { 0 };
+        self.ram_pixel(point + Point::new(offset, 0)) != self.inverted
+    }
+
+    /// The top-left `size` pixels as they look, in the same format as
+    /// [`Framebuffer::to_ascii`](crate::emulator::Framebuffer::to_ascii).
+    pub fn to_ascii(&self, size: Size) -> String {
+        use crate::emulator::{ASCII_OFF, ASCII_ON};
+
+        let mut out = String::new();
+        for y in 0..size.height as i32 {
+            for x in 0..size.width as i32 {
+                out.push(if self.lit(Point::new(x, y)) {
+                    ASCII_ON
+                } else {
+                    ASCII_OFF
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Forget the command log and traffic counters, keeping RAM and registers.
+    pub fn clear_log(&mut self) {
+        self.commands.clear();
+        self.transactions = 0;
+        self.data_bytes = 0;
+    }
+
+    fn receive(&mut self, bytes: &[u8]) {
+        let mut bytes = bytes.iter().copied();
+        while let Some(control) = bytes.next() {
+            let continuation = control & 0x80 != 0;
+            let data = control & 0x40 != 0;
+            if continuation {
+                // Co set: exactly one byte follows, then another control byte.
+                if let Some(byte) = bytes.next() {
+                    self.accept(data, byte);
+                }
+            } else {
+                // Co clear: the rest of the transaction is all of one kind.
+                for byte in bytes.by_ref() {
+                    self.accept(data, byte);
+                }
+            }
+        }
+    }
+
+    fn accept(&mut self, data: bool, byte: u8) {
+        if data {
+            self.write_ram(byte);
+        } else {
+            self.pending.push(byte);
+            if self.pending.len() > arguments(self.pending[0]) {
+                let command = core::mem::take(&mut self.pending);
+                self.execute(&command);
+                self.commands.push(command);
+            }
+        }
+    }
+
+    fn execute(&mut self, command: &[u8]) {
+        match *command {
+            [0x81, contrast] => self.contrast = contrast,
+            [op @ (0xA4 | 0xA5)] => self.all_on = op & 1 != 0,
+            [op @ (0xA6 | 0xA7)] => self.inverted = op & 1 != 0,
+            [op @ (0xAE | 0xAF)] => self.display_on = op & 1 != 0,
+            [op @ (0x2E | 0x2F)] => self.scrolling = op & 1 != 0,
+            [op @ 0x00..=0x0F] => self.column = (self.column & 0xF0) | op as usize,
+            [op @ 0x10..=0x1F] => self.column = (self.column & 0x0F) | ((op as usize & 0x0F) << 4),
+            // Not on the SH1106: it takes these as other commands.
+            [0x20..=0x22, ..] if self.sh1106 => {}
+            [0x20, mode] => {
+                self.addr_mode = match mode & 0b11 {
+                    0 => AddrMode::Horizontal,
+                    1 => AddrMode::Vertical,
+                    _ => AddrMode::Page,
+                }
+            }
+            [0x21, start, end] => {
+                self.column_start = start as usize % self.columns;
+                self.column_end = end as usize % self.columns;
+                self.column = self.column_start;
+            }
+            [0x22, start, end] => {
+                self.page_start = start as usize % RAM_PAGES;
+                self.page_end = end as usize % RAM_PAGES;
+                self.page = self.page_start;
+            }
+            [op @ 0xB0..=0xB7] => self.page = (op & 0x07) as usize,
+            [op @ (0xA0 | 0xA1)] => self.segment_remap = op & 1 != 0,
+            [op @ (0xC0 | 0xC8)] => self.com_reversed = op & 0x08 != 0,
+            [0xA8, ratio] => self.multiplex = ratio & 0x3F,
+            [0xD9, precharge] => self.precharge = precharge,
+            [0x8D, pump] => self.charge_pump = pump & 0x04 != 0,
+            // Timing, offsets, COM pins, Vcomh, scroll setup and NOP don't
+            // change what this model shows; they're only logged.
+            _ => {}
+        }
+    }
+
+    fn write_ram(&mut self, byte: u8) {
+        self.gddram[self.page * self.columns + self.column] = byte;
+        self.data_bytes += 1;
+
+        match self.addr_mode {
+            AddrMode::Horizontal => {
+                if self.column >= self.column_end {
+                    self.column = self.column_start;
+                    self.page = wrap(self.page, self.page_start, self.page_end);
+                } else {
+                    self.column += 1;
+                }
+            }
+            AddrMode::Vertical => {
+                if self.page >= self.page_end {
+                    self.page = self.page_start;
+                    self.column = wrap(self.column, self.column_start, self.column_end);
+                } else {
+                    self.page += 1;
+                }
+            }
+            AddrMode::Page => self.column = (self.column + 1) % self.columns,
+        }
+    }
+}
+
+/// A control line of an SPI module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Line {
+    /// Data (high) or command (low).
+    Dc,
+    /// Chip select, active low.
+    Cs,
+    /// Reset, active low.
+    Rst,
+}
+
+/// Levels of the SPI control lines, high as true.
+#[derive(Clone, Copy, Debug)]
+struct Levels {
+    dc: bool,
+    cs: bool,
+    rst: bool,
+}
+
+/// A fake SSD1306 listening on the I2C bus.
+#[derive(Clone, Debug)]
+pub struct MockSsd1306 {
+    address: u8,
+    present: Rc<Cell<bool>>,
+    status: Rc<Cell<Option<u8>>>,
+    hung: Rc<Cell<bool>>,
+    levels: Rc<Cell<Levels>>,
+    resets: Rc<Cell<usize>>,
+    state: Rc<RefCell<Ssd1306State>>,
+}
+
+impl Default for MockSsd1306 {
+    fn default() -> Self {
+        Self::new(DEFAULT_ADDRESS)
+    }
+}
+
+impl MockSsd1306 {
+    /// A freshly reset controller answering at `address`.
+    pub fn new(address: u8) -> Self {
+        Self {
+            address,
+            present: Rc::new(Cell::new(true)),
+            status: Rc::new(Cell::new(None)),
+            hung: Rc::new(Cell::new(false)),
+            levels: Rc::new(Cell::new(Levels {
+                dc: false,
+                cs: true,
+                rst: true,
+            })),
+            resets: Rc::new(Cell::new(0)),
+            state: Rc::new(RefCell::new(Ssd1306State::default())),
+        }
+    }
+
+    /// A freshly reset SH1106 answering at `address`.
+    pub fn sh1106(address: u8) -> Self {
+        let mock = Self::new(address);
+        *mock.state.borrow_mut() = Ssd1306State::reset(true);
+        mock
+    }
+
+    /// The address this controller answers on.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// What the controller has received so far.
+    pub fn state(&self) -> Ref<'_, Ssd1306State> {
+        self.state.borrow()
+    }
+
+    /// See [`Ssd1306State::clear_log`].
+    pub fn clear_log(&self) {
+        self.state.borrow_mut().clear_log();
+    }
+
+    /// Plug the module in or pull it off the bus. While unplugged every write
+    /// is NACKed and nothing is recorded.
+    pub fn set_present(&self, present: bool) {
+        self.present.set(present);
+    }
+
+    /// Read back `status` instead of what an SSD1306 would, or go back to
+    /// the SSD1306's with `None`.
+    pub fn set_status(&self, status: Option<u8>) {
+        self.status.set(status);
+    }
+
+    /// Make every transfer hang until it is aborted, or behave again with
+    /// `false`. Only transfers through [`Abortable`] notice.
+    pub fn set_hung(&self, hung: bool) {
+        self.hung.set(hung);
+    }
+
+    /// Spin until `expired` if transfers are hanging, then time out.
+    fn hang(&self, expired: &mut dyn FnMut() -> bool) -> Result<(), Timed<MockError>> {
+        if !self.hung.get() {
+            return Ok(());
+        }
+        while !expired() {}
+        Err(Timed::Timeout)
+    }
+
+    /// The controller's SPI bus. Bytes are taken while CS is low and RST
+    /// high, as commands or data by the level of D/C.
+    pub fn spi(&self) -> MockSpi {
+        MockSpi(self.clone())
+    }
+
+    /// One of the controller's SPI control lines, to drive.
+    pub fn line(&self, line: Line) -> MockLine {
+        MockLine {
+            line,
+            mock: self.clone(),
+        }
+    }
+
+    /// How many times RST has been pulled low.
+    pub fn resets(&self) -> usize {
+        self.resets.get()
+    }
+
+    /// Power-cycle the controller: RAM and registers go back to reset values.
+    /// The command log is kept.
+    pub fn power_cycle(&self) {
+        let mut state = self.state.borrow_mut();
+        let fresh = Ssd1306State {
+            commands: core::mem::take(&mut state.commands),
+            transactions: state.transactions,
+            data_bytes: state.data_bytes,
+            ..Ssd1306State::reset(state.sh1106)
+        };
+        *state = fresh;
+    }
+}
+
+impl Write for MockSsd1306 {
+    type Error = MockError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        if address != self.address || !self.present.get() {
+            return Err(MockError::Nack { address });
+        }
+        let mut state = self.state.borrow_mut();
+        state.transactions += 1;
+        state.receive(bytes);
+        Ok(())
+    }
+}
+
+impl Read for MockSsd1306 {
+    type Error = MockError;
+
+    /// Every byte read is the status register: bit 6 set while the display is
+    /// off, low bits 0x3 like the common SSD1306 modules, or 0x8 for an
+    /// SH1106.
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        if address != self.address || !self.present.get() {
+            return Err(MockError::Nack { address });
+        }
+        let mut state = self.state.borrow_mut();
+        state.transactions += 1;
+        let status = self.status.get().unwrap_or(
+            if state.sh1106 { 0x08 } else { 0x03 } | if state.display_on { 0x00 } else { 0x40 },
+        );
+        buffer.fill(status);
+        Ok(())
+    }
+}
+
+impl Diagnose for MockSsd1306 {
+    fn diagnose(error: &MockError) -> BusFault {
+        match error {
+            MockError::Nack { .. } => BusFault::AddressNack,
+        }
+    }
+}
+
+impl Abortable for MockSsd1306 {
+    type Error = MockError;
+
+    fn write_until(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        expired: &mut dyn FnMut() -> bool,
+    ) -> Result<(), Timed<MockError>> {
+        self.hang(expired)?;
+        self.write(address, bytes).map_err(Timed::Bus)
+    }
+
+    fn read_until(
+        &mut self,
+        address: u8,
+        buffer: &mut [u8],
+        expired: &mut dyn FnMut() -> bool,
+    ) -> Result<(), Timed<MockError>> {
+        self.hang(expired)?;
+        self.read(address, buffer).map_err(Timed::Bus)
+    }
+
+    fn diagnose(error: &MockError) -> BusFault {
+        <Self as Diagnose>::diagnose(error)
+    }
+}
+
+/// The SPI bus to a [`MockSsd1306`]. There's nothing on SPI to say a byte
+/// wasn't taken, so writes always succeed.
+#[derive(Clone, Debug)]
+pub struct MockSpi(MockSsd1306);
+
+impl spi::Write<u8> for MockSpi {
+    type Error = Infallible;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        let mock = &self.0;
+        let levels = mock.levels.get();
+        if !mock.present.get() || levels.cs || !levels.rst {
+            return Ok(());
+        }
+        let mut state = mock.state.borrow_mut();
+        state.transactions += 1;
+        for &byte in words {
+            state.accept(levels.dc, byte);
+        }
+        Ok(())
+    }
+}
+
+/// A control line to a [`MockSsd1306`] on SPI. Pulling RST low resets the
+/// controller like [`power_cycle`](MockSsd1306::power_cycle).
+#[derive(Clone, Debug)]
+pub struct MockLine {
+    line: Line,
+    mock: MockSsd1306,
+}
+
+impl MockLine {
+    fn drive(&mut self, high: bool) {
+        let mut levels = self.mock.levels.get();
+        match self.line {
+            Line::Dc => levels.dc = high,
+            Line::Cs => levels.cs = high,
+            Line::Rst => {
+                if levels.rst && !high {
+                    self.mock.resets.set(self.mock.resets.get() + 1);
+                    self.mock.power_cycle();
+                }
+                levels.rst = high;
+            }
+        }
+        self.mock.levels.set(levels);
+    }
+}
+
+impl OutputPin for MockLine {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.drive(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.drive(true);
+        Ok(())
+    }
+}
+
+/// How many argument bytes follow `opcode`.
+fn arguments(opcode: u8) -> usize {
+    match opcode {
+        0x26 | 0x27 => 6,
+        0x29 | 0x2A => 5,
+        0x21 | 0x22 | 0xA3 => 2,
+        0x20 | 0x81 | 0x8D | 0xA8 | 0xAD | 0xD3 | 0xD5 | 0xD9 | 0xDA | 0xDB => 1,
+        _ => 0,
+    }
+}
+
+/// Step `value` on by one within `start..=end`, going back to `start` past the end.
+fn wrap(value: usize, start: usize, end: usize) -> usize {
+    if value >= end {
+        start
+    } else {
+        value + 1
+    }
+}
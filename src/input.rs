@@ -0,0 +1,56 @@
+//! User input.
+
+/// The two push buttons on the front of the micro:bit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Button {
+    A,
+    B,
+}
+
+/// How long a button has to stay put before a change counts, to ride out
+/// contact bounce.
+pub const DEBOUNCE_MS: u32 = 20;
+
+/// Turns the buttons' levels, read over and over, into presses.
+#[derive(Clone, Copy, Debug)]
+pub struct Presses {
+    /// Whether A and B are down, as far as presses go.
+    down: [bool; 2],
+    /// When each last changed.
+    changed_ms: [u32; 2],
+}
+
+impl Presses {
+    /// Start with A and B down or not, at `now_ms`. A button held from the
+    /// start only counts once it's been let go and pressed again.
+    pub const fn new(a_down: bool, b_down: bool, now_ms: u32) -> Self {
+        Self {
+            down: [a_down, b_down],
+            changed_ms: [now_ms; 2],
+        }
+    }
+
+    /// Look at whether A and B are down at `now_ms`, and return a button that
+    /// has just been pressed. If both were, A comes back now and B next time.
+    pub fn update(&mut self, now_ms: u32, a_down: bool, b_down: bool) -> Option<Button> {
+        let mut pressed = None;
+        for (i, (button, down)) in [(Button::A, a_down), (Button::B, b_down)]
+            .into_iter()
+            .enumerate()
+        {
+            if down == self.down[i] || now_ms.wrapping_sub(self.changed_ms[i]) < DEBOUNCE_MS {
+                continue;
+            }
+            if down && pressed.is_some() {
+                // Left down for the next call to find.
+                continue;
+            }
+            self.down[i] = down;
+            self.changed_ms[i] = now_ms;
+            if down {
+                pressed = Some(button);
+            }
+        }
+        pressed
+    }
+}
@@ -0,0 +1,66 @@
+//! The 5x5 LED matrix.
+
+use crate::patterns::Pattern;
+
+/// A matrix that keeps showing the last pattern it was given on its own,
+/// like `microbit::display::nonblocking::Display` refreshed from a timer
+/// interrupt. Setting a pattern returns straight away.
+pub trait NonBlockingMatrix {
+    /// Show `pattern` from now until the next call.
+    fn set(&mut self, pattern: Pattern);
+}
+
+/// What the matrix shows, and for how long.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub pattern: Pattern,
+    pub duration_ms: u32,
+}
+
+/// A [`NonBlockingMatrix`] that writes down every pattern it's set to, and
+/// when, for the host tests and the simulator. It has no clock of its own:
+/// the caller moves it on with [`set_now_ms`](Self::set_now_ms) before
+/// anything that might light it.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default)]
+pub struct RecordingMatrix {
+    now_ms: u32,
+    changes: std::vec::Vec<(u32, Pattern)>,
+}
+
+#[cfg(feature = "std")]
+impl RecordingMatrix {
+    /// A dark matrix that hasn't been set yet, at 0 ms.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take patterns set from now on as set at `now_ms`.
+    pub fn set_now_ms(&mut self, now_ms: u32) {
+        self.now_ms = now_ms;
+    }
+
+    /// Every pattern set so far with the time it was set at, oldest first.
+    pub fn changes(&self) -> &[(u32, Pattern)] {
+        &self.changes
+    }
+
+    /// Every pattern set so far, oldest first.
+    pub fn patterns(&self) -> impl DoubleEndedIterator<Item = Pattern> + '_ {
+        self.changes.iter().map(|&(_, pattern)| pattern)
+    }
+
+    /// What is lit right now.
+    pub fn lit(&self) -> Pattern {
+        self.changes
+            .last()
+            .map_or(Pattern::BLANK, |&(_, pattern)| pattern)
+    }
+}
+
+#[cfg(feature = "std")]
+impl NonBlockingMatrix for RecordingMatrix {
+    fn set(&mut self, pattern: Pattern) {
+        self.changes.push((self.now_ms, pattern));
+    }
+}
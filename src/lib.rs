@@ -0,0 +1,42 @@
+//! Core logic of the micro:bit OLED demo.
+//!
+//! Everything in here is generic over `embedded-graphics` and `display-interface`
+//! traits, so it runs unchanged on the nRF52833 and under `cargo host-test` on a
+//! PC. The firmware binary in `src/main.rs` only wires up the board peripherals.
+#![no_std]
+
+#[cfg(feature = "std")]
+extern crate std;
+
+pub mod app;
+pub mod boot;
+pub mod brightness;
+pub mod burnin;
+pub mod bus;
+pub mod display;
+#[cfg(feature = "std")]
+pub mod emulator;
+pub mod error;
+pub mod font;
+pub mod frame;
+#[cfg(feature = "std")]
+pub mod i2c_mock;
+pub mod input;
+pub mod led;
+pub mod lsm303agr;
+pub mod marquee;
+pub mod menu;
+pub mod orientation;
+pub mod patterns;
+pub mod player;
+pub mod power;
+pub mod queue;
+pub mod recovery;
+pub mod scan;
+pub mod screens;
+pub mod scroll;
+pub mod settings;
+pub mod sh1106;
+pub mod speed;
+pub mod text;
+pub mod timeout;
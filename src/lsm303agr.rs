@@ -0,0 +1,110 @@
+//! Just enough of the LSM303AGR's accelerometer to tell which way up the
+//! board is.
+//!
+//! The micro:bit v2 has it on the internal I2C bus, on a TWIM of its own, so
+//! it never waits behind the OLED's frames. It's set to measure 10 times a
+//! second at high resolution, where a reading is 1 mg per count over ±2 g;
+//! the magnetometer beside it is left off.
+//!
+//! Register addresses go out from the stack rather than from constant
+//! slices: the TWIM's EasyDMA can't read flash, and `write_read` won't copy.
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+/// The accelerometer's I2C address.
+pub const ADDRESS: u8 = 0x19;
+
+/// What `WHO_AM_I_A` reads on an LSM303AGR.
+pub const WHO_AM_I: u8 = 0x33;
+
+const WHO_AM_I_A: u8 = 0x0F;
+const CTRL_REG1_A: u8 = 0x20;
+const CTRL_REG4_A: u8 = 0x23;
+const OUT_X_L_A: u8 = 0x28;
+/// Set on a register address to read the ones after it too.
+const AUTO_INCREMENT: u8 = 0x80;
+
+/// 10 Hz, normal power, X, Y and Z on.
+const CTRL_REG1_10HZ: u8 = 0x27;
+/// No measuring: the accelerometer's power-down mode.
+const CTRL_REG1_OFF: u8 = 0x07;
+/// Both bytes of a reading from the same sample, high resolution, ±2 g.
+const CTRL_REG4_HR: u8 = 0x88;
+
+/// What went wrong talking to the accelerometer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccelError<E> {
+    Bus(E),
+    /// Something answered that isn't an LSM303AGR.
+    WrongChip {
+        who_am_i: u8,
+    },
+}
+
+/// Acceleration along the sensor's axes, in mg. Lying still, it's gravity
+/// pointing up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Accel {
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+}
+
+/// The LSM303AGR's accelerometer on `I`.
+#[derive(Debug)]
+pub struct Lsm303agr<I> {
+    i2c: I,
+}
+
+impl<I, E> Lsm303agr<I>
+where
+    I: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// The accelerometer on `i2c`, not yet set up.
+    pub const fn new(i2c: I) -> Self {
+        Self { i2c }
+    }
+
+    /// Check it's there and start it measuring.
+    pub fn init(&mut self) -> Result<(), AccelError<E>> {
+        let register = [WHO_AM_I_A];
+        let mut who_am_i = [0];
+        self.i2c
+            .write_read(ADDRESS, &register, &mut who_am_i)
+            .map_err(AccelError::Bus)?;
+        if who_am_i[0] != WHO_AM_I {
+            return Err(AccelError::WrongChip {
+                who_am_i: who_am_i[0],
+            });
+        }
+        for command in [[CTRL_REG1_A, CTRL_REG1_10HZ], [CTRL_REG4_A, CTRL_REG4_HR]] {
+            self.i2c.write(ADDRESS, &command).map_err(AccelError::Bus)?;
+        }
+        Ok(())
+    }
+
+    /// The latest reading.
+    pub fn accel(&mut self) -> Result<Accel, E> {
+        let register = [OUT_X_L_A | AUTO_INCREMENT];
+        let mut out = [0; 6];
+        self.i2c.write_read(ADDRESS, &register, &mut out)?;
+        // Left-justified 12-bit values.
+        let axis = |i: usize| i16::from_le_bytes([out[i], out[i + 1]]) >> 4;
+        Ok(Accel {
+            x: axis(0),
+            y: axis(2),
+            z: axis(4),
+        })
+    }
+
+    /// Stop it measuring, down to a couple of µA. [`init`](Self::init)
+    /// starts it again.
+    pub fn power_down(&mut self) -> Result<(), E> {
+        self.i2c.write(ADDRESS, &[CTRL_REG1_A, CTRL_REG1_OFF])
+    }
+
+    /// The bus, back.
+    pub fn release(self) -> I {
+        self.i2c
+    }
+}
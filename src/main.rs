@@ -1,99 +1,796 @@
-#![no_main]
-#![no_std]
-
-use cortex_m_rt::entry;
-use microbit::{
-    board::Board,
-    display::blocking::Display,
-    hal::{prelude::*, timer::Timer, twim},
-};
-use panic_halt as _;
-
-use embedded_graphics::{
-    mono_font::{ascii::FONT_6X10, MonoTextStyle},
-    pixelcolor::BinaryColor,
-    prelude::*,
-    text::Text,
-};
-use ssd1306::{prelude::*, I2CDisplayInterface, Ssd1306};
-
-#[entry]
-fn main() -> ! {
-    let board = Board::take().unwrap();
-    let mut timer = Timer::new(board.TIMER0);
-    let mut led_display = Display::new(board.display_pins);
-
-    // Show a smiley to indicate program started
-    let smiley = [
-        [0, 1, 0, 1, 0],
-        [0, 1, 0, 1, 0],
-        [0, 0, 0, 0, 0],
-        [1, 0, 0, 0, 1],
-        [0, 1, 1, 1, 0],
-    ];
-    led_display.show(&mut timer, smiley, 1000);
-    led_display.clear();
-
-    // Use the external I2C bus (pins 19/20 on edge connector)
-    let i2c = twim::Twim::new(
-        board.TWIM0,
-        board.i2c_external.into(),
-        twim::Frequency::K100,
-    );
-
-    // Set up OLED display at address 0x3C
-    let interface = I2CDisplayInterface::new(i2c);
-    let mut display = Ssd1306::new(interface, DisplaySize128x32, DisplayRotation::Rotate0)
-        .into_buffered_graphics_mode();
-
-    // Try to initialize the display
-    if display.init().is_err() {
-        // Show X on LED matrix if init fails
-        let x_pattern = [
-            [1, 0, 0, 0, 1],
-            [0, 1, 0, 1, 0],
-            [0, 0, 1, 0, 0],
-            [0, 1, 0, 1, 0],
-            [1, 0, 0, 0, 1],
-        ];
+//! micro:bit v2 firmware: takes the board peripherals and hands them to the
+//! main loop in the library.
+#![cfg_attr(target_os = "none", no_std)]
+#![cfg_attr(target_os = "none", no_main)]
+
+#[cfg(target_os = "none")]
+mod firmware {
+    use core::{
+        cell::RefCell,
+        sync::atomic::{compiler_fence, Ordering::SeqCst},
+    };
+
+    use cortex_m::{
+        asm,
+        interrupt::{free, Mutex},
+    };
+    use cortex_m_rt::entry;
+    #[cfg(feature = "spi")]
+    use microbit::hal::{spim, Spim};
+    use microbit::{
+        board::Board,
+        display::nonblocking::{Display, GreyscaleImage},
+        hal::{
+            clocks::Clocks,
+            delay::Delay,
+            gpio::{Floating, Input, Level, Output, Pin, PushPull},
+            gpiote::Gpiote,
+            nvmc::Nvmc,
+            prelude::*,
+            rtc::{Rtc, RtcCompareReg, RtcInterrupt},
+            target_constants::{SRAM_LOWER, SRAM_UPPER},
+            timer::Periodic,
+            timer::Timer,
+            twim, Twim,
+        },
+        pac::{self, interrupt, twim0, Interrupt, NVIC, NVMC, RTC0, TIMER1, TIMER2, TWIM0, TWIM1},
+    };
+    use microbit_oled::{
+        app::{App, Platform},
+        boot::Step,
+        bus::BusFault,
+        display::{Oled, PanelSize},
+        led::NonBlockingMatrix,
+        lsm303agr::{Accel, Lsm303agr},
+        patterns::Pattern,
+        queue::Queue,
+        recovery::{self, OpenDrain},
+        scan::Scanner,
+        settings::Settings,
+        speed::Speed,
+        timeout::{Abortable, Deadline, Micros, Timed},
+    };
+    #[cfg(not(feature = "spi"))]
+    use microbit_oled::{
+        bus,
+        display::{Panel, CONTROLLER},
+    };
+    #[cfg(feature = "spi")]
+    use microbit_oled::{display::SpiPanel, speed::Link};
+    use panic_halt as _;
+
+    /// The LED matrix driver, shared with the `TIMER1` interrupt that refreshes it.
+    static DISPLAY: Mutex<RefCell<Option<Display<TIMER1>>>> = Mutex::new(RefCell::new(None));
+
+    /// The LED matrix as seen from `main`: patterns are handed to the
+    /// interrupt-driven driver and stay up until replaced.
+    struct InterruptMatrix;
+
+    impl NonBlockingMatrix for InterruptMatrix {
+        fn set(&mut self, pattern: Pattern) {
+            let image = GreyscaleImage::new(&pattern.rows());
+            free(|cs| {
+                if let Some(display) = DISPLAY.borrow(cs).borrow_mut().as_mut() {
+                    display.show(&image);
+                }
+            });
+        }
+    }
+
+    /// How long the LED matrix takes to go round all its rows.
+    const LED_CYCLE_MS: u32 = 6;
+
+    /// Stop the LED matrix's timer, and its interrupt waking the CPU, once
+    /// it's been round every row with the blank pattern up, so none is left
+    /// lit.
+    fn pause_leds(clock: &mut Clock) {
+        let start = clock.now_ms();
+        while clock.now_ms().wrapping_sub(start) <= LED_CYCLE_MS {
+            // The matrix's own interrupts wake it.
+            asm::wfe();
+        }
+        NVIC::mask(Interrupt::TIMER1);
+        // SAFETY: the display driver owns TIMER1, but with its interrupt
+        // masked nothing is using it.
+        unsafe { &*TIMER1::ptr() }
+            .tasks_stop
+            .write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Start the LED matrix again.
+    fn resume_leds() {
+        // SAFETY: as in `pause_leds`; the interrupt isn't unmasked until
+        // it's going again.
+        unsafe { &*TIMER1::ptr() }
+            .tasks_start
+            .write(|w| unsafe { w.bits(1) });
+        // SAFETY: the handler only touches `DISPLAY`, behind a critical section.
+        unsafe { NVIC::unmask(Interrupt::TIMER1) };
+    }
+
+    /// The last 4 KiB page of the nRF52833's 512 KiB of flash, well clear of
+    /// the firmware image, where the settings live.
+    const SETTINGS_PAGE: usize = 0x7F000;
+
+    /// The settings page, to hand to the NVMC.
+    fn settings_page() -> &'static mut [u32] {
+        // SAFETY: nothing else refers to this page, and it's only handed out once.
+        unsafe { core::slice::from_raw_parts_mut(SETTINGS_PAGE as *mut u32, 1024) }
+    }
+
+    /// An I2C pin worked open-drain by hand for bus recovery: an input while
+    /// released, so the line can be read, and driven low otherwise. It is
+    /// never driven high.
+    struct GpioLine(Option<LineMode>);
+
+    enum LineMode {
+        Released(Pin<Input<Floating>>),
+        Low(Pin<Output<PushPull>>),
+    }
+
+    impl GpioLine {
+        fn new(pin: Pin<Input<Floating>>) -> Self {
+            Self(Some(LineMode::Released(pin)))
+        }
+
+        /// Give the pin back, released, for the TWIM.
+        fn into_input(self) -> Pin<Input<Floating>> {
+            match self.0 {
+                Some(LineMode::Released(pin)) => pin,
+                Some(LineMode::Low(pin)) => pin.into_floating_input(),
+                // Only empty halfway through a switch.
+                None => unreachable!(),
+            }
+        }
+    }
+
+    impl OpenDrain for GpioLine {
+        fn release(&mut self) {
+            self.0 = self.0.take().map(|mode| match mode {
+                LineMode::Low(pin) => LineMode::Released(pin.into_floating_input()),
+                released => released,
+            });
+        }
+
+        fn pull_low(&mut self) {
+            self.0 = self.0.take().map(|mode| match mode {
+                LineMode::Released(pin) => LineMode::Low(pin.into_push_pull_output(Level::Low)),
+                low => low,
+            });
+        }
+
+        fn is_high(&mut self) -> bool {
+            match &self.0 {
+                Some(LineMode::Released(pin)) => pin.is_high().unwrap(),
+                _ => false,
+            }
+        }
+    }
+
+    /// Writes waiting for the TWIM interrupt to send them, shared with it.
+    static QUEUE: Mutex<RefCell<Queue<twim::Error>>> = Mutex::new(RefCell::new(Queue::new()));
+
+    /// The external I2C bus, able to say what its errors mean. Its transfers
+    /// all go through [`Abortable`], so each one is timed by [`Deadline`].
+    ///
+    /// In the background, writes go to [`QUEUE`] and the TWIM interrupt sends
+    /// them one after another; anything else waits for the queue to empty.
+    struct Bus {
+        twim: Twim<TWIM0>,
+        background: bool,
+    }
+
+    /// Longest transfer that can be sent from flash, by copying it to RAM.
+    /// Only short command sequences are kept in flash.
+    const COPY_LEN: usize = 32;
+
+    /// The TWIM's registers. `Twim` keeps them to itself, and only has
+    /// blocking transfers that can't be abandoned.
+    fn twim_regs() -> &'static twim0::RegisterBlock {
+        // SAFETY: `Bus` owns TWIM0, and only uses it while the queue is idle,
+        // when the interrupt leaves it alone.
+        unsafe { &*TWIM0::ptr() }
+    }
+
+    /// Clear the events and error flags left by the last transfer.
+    fn clear_events(regs: &twim0::RegisterBlock) {
+        regs.events_stopped.reset();
+        regs.events_error.reset();
+        regs.events_lasttx.reset();
+        regs.events_lastrx.reset();
+        regs.errorsrc
+            .write(|w| w.anack().bit(true).dnack().bit(true).overrun().bit(true));
+    }
+
+    /// Point EasyDMA at `bytes` for `address` and start sending, stopping
+    /// after the last byte. `bytes` has to be in RAM and stay put until the
+    /// transfer stops.
+    fn start_write(regs: &twim0::RegisterBlock, address: u8, bytes: &[u8], len: u16) {
+        compiler_fence(SeqCst);
+        clear_events(regs);
+        regs.address.write(|w| unsafe { w.address().bits(address) });
+        regs.txd
+            .ptr
+            .write(|w| unsafe { w.ptr().bits(bytes.as_ptr() as u32) });
+        regs.txd.maxcnt.write(|w| unsafe { w.maxcnt().bits(len) });
+        regs.shorts.write(|w| w.lasttx_stop().enabled());
+        regs.tasks_starttx.write(|w| unsafe { w.bits(1) });
+    }
+
+    /// How the transfer that just stopped went.
+    fn outcome(regs: &twim0::RegisterBlock) -> Result<(), twim::Error> {
+        compiler_fence(SeqCst);
+        let errors = regs.errorsrc.read();
+        if errors.anack().is_received() {
+            Err(twim::Error::AddressNack)
+        } else if errors.dnack().is_received() || errors.overrun().is_received() {
+            Err(twim::Error::DataNack)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether `bytes` is somewhere EasyDMA can read.
+    fn in_ram(bytes: &[u8]) -> bool {
+        let start = bytes.as_ptr() as usize;
+        start >= SRAM_LOWER && start + bytes.len() <= SRAM_UPPER
+    }
+
+    impl Bus {
+        fn regs(&self) -> &twim0::RegisterBlock {
+            twim_regs()
+        }
+
+        /// Queue writes for the interrupt to send from now on, or go back to
+        /// sending them straight away.
+        fn set_background(&mut self, background: bool) {
+            self.background = background;
+        }
+
+        /// Get the interrupt sending, if it isn't already.
+        fn kick(&self) {
+            free(|cs| {
+                let queue = QUEUE.borrow(cs).borrow();
+                if !queue.in_flight() && !queue.is_idle() {
+                    NVIC::pend(Interrupt::SPIM0_SPIS0_TWIM0_TWIS0_SPI0_TWI0);
+                }
+            });
+        }
+
+        /// Wait for the queue to empty, then report anything that failed in
+        /// it. Gives up on it all once `expired`.
+        fn drain(&mut self, expired: &mut dyn FnMut() -> bool) -> Result<(), Timed<twim::Error>> {
+            self.kick();
+            while !free(|cs| QUEUE.borrow(cs).borrow().is_idle()) {
+                if expired() {
+                    self.drop_queue();
+                    return Err(Timed::Timeout);
+                }
+            }
+            match free(|cs| QUEUE.borrow(cs).borrow_mut().take_error()) {
+                Some(error) => Err(Timed::Bus(error)),
+                None => Ok(()),
+            }
+        }
+
+        /// Abandon everything queued, including the write in flight.
+        fn drop_queue(&mut self) {
+            free(|cs| {
+                self.regs()
+                    .intenclr
+                    .write(|w| w.stopped().clear().error().clear());
+                let mut queue = QUEUE.borrow(cs).borrow_mut();
+                if queue.in_flight() {
+                    self.abort();
+                }
+                queue.fail(twim::Error::Transmit);
+                queue.take_error();
+            });
+        }
+
+        /// Clear the events and error flags left by the last transfer.
+        fn clear(&self) {
+            clear_events(self.regs());
+        }
+
+        /// Wait for the transfer that was just started to stop, or give up
+        /// on it once `expired`.
+        fn finish(&mut self, expired: &mut dyn FnMut() -> bool) -> Result<(), Timed<twim::Error>> {
+            loop {
+                let regs = self.regs();
+                if regs.events_stopped.read().bits() != 0 {
+                    regs.events_stopped.reset();
+                    break;
+                }
+                if regs.events_error.read().bits() != 0 {
+                    regs.events_error.reset();
+                    regs.tasks_stop.write(|w| unsafe { w.bits(1) });
+                }
+                if expired() {
+                    self.abort();
+                    return Err(Timed::Timeout);
+                }
+            }
+            outcome(self.regs()).map_err(Timed::Bus)
+        }
+
+        /// Abandon the current transfer. A STOP can't go out on a bus that
+        /// is held low, so the peripheral is switched off and on again too,
+        /// which drops the transfer whatever state it's in.
+        fn abort(&mut self) {
+            self.regs().tasks_stop.write(|w| unsafe { w.bits(1) });
+            self.twim.disable();
+            self.twim.enable();
+            self.clear();
+            compiler_fence(SeqCst);
+        }
+    }
+
+    impl Bus {
+        /// Change the clock for the transfers after this one. Anything still
+        /// queued is dropped: the speed only changes after transfers have
+        /// been failing anyway.
+        fn set_speed(&mut self, speed: Speed) {
+            if !free(|cs| QUEUE.borrow(cs).borrow().is_idle()) {
+                self.drop_queue();
+            }
+            self.regs()
+                .frequency
+                .write(|w| w.frequency().variant(frequency(speed)));
+        }
+    }
+
+    /// The TWIM setting for `speed`.
+    fn frequency(speed: Speed) -> twim::Frequency {
+        match speed {
+            Speed::K100 => twim::Frequency::K100,
+            Speed::K250 => twim::Frequency::K250,
+            Speed::K400 => twim::Frequency::K400,
+        }
+    }
+
+    impl Abortable for Bus {
+        type Error = twim::Error;
+
+        fn write_until(
+            &mut self,
+            address: u8,
+            bytes: &[u8],
+            expired: &mut dyn FnMut() -> bool,
+        ) -> Result<(), Timed<twim::Error>> {
+            if bytes.is_empty() {
+                return Err(Timed::Bus(twim::Error::TxBufferZeroLength));
+            }
+            if self.background {
+                loop {
+                    let queued = free(|cs| {
+                        let mut queue = QUEUE.borrow(cs).borrow_mut();
+                        queue.write(address, bytes).map_err(|_| queue.is_idle())
+                    });
+                    match queued {
+                        Ok(()) => {
+                            self.kick();
+                            return Ok(());
+                        }
+                        // Too long for a batch; it goes out below instead.
+                        Err(true) => break,
+                        // Room is made as the batch going out finishes.
+                        Err(false) => {
+                            self.kick();
+                            if expired() {
+                                self.drop_queue();
+                                return Err(Timed::Timeout);
+                            }
+                        }
+                    }
+                }
+            }
+            self.drain(expired)?;
+
+            // EasyDMA can only read RAM, so constants are copied out of flash,
+            // whole: split up, the later parts would go without the control
+            // byte at the front.
+            let mut copy = [0; COPY_LEN];
+            let bytes = if in_ram(bytes) {
+                bytes
+            } else {
+                let copy = copy
+                    .get_mut(..bytes.len())
+                    .ok_or(Timed::Bus(twim::Error::TxBufferTooLong))?;
+                copy.copy_from_slice(bytes);
+                copy
+            };
+            let len =
+                u16::try_from(bytes.len()).map_err(|_| Timed::Bus(twim::Error::TxBufferTooLong))?;
+
+            // SAFETY (of the DMA): `bytes` outlives the transfer, which is
+            // finished or aborted before this returns.
+            start_write(self.regs(), address, bytes, len);
+
+            self.finish(expired)?;
+            if self.regs().txd.amount.read().bits() != u32::from(len) {
+                return Err(Timed::Bus(twim::Error::Transmit));
+            }
+            Ok(())
+        }
+
+        fn read_until(
+            &mut self,
+            address: u8,
+            buffer: &mut [u8],
+            expired: &mut dyn FnMut() -> bool,
+        ) -> Result<(), Timed<twim::Error>> {
+            if buffer.is_empty() {
+                return Err(Timed::Bus(twim::Error::RxBufferZeroLength));
+            }
+            let len = u16::try_from(buffer.len())
+                .map_err(|_| Timed::Bus(twim::Error::RxBufferTooLong))?;
+            self.drain(expired)?;
+
+            compiler_fence(SeqCst);
+            self.clear();
+            let regs = self.regs();
+            regs.address.write(|w| unsafe { w.address().bits(address) });
+            // SAFETY: as for writes; a `&mut` slice is always in RAM.
+            regs.rxd
+                .ptr
+                .write(|w| unsafe { w.ptr().bits(buffer.as_mut_ptr() as u32) });
+            regs.rxd.maxcnt.write(|w| unsafe { w.maxcnt().bits(len) });
+            regs.shorts.write(|w| w.lastrx_stop().enabled());
+            regs.tasks_startrx.write(|w| unsafe { w.bits(1) });
+
+            self.finish(expired)?;
+            if self.regs().rxd.amount.read().bits() != u32::from(len) {
+                return Err(Timed::Bus(twim::Error::Receive));
+            }
+            Ok(())
+        }
+
+        fn diagnose(error: &twim::Error) -> BusFault {
+            match error {
+                twim::Error::AddressNack => BusFault::AddressNack,
+                twim::Error::DataNack => BusFault::DataNack,
+                _ => BusFault::Other,
+            }
+        }
+
+        fn queued(&self) -> usize {
+            free(|cs| QUEUE.borrow(cs).borrow().queued())
+        }
+    }
+
+    /// Microseconds from `TIMER2` free-running at 1 MHz, to time bus
+    /// transfers and frame flushes. Shared by reference, since reading it
+    /// changes nothing.
+    struct Stopwatch(Timer<TIMER2, Periodic>);
+
+    impl Stopwatch {
+        fn new(timer: TIMER2) -> Self {
+            let mut timer = Timer::periodic(timer);
+            timer.start(u32::MAX);
+            Self(timer)
+        }
+
+        /// Stop it, so it doesn't keep the 16 MHz clock running in standby.
+        /// Nothing goes over the bus meanwhile, so nothing is timed.
+        fn pause(&self) {
+            self.0.task_stop().write(|w| unsafe { w.bits(1) });
+        }
+
+        /// Start it again where it stopped.
+        fn resume(&self) {
+            self.0.task_start().write(|w| unsafe { w.bits(1) });
+        }
+    }
+
+    impl Micros for &Stopwatch {
+        fn now_us(&mut self) -> u32 {
+            self.0.read()
+        }
+    }
+
+    /// Ticks a second of the RTC's 32.768 kHz clock.
+    const RTC_HZ: u64 = 32_768;
+
+    /// The RTC counter's 24 bits.
+    const RTC_MASK: u32 = 0xFF_FFFF;
+
+    /// Milliseconds since boot, from `RTC0` counting the 32.768 kHz clock,
+    /// which keeps going while the CPU sleeps. It runs off the internal RC
+    /// oscillator, good to a couple of percent. Has to be read at least once
+    /// per counter wrap (about 8 minutes), which never sleeping longer than
+    /// `STANDBY_TICK_MS` sees to.
+    struct Clock {
+        rtc: Rtc<RTC0>,
+        last: u32,
+        ticks: u64,
+    }
+
+    impl Clock {
+        /// Start counting. The 32.768 kHz clock has to be running.
+        fn new(rtc: RTC0) -> Self {
+            let mut rtc = Rtc::new(rtc, 0).unwrap();
+            // The interrupt stays masked in the NVIC: pending, it wakes `WFE`.
+            rtc.enable_event(RtcInterrupt::Compare0);
+            rtc.enable_interrupt(RtcInterrupt::Compare0, None);
+            rtc.enable_counter();
+            Self {
+                last: rtc.get_counter(),
+                rtc,
+                ticks: 0,
+            }
+        }
+
+        fn now_ms(&mut self) -> u32 {
+            let now = self.rtc.get_counter();
+            self.ticks += (now.wrapping_sub(self.last) & RTC_MASK) as u64;
+            self.last = now;
+            (self.ticks * 1000 / RTC_HZ) as u32
+        }
+
+        /// Have the RTC wake the CPU `ms` from now.
+        fn wake_in(&mut self, ms: u32) {
+            self.rtc.reset_event(RtcInterrupt::Compare0);
+            NVIC::unpend(Interrupt::RTC0);
+            // The RTC misses a compare less than 2 ticks ahead.
+            let ticks = (ms as u64 * RTC_HZ).div_ceil(1000).max(2) as u32;
+            let at = self.rtc.get_counter().wrapping_add(ticks) & RTC_MASK;
+            self.rtc.set_compare(RtcCompareReg::Compare0, at).unwrap();
+        }
+    }
+
+    /// The system control register's bit for waking `WFE` when an
+    /// interrupt goes pending, masked or not.
+    const SCR_SEVONPEND: u32 = 1 << 4;
+
+    /// Sleep until `clock` is due to wake the CPU in `ms`, a button goes
+    /// down, or an interrupt comes. Both the RTC's and the buttons' interrupts
+    /// stay masked, and wake `WFE` by going pending, so each is cleared first
+    /// to let it go pending again. Anything that happens after that, even
+    /// before `WFE`, leaves the event register set and it returns at once.
+    fn sleep(clock: &mut Clock, gpiote: &Gpiote, ms: u32) {
+        gpiote.port().reset_events();
+        NVIC::unpend(Interrupt::GPIOTE);
+        clock.wake_in(ms);
+        asm::wfe();
+    }
+
+    /// The SPI clock for the OLED. Modules are rated for 10 MHz, and 8 is
+    /// the fastest the SPIM offers under that.
+    #[cfg(feature = "spi")]
+    const SPI_FREQUENCY: spim::Frequency = spim::Frequency::M8;
+    /// [`SPI_FREQUENCY`] in MHz, for the link line.
+    #[cfg(feature = "spi")]
+    const SPI_MHZ: u32 = 8;
+
+    /// What the main loop needs from the board besides the OLED and the LED
+    /// matrix.
+    struct Hardware<'a> {
+        clock: Clock,
+        flash: Nvmc<NVMC>,
+        i2c: &'a RefCell<Deadline<Bus, &'a Stopwatch>>,
+        stopwatch: &'a Stopwatch,
+        scanner: Scanner,
+        /// The accelerometer, if one answered at boot.
+        accel: Option<Lsm303agr<Twim<TWIM1>>>,
+    }
+
+    impl Platform for Hardware<'_> {
+        fn save(&mut self, settings: &Settings) {
+            settings.save(&mut self.flash).ok();
+        }
+
+        fn set_speed(&mut self, speed: Speed) {
+            self.i2c.borrow_mut().inner().set_speed(speed);
+        }
+
+        fn set_background(&mut self, background: bool) {
+            self.i2c.borrow_mut().inner().set_background(background);
+        }
+
+        fn scan<D: Oled>(&mut self, display: &mut D) -> Step {
+            self.scanner.step(self.i2c, display)
+        }
+
+        fn accel(&mut self) -> Option<Accel> {
+            self.accel.as_mut()?.accel().ok()
+        }
+
+        fn standby(&mut self) {
+            if let Some(accel) = &mut self.accel {
+                accel.power_down().ok();
+            }
+            pause_leds(&mut self.clock);
+            self.stopwatch.pause();
+        }
+
+        fn wake(&mut self) {
+            // The stopwatch first, to time the bus again.
+            self.stopwatch.resume();
+            resume_leds();
+            if let Some(accel) = &mut self.accel {
+                accel.init().ok();
+            }
+        }
+
+        #[cfg(feature = "spi")]
+        fn link(&self, _: Speed) -> Link {
+            Link::Spi { mhz: SPI_MHZ }
+        }
+    }
+
+    /// A GPIO pin set up to drive one of the OLED's SPI lines, idling high.
+    #[cfg(feature = "spi")]
+    fn spi_line<MODE>(pin: Pin<MODE>) -> Pin<Output<PushPull>> {
+        pin.into_push_pull_output(Level::High)
+    }
+
+    #[entry]
+    fn main() -> ! {
+        let board = Board::take().unwrap();
+        let button_a = board.buttons.button_a.degrade();
+        let button_b = board.buttons.button_b.degrade();
+        // The RTC keeps time on the 32.768 kHz clock, and wakes the CPU.
+        let _clocks = Clocks::new(board.CLOCK).start_lfclk();
+        // Button A held through reset starts the bus scanner instead.
+        let scanning = button_a.is_low().unwrap();
+        // Button B held through reset moves the bus on to the next speed.
+        let next_speed = button_b.is_low().unwrap();
+        let mut clock = Clock::new(board.RTC0);
+        // Masked interrupts wake `WFE` too, as they go pending. The buttons
+        // wake it by going low.
+        // SAFETY: only sets SEVONPEND in the system control register.
+        unsafe { board.SCB.scr.modify(|scr| scr | SCR_SEVONPEND) };
+        let gpiote = Gpiote::new(board.GPIOTE);
+        gpiote.port().input_pin(&button_a).low();
+        gpiote.port().input_pin(&button_b).low();
+        gpiote.port().enable_interrupt();
+
+        let leds = Display::new(board.TIMER1, board.display_pins);
+        free(|cs| DISPLAY.borrow(cs).replace(Some(leds)));
+        // SAFETY: the handler only touches `DISPLAY`, behind a critical section.
+        unsafe { pac::NVIC::unmask(pac::Interrupt::TIMER1) };
+
+        // SAFETY: `Board` doesn't take the NVMC, so this is the only handle.
+        let nvmc = unsafe { pac::Peripherals::steal() }.NVMC;
+        let mut flash: Nvmc<NVMC> = Nvmc::new(nvmc, settings_page());
+        let mut settings = Settings::load(&mut flash);
+        if next_speed {
+            let speed = settings.i2c_speed.unwrap_or_default().next();
+            settings.i2c_speed = Some(speed);
+            settings.save(&mut flash).ok();
+        }
+        let speed = settings.i2c_speed.unwrap_or_default();
+
+        // Use the external I2C bus (pins 19/20 on edge connector). Make sure
+        // nothing is holding it before the TWIM takes the pins.
+        let pins: twim::Pins = board.i2c_external.into();
+        let mut scl = GpioLine::new(pins.scl);
+        let mut sda = GpioLine::new(pins.sda);
+        let mut delay = Delay::new(board.SYST);
+        let recovery = recovery::recover(&mut scl, &mut sda, &mut delay);
+        let pins = twim::Pins {
+            scl: scl.into_input(),
+            sda: sda.into_input(),
+        };
+        // Every transfer is timed, so a bus that locks up later can't hang
+        // the device.
+        let stopwatch = Stopwatch::new(board.TIMER2);
+        let twim = Twim::new(board.TWIM0, pins, frequency(speed));
+        let bus = Bus {
+            twim,
+            background: false,
+        };
+        let i2c = RefCell::new(Deadline::new(bus, &stopwatch));
+        // SAFETY: the handler only touches `QUEUE`, behind a critical
+        // section, and the TWIM while the queue has a write in flight.
+        unsafe { NVIC::unmask(Interrupt::SPIM0_SPIS0_TWIM0_TWIS0_SPI0_TWI0) };
+
+        // Set up the OLED where it was last found, 0x3C the first time; the
+        // panel falls back to 0x3D by itself, and drives an SH1106 as one.
+        #[cfg(not(feature = "spi"))]
+        let panel = {
+            let address = settings.oled_address.unwrap_or(bus::ADDRESSES[0]);
+            Panel::new(&i2c, address, PanelSize {}).only(CONTROLLER)
+        };
+        // Or on SPI: SCK on pin 13, MOSI on 15, D/C on 16, CS on 12 and RST
+        // on 2. Not on 8 or 9: those are the NFC pads, and don't work as GPIO
+        // until UICR.NFCPINS is cleared. The I2C bus is still there for the
+        // scanner.
+        #[cfg(feature = "spi")]
+        let panel = {
+            // SAFETY: `Board` doesn't take SPIM2 either, so this is the only handle.
+            let spim = unsafe { pac::Peripherals::steal() }.SPIM2;
+            let pins = spim::Pins {
+                sck: board.pins.p0_17.into_push_pull_output(Level::Low).degrade(),
+                mosi: Some(board.pins.p0_13.into_push_pull_output(Level::Low).degrade()),
+                miso: None,
+            };
+            let spi = Spim::new(spim, pins, SPI_FREQUENCY, spim::MODE_0, 0);
+            SpiPanel::new(
+                spi,
+                spi_line(board.pins.p1_02.degrade()),
+                spi_line(board.pins.p0_12.degrade()),
+                spi_line(board.pins.p0_04.degrade()),
+                delay,
+                PanelSize {},
+            )
+        };
+
+        // The accelerometer is on the internal bus, on a TWIM of its own.
+        // Only on-board parts share it, so it isn't timed. Without one that
+        // answers, everything stays the right way up.
+        // SAFETY: `Board` doesn't take TWIM1, so this is the only handle.
+        let twim1 = unsafe { pac::Peripherals::steal() }.TWIM1;
+        let internal = Twim::new(twim1, board.i2c_internal.into(), twim::Frequency::K400);
+        let mut accel = Lsm303agr::new(internal);
+        let accel = accel.init().is_ok().then_some(accel);
+
+        // From here the loop only feeds the library's app the time and the
+        // buttons, and sleeps until it next wants polling.
+        let now_ms = clock.now_ms();
+        let mut app = App::new(panel, &stopwatch, InterruptMatrix, settings, now_ms)
+            .with_recovery(recovery)
+            .with_accelerometer(accel.is_some())
+            .held_at_reset(scanning, next_speed, now_ms);
+        let mut hardware = Hardware {
+            clock,
+            flash,
+            i2c: &i2c,
+            stopwatch: &stopwatch,
+            scanner: Scanner::new(),
+            accel,
+        };
         loop {
-            led_display.show(&mut timer, x_pattern, 1000);
-            led_display.clear();
-            timer.delay_ms(500u32);
-        }
-    }
-
-    // Show checkmark on LED matrix - init succeeded!
-    let check = [
-        [0, 0, 0, 0, 1],
-        [0, 0, 0, 1, 0],
-        [1, 0, 1, 0, 0],
-        [0, 1, 0, 0, 0],
-        [0, 0, 0, 0, 0],
-    ];
-    led_display.show(&mut timer, check, 1000);
-    led_display.clear();
-
-    // Draw "Hello World!" on OLED
-    display.clear(BinaryColor::Off).ok();
-    let text_style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
-    Text::new("Hello Tony of Time!", Point::new(0, 10), text_style)
-        .draw(&mut display)
-        .ok();
-    display.flush().ok();
-
-    // Show heart on LED matrix - display updated!
-    let heart = [
-        [0, 1, 0, 1, 0],
-        [1, 0, 1, 0, 1],
-        [1, 0, 0, 0, 1],
-        [0, 1, 0, 1, 0],
-        [0, 0, 1, 0, 0],
-    ];
-    led_display.show(&mut timer, heart, 2000);
-    led_display.clear();
-
-    loop {
-        timer.delay_ms(1000u32);
+            let now_ms = hardware.clock.now_ms();
+            let a_down = button_a.is_low().unwrap();
+            let b_down = button_b.is_low().unwrap();
+            app.poll(now_ms, a_down, b_down, &mut hardware);
+            let now_ms = hardware.clock.now_ms();
+            sleep(&mut hardware.clock, &gpiote, app.sleep_ms(now_ms));
+        }
+    }
+
+    /// Sends the queued writes: each time one stops, start the next, until
+    /// the queue is empty. `main` pends it to get it going.
+    #[interrupt]
+    fn SPIM0_SPIS0_TWIM0_TWIS0_SPI0_TWI0() {
+        let regs = twim_regs();
+        free(|cs| {
+            let mut queue = QUEUE.borrow(cs).borrow_mut();
+            if queue.in_flight() {
+                if regs.events_error.read().bits() != 0 {
+                    regs.events_error.reset();
+                    regs.tasks_stop.write(|w| unsafe { w.bits(1) });
+                }
+                if regs.events_stopped.read().bits() == 0 {
+                    return;
+                }
+                regs.events_stopped.reset();
+                queue.finished(outcome(regs));
+            }
+            match queue.start_next() {
+                Some((address, bytes)) => {
+                    regs.intenset.write(|w| w.stopped().set().error().set());
+                    // A batch is at most `BATCH_BYTES`, so this always fits.
+                    start_write(regs, address, bytes, bytes.len() as u16);
+                }
+                None => regs.intenclr.write(|w| w.stopped().clear().error().clear()),
+            }
+        });
     }
+
+    #[interrupt]
+    fn TIMER1() {
+        free(|cs| {
+            if let Some(display) = DISPLAY.borrow(cs).borrow_mut().as_mut() {
+                display.handle_display_event();
+            }
+        });
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+fn main() {
+    eprintln!("microbit-oled is firmware: build it for thumbv7em-none-eabihf and flash it");
 }
@@ -0,0 +1,224 @@
+//! Text sliding sideways along a strip of the OLED.
+//!
+//! The SSD1306 can scroll a range of its pages by itself, straight across or
+//! diagonally, and once it's started that costs no CPU time and no bus
+//! traffic. [`Scroll`] says which pages, which way and how fast, for
+//! [`Oled::start_scroll`]. The SH1106 has nothing like it, so there
+//! [`Marquee`] moves the text itself, a column at a time at about the speed
+//! the controller would, and redraws the strip.
+//!
+//! The controller scrolls what's in its RAM, wrapping it round from one side
+//! to the other, so it can only go round with text that fits across the
+//! panel. Longer text is moved by hand on either controller, all of it, with
+//! a gap before it comes round again. RAM written while the controller
+//! scrolls comes out scrambled, so the drivers stop the scroll before any
+//! flush that changes something, and send the whole frame.
+
+use display_interface::DisplayError;
+use embedded_graphics::geometry::Dimensions;
+use ssd1306::command::NFrames;
+
+use crate::{display::Oled, screens};
+
+/// Which way a [`Scroll`] goes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Direction {
+    /// Right to left, the way text is read.
+    #[default]
+    Left,
+    Right,
+}
+
+/// How many frames the controller shows between steps of a [`Scroll`]: the
+/// eight it can do.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interval {
+    Frames2,
+    Frames3,
+    Frames4,
+    #[default]
+    Frames5,
+    Frames25,
+    Frames64,
+    Frames128,
+    Frames256,
+}
+
+/// The controllers' oscillator, roughly, at the clock they're set up with.
+const OSC_HZ: u64 = 370_000;
+
+/// Oscillator clocks the controller spends on each row: the two pre-charge
+/// phases, and 50 more.
+const CLOCKS_PER_ROW: u64 = 53;
+
+impl Interval {
+    /// Frames between steps.
+    pub const fn frames(self) -> u32 {
+        match self {
+            Interval::Frames2 => 2,
+            Interval::Frames3 => 3,
+            Interval::Frames4 => 4,
+            Interval::Frames5 => 5,
+            Interval::Frames25 => 25,
+            Interval::Frames64 => 64,
+            Interval::Frames128 => 128,
+            Interval::Frames256 => 256,
+        }
+    }
+
+    /// About how long a step takes on a panel of `rows` rows. The oscillator
+    /// varies from part to part, so it's only near enough to keep a marquee
+    /// moved by hand at much the same speed.
+    pub const fn step_us(self, rows: u32) -> u32 {
+        let frame_us = rows as u64 * CLOCKS_PER_ROW * 1_000_000 / OSC_HZ;
+        (frame_us * self.frames() as u64) as u32
+    }
+}
+
+impl From<Interval> for NFrames {
+    fn from(interval: Interval) -> Self {
+        match interval {
+            Interval::Frames2 => NFrames::F2,
+            Interval::Frames3 => NFrames::F3,
+            Interval::Frames4 => NFrames::F4,
+            Interval::Frames5 => NFrames::F5,
+            Interval::Frames25 => NFrames::F25,
+            Interval::Frames64 => NFrames::F64,
+            Interval::Frames128 => NFrames::F128,
+            Interval::Frames256 => NFrames::F256,
+        }
+    }
+}
+
+/// A scroll for the controller to run by itself.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Scroll {
+    /// The first page (8-pixel band down the panel) that moves.
+    pub first_page: u8,
+    /// The last page that moves; can be the first.
+    pub last_page: u8,
+    pub direction: Direction,
+    pub interval: Interval,
+    /// Rows the panel moves up at each step as well, for a diagonal scroll,
+    /// or 0 to go straight across. Every row moves up, not just the pages
+    /// that go across.
+    pub rows: u8,
+}
+
+impl Scroll {
+    /// Pages `first_page` to `last_page`, straight across `direction` every
+    /// `interval`.
+    pub const fn horizontal(
+        first_page: u8,
+        last_page: u8,
+        direction: Direction,
+        interval: Interval,
+    ) -> Self {
+        Self {
+            first_page,
+            last_page,
+            direction,
+            interval,
+            rows: 0,
+        }
+    }
+
+    /// The same, moving up `rows` at each step as well.
+    pub const fn diagonal(self, rows: u8) -> Self {
+        Self { rows, ..self }
+    }
+}
+
+/// How a [`Marquee`] is moving.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Motion {
+    Still,
+    /// The controller is scrolling it.
+    Hardware,
+    /// It's being moved by hand, and is this many columns along.
+    Software(u32),
+}
+
+/// A line of text going round a strip of the panel.
+#[derive(Clone, Copy, Debug)]
+pub struct Marquee<'a> {
+    text: &'a str,
+    scroll: Scroll,
+    started_ms: u32,
+    motion: Motion,
+}
+
+impl<'a> Marquee<'a> {
+    /// `text` going round `scroll`'s pages, not started yet. The text only
+    /// ever goes straight across, whatever `scroll` says about rows.
+    pub const fn new(text: &'a str, scroll: Scroll) -> Self {
+        Self {
+            text,
+            scroll: scroll.diagonal(0),
+            started_ms: 0,
+            motion: Motion::Still,
+        }
+    }
+
+    /// Draw the text where it starts and send it, then set the controller
+    /// scrolling it, or get ready to move it by hand if it can't or the
+    /// text is too long for it.
+    pub fn start<D: Oled>(&mut self, display: &mut D, now_ms: u32) -> Result<(), DisplayError> {
+        self.motion = Motion::Still;
+        // A failed draw only leaves the strip blank; the flush is what matters.
+        screens::marquee(display, self.text, &self.scroll, 0).ok();
+        display.flush()?;
+        self.started_ms = now_ms;
+        let fits = screens::marquee_span(display, self.text) == display.bounding_box().size.width;
+        self.motion = if fits && display.start_scroll(self.scroll)? {
+            Motion::Hardware
+        } else {
+            Motion::Software(0)
+        };
+        Ok(())
+    }
+
+    /// Whether the controller is doing the moving.
+    pub fn in_hardware(&self) -> bool {
+        self.motion == Motion::Hardware
+    }
+
+    /// When it's moved by hand, draw it where it should be by `now_ms`, if
+    /// that's somewhere new, and say whether it needs flushing. It goes by
+    /// the time rather than counting steps, so a slow flush skips columns
+    /// rather than falling behind.
+    pub fn poll<D: Oled>(&mut self, display: &mut D, now_ms: u32) -> bool {
+        let Motion::Software(moved) = self.motion else {
+            return false;
+        };
+        let span = screens::marquee_span(display, self.text);
+        let rows = display.bounding_box().size.height;
+        let step_us = self.scroll.interval.step_us(rows).max(1);
+        let elapsed_us = now_ms.wrapping_sub(self.started_ms) as u64 * 1000;
+        let columns = (elapsed_us / step_us as u64 % span as u64) as u32;
+        if columns == moved {
+            return false;
+        }
+        self.motion = Motion::Software(columns);
+        screens::marquee(display, self.text, &self.scroll, columns).ok();
+        true
+    }
+
+    /// When it's moved by hand on `display`, how long until it's due to move
+    /// on a column. `None` if it isn't.
+    pub fn due_in_ms<D: Dimensions>(&self, display: &D, now_ms: u32) -> Option<u32> {
+        let Motion::Software(_) = self.motion else {
+            return None;
+        };
+        let rows = display.bounding_box().size.height;
+        let step_us = self.scroll.interval.step_us(rows).max(1) as u64;
+        let elapsed_us = now_ms.wrapping_sub(self.started_ms) as u64 * 1000;
+        Some((step_us - elapsed_us % step_us).div_ceil(1000) as u32)
+    }
+
+    /// Stop it where it is.
+    pub fn stop<D: Oled>(&mut self, display: &mut D) -> Result<(), DisplayError> {
+        self.motion = Motion::Still;
+        display.stop_scroll()
+    }
+}
@@ -0,0 +1,84 @@
+//! The settings screen, worked with the two buttons.
+//!
+//! Once the greeting is up, button A opens the menu and steps through the
+//! [`Item`]s, closing it again after the last one; button B changes the item
+//! picked. [`screens::settings`](crate::screens::settings) draws it.
+
+use crate::{input::Button, settings::Settings};
+
+/// Something that can be changed on the settings screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Item {
+    /// The OLED's brightness level.
+    Brightness,
+    /// Night mode, on or off.
+    NightMode,
+    /// The screensaver.
+    Saver,
+}
+
+impl Item {
+    /// Every item, in the order they're listed.
+    pub const ALL: [Item; 3] = [Item::Brightness, Item::NightMode, Item::Saver];
+
+    /// The item after this one, if there is one.
+    pub const fn next(self) -> Option<Item> {
+        match self {
+            Item::Brightness => Some(Item::NightMode),
+            Item::NightMode => Some(Item::Saver),
+            Item::Saver => None,
+        }
+    }
+
+    /// The item's position in [`ALL`](Item::ALL).
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Whether the settings screen is open, and on which item.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Menu {
+    item: Option<Item>,
+}
+
+impl Menu {
+    /// Closed.
+    pub const fn new() -> Self {
+        Self { item: None }
+    }
+
+    /// The item picked, while the menu is open.
+    pub const fn item(&self) -> Option<Item> {
+        self.item
+    }
+
+    /// Whether the settings screen is showing.
+    pub const fn is_open(&self) -> bool {
+        self.item.is_some()
+    }
+
+    /// Close the menu without going through the rest of the items.
+    pub fn close(&mut self) {
+        self.item = None;
+    }
+
+    /// Act on `button`, changing `settings`. B does nothing while the menu
+    /// is closed. A setting that was never set starts from its default.
+    pub fn press(&mut self, button: Button, settings: &mut Settings) {
+        match (button, self.item) {
+            (Button::A, None) => self.item = Some(Item::ALL[0]),
+            (Button::A, Some(item)) => self.item = item.next(),
+            (Button::B, Some(Item::Brightness)) => {
+                settings.brightness = Some(settings.brightness.unwrap_or_default().next());
+            }
+            (Button::B, Some(Item::NightMode)) => {
+                settings.night_mode = Some(!settings.night_mode.unwrap_or(false));
+            }
+            (Button::B, Some(Item::Saver)) => {
+                settings.saver = Some(settings.saver.unwrap_or_default().next());
+            }
+            (Button::B, None) => {}
+        }
+    }
+}
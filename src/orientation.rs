@@ -0,0 +1,272 @@
+//! Turning the OLED and the LED matrix round when the board is upside down.
+//!
+//! [`Tracker`] takes accelerometer readings and works out which way up the
+//! board is, only changing its mind once the new way has held for
+//! [`SETTLE_MS`], so a knock or a wave doesn't flip the screen. Lying flat
+//! or on its side it keeps to the way it last was. [`Rotated`] and
+//! [`RotatedLeds`] turn what's drawn on the OLED and lit on the matrix.
+//!
+//! Only half turns: the screens are laid out for a panel wider than it's
+//! tall, and a quarter turn would leave the greeting a few letters across.
+
+use display_interface::DisplayError;
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*, Pixel};
+
+use crate::{
+    display::{forward_oled, Oled},
+    error::Error,
+    led::NonBlockingMatrix,
+    lsm303agr::Accel,
+    marquee::{Direction, Scroll},
+    patterns::Pattern,
+};
+
+/// Gravity along the board's length, in mg, before it counts as one way up
+/// or the other: tilted about 30° from lying flat.
+pub const TILT_MG: i16 = 500;
+
+/// How long a new way up has to hold before the display turns.
+pub const SETTLE_MS: u32 = 1000;
+
+/// How often to read the accelerometer.
+pub const POLL_MS: u32 = 100;
+
+/// Which way up the board is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Orientation {
+    /// Logo at the top, edge connector at the bottom.
+    #[default]
+    Upright,
+    /// Edge connector at the top.
+    UpsideDown,
+}
+
+impl Orientation {
+    /// Which way up a board reading `accel` is, if it's stood up enough to
+    /// say. On the micro:bit v2 the sensor's Y axis runs along the board
+    /// towards the logo, so it reads +1 g upright.
+    pub fn of(accel: Accel) -> Option<Orientation> {
+        let along = accel.y.unsigned_abs();
+        if along < TILT_MG.unsigned_abs() || along < accel.x.unsigned_abs() {
+            return None;
+        }
+        Some(if accel.y > 0 {
+            Orientation::Upright
+        } else {
+            Orientation::UpsideDown
+        })
+    }
+}
+
+/// Works out which way up the board is from a reading every [`POLL_MS`].
+#[derive(Clone, Copy, Debug)]
+pub struct Tracker {
+    orientation: Orientation,
+    /// A different way up, seen in every reading since `since_ms`.
+    candidate: Option<Orientation>,
+    since_ms: u32,
+    polled_ms: u32,
+}
+
+impl Tracker {
+    /// Upright until it's seen otherwise, with the first reading due now.
+    pub const fn new(now_ms: u32) -> Self {
+        Self {
+            orientation: Orientation::Upright,
+            candidate: None,
+            since_ms: now_ms,
+            polled_ms: now_ms.wrapping_sub(POLL_MS),
+        }
+    }
+
+    /// Which way up the board is, as far as it's sure.
+    pub const fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Whether it's time for another reading.
+    pub const fn is_due(&self, now_ms: u32) -> bool {
+        now_ms.wrapping_sub(self.polled_ms) >= POLL_MS
+    }
+
+    /// How long until the next reading is due.
+    pub const fn due_in_ms(&self, now_ms: u32) -> u32 {
+        POLL_MS.saturating_sub(now_ms.wrapping_sub(self.polled_ms))
+    }
+
+    /// The reading due at `now_ms` couldn't be taken: try again after
+    /// [`POLL_MS`].
+    pub fn missed(&mut self, now_ms: u32) {
+        self.polled_ms = now_ms;
+    }
+
+    /// Take `accel`, read at `now_ms`. Returns the new way up when that
+    /// changes.
+    pub fn update(&mut self, now_ms: u32, accel: Accel) -> Option<Orientation> {
+        self.polled_ms = now_ms;
+        match Orientation::of(accel) {
+            Some(seen) if seen != self.orientation => {
+                if self.candidate != Some(seen) {
+                    self.candidate = Some(seen);
+                    self.since_ms = now_ms;
+                } else if now_ms.wrapping_sub(self.since_ms) >= SETTLE_MS {
+                    self.orientation = seen;
+                    self.candidate = None;
+                    return Some(seen);
+                }
+            }
+            // Back the way it was, or lying down: start again.
+            _ => self.candidate = None,
+        }
+        None
+    }
+}
+
+/// An OLED drawn the way up the board is. Turning it only changes what's
+/// drawn from then on, so the screen wants drawing again after.
+#[derive(Debug)]
+pub struct Rotated<D> {
+    display: D,
+    orientation: Orientation,
+}
+
+impl<D: Oled> Rotated<D> {
+    /// `display`, drawn upright until told otherwise.
+    pub const fn new(display: D) -> Self {
+        Self {
+            display,
+            orientation: Orientation::Upright,
+        }
+    }
+
+    /// Draw for a board `orientation` up.
+    pub fn set(&mut self, orientation: Orientation) {
+        self.orientation = orientation;
+    }
+
+    /// Which way up it draws.
+    pub const fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// The display underneath.
+    pub fn inner(&mut self) -> &mut D {
+        &mut self.display
+    }
+}
+
+impl<D: Oled> OriginDimensions for Rotated<D> {
+    fn size(&self) -> Size {
+        self.display.bounding_box().size
+    }
+}
+
+impl<D: Oled> DrawTarget for Rotated<D> {
+    type Color = BinaryColor;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        if self.orientation == Orientation::Upright {
+            return self.display.draw_iter(pixels);
+        }
+        let size = self.size();
+        let corner = Point::new(size.width as i32 - 1, size.height as i32 - 1);
+        self.display.draw_iter(
+            pixels
+                .into_iter()
+                .map(|Pixel(point, color)| Pixel(corner - point, color)),
+        )
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.display.clear(color)
+    }
+}
+
+impl<D: Oled> Oled for Rotated<D> {
+    fn init(&mut self) -> Result<(), Error> {
+        self.display.init()
+    }
+
+    /// Upside down, the pages counted from the other end and going the other
+    /// way. A diagonal scroll still moves the controller's rows up, which is
+    /// down the turned screen.
+    fn start_scroll(&mut self, scroll: Scroll) -> Result<bool, DisplayError> {
+        if self.orientation == Orientation::Upright {
+            return self.display.start_scroll(scroll);
+        }
+        let last_page = (self.size().height / 8) as u8 - 1;
+        self.display.start_scroll(Scroll {
+            first_page: last_page.saturating_sub(scroll.last_page),
+            last_page: last_page.saturating_sub(scroll.first_page),
+            direction: match scroll.direction {
+                Direction::Left => Direction::Right,
+                Direction::Right => Direction::Left,
+            },
+            ..scroll
+        })
+    }
+
+    forward_oled!(
+        flush,
+        check,
+        address,
+        flushed_bytes,
+        set_brightness,
+        stop_scroll,
+        set_display_on
+    );
+}
+
+/// An LED matrix lit the way up the board is. Turning it turns what's
+/// showing straight away.
+#[derive(Debug)]
+pub struct RotatedLeds<M> {
+    matrix: M,
+    orientation: Orientation,
+    /// What it was last told to show, the right way up.
+    shown: Pattern,
+}
+
+impl<M: NonBlockingMatrix> RotatedLeds<M> {
+    /// `matrix`, upright until told otherwise.
+    pub const fn new(matrix: M) -> Self {
+        Self {
+            matrix,
+            orientation: Orientation::Upright,
+            shown: Pattern::BLANK,
+        }
+    }
+
+    /// Light the matrix for a board `orientation` up.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        if orientation != self.orientation {
+            self.orientation = orientation;
+            self.set(self.shown);
+        }
+    }
+
+    /// Show what it was last told to again, after the matrix underneath was
+    /// set to something else.
+    pub fn refresh(&mut self) {
+        self.set(self.shown);
+    }
+
+    /// The matrix underneath.
+    pub fn inner(&mut self) -> &mut M {
+        &mut self.matrix
+    }
+}
+
+impl<M: NonBlockingMatrix> NonBlockingMatrix for RotatedLeds<M> {
+    fn set(&mut self, pattern: Pattern) {
+        self.shown = pattern;
+        self.matrix.set(match self.orientation {
+            Orientation::Upright => pattern,
+            Orientation::UpsideDown => pattern.rotate_180(),
+        });
+    }
+}
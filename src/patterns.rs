@@ -0,0 +1,367 @@
+//! 5x5 LED matrix patterns.
+//!
+//! Patterns are written as five strings of `.` (off) and `#` (on), top row
+//! first, and parsed by a `const fn`, so a malformed pattern is a compile
+//! error rather than a garbled matrix. Digits `1` to `9` give an LED a
+//! brightness level instead; `#` is the brightest, [`MAX_BRIGHTNESS`]:
+//!
+//! ```
+//! use microbit_oled::patterns::Pattern;
+//!
+//! const TOP_LEFT: Pattern = Pattern::from_rows(["#....", ".....", ".....", ".....", "....."]);
+//! assert!(TOP_LEFT.is_lit(0, 0));
+//! ```
+//!
+//! The transformations are `const` too, which is how most of the arrows below
+//! are made.
+
+use core::ops::{BitAnd, BitOr, BitXor, Not};
+
+/// The brightest an LED gets, matching the greyscale levels of
+/// `microbit::display::nonblocking`. 0 is off.
+pub const MAX_BRIGHTNESS: u8 = 9;
+
+/// One frame for the LED matrix, row by row, in the layout
+/// `microbit::display::nonblocking` expects. Each cell is a brightness from
+/// 0 to [`MAX_BRIGHTNESS`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Pattern([[u8; 5]; 5]);
+
+impl Pattern {
+    /// Nothing lit.
+    pub const BLANK: Pattern = Pattern([[0; 5]; 5]);
+
+    /// Everything lit.
+    pub const FULL: Pattern = Pattern([[MAX_BRIGHTNESS; 5]; 5]);
+
+    /// A pattern from brightness levels, top row first. Levels above
+    /// [`MAX_BRIGHTNESS`] are capped.
+    pub const fn new(rows: [[u8; 5]; 5]) -> Self {
+        Self(rows).dimmed(MAX_BRIGHTNESS)
+    }
+
+    /// Parse five rows of `.`, `#` and `1` to `9`, top row first.
+    ///
+    /// # Panics
+    ///
+    /// If a row is not exactly five characters or contains anything else. In a
+    /// `const` this fails the build:
+    ///
+    /// ```compile_fail
+    /// use microbit_oled::patterns::Pattern;
+    ///
+    /// const TYPO: Pattern = Pattern::from_rows([".#.#.", ".#.#.", "..o..", "#...#", ".###."]);
+    /// ```
+    pub const fn from_rows(rows: [&str; 5]) -> Self {
+        let mut cells = [[0; 5]; 5];
+        let mut y = 0;
+        while y < 5 {
+            let row = rows[y].as_bytes();
+            assert!(row.len() == 5, "pattern rows must be 5 characters wide");
+            let mut x = 0;
+            while x < 5 {
+                cells[y][x] = match row[x] {
+                    b'.' => 0,
+                    b'#' => MAX_BRIGHTNESS,
+                    level @ b'1'..=b'9' => level - b'0',
+                    _ => panic!("pattern rows may only contain '.', '#' and '1' to '9'"),
+                };
+                x += 1;
+            }
+            y += 1;
+        }
+        Self(cells)
+    }
+
+    /// The brightness levels, top row first.
+    pub const fn rows(self) -> [[u8; 5]; 5] {
+        self.0
+    }
+
+    /// Whether the LED in column `x`, row `y` is lit. Out of range is dark.
+    pub const fn is_lit(self, x: usize, y: usize) -> bool {
+        x < 5 && y < 5 && self.0[y][x] != 0
+    }
+
+    /// How bright the LED in column `x`, row `y` is. Out of range is 0.
+    pub const fn brightness(self, x: usize, y: usize) -> u8 {
+        if x < 5 && y < 5 {
+            self.0[y][x]
+        } else {
+            0
+        }
+    }
+
+    /// The same pattern with the LED at `x`, `y` switched fully on or off.
+    pub const fn with(self, x: usize, y: usize, on: bool) -> Self {
+        self.with_brightness(x, y, if on { MAX_BRIGHTNESS } else { 0 })
+    }
+
+    /// The same pattern with the LED at `x`, `y` set to `level`, capped at
+    /// [`MAX_BRIGHTNESS`].
+    pub const fn with_brightness(mut self, x: usize, y: usize, level: u8) -> Self {
+        if x < 5 && y < 5 {
+            self.0[y][x] = if level > MAX_BRIGHTNESS {
+                MAX_BRIGHTNESS
+            } else {
+                level
+            };
+        }
+        self
+    }
+
+    /// The same pattern with no LED brighter than `level`.
+    pub const fn dimmed(self, level: u8) -> Self {
+        let mut cells = self.0;
+        let mut i = 0;
+        while i < 25 {
+            if cells[i / 5][i % 5] > level {
+                cells[i / 5][i % 5] = level;
+            }
+            i += 1;
+        }
+        Self(cells)
+    }
+
+    /// How many LEDs are lit.
+    pub const fn lit_count(self) -> usize {
+        let mut count = 0;
+        let mut i = 0;
+        while i < 25 {
+            if self.0[i / 5][i % 5] != 0 {
+                count += 1;
+            }
+            i += 1;
+        }
+        count
+    }
+
+    /// Fully lit where `self` is dark and the other way round.
+    pub const fn invert(self) -> Self {
+        let mut cells = [[0; 5]; 5];
+        let mut i = 0;
+        while i < 25 {
+            if self.0[i / 5][i % 5] == 0 {
+                cells[i / 5][i % 5] = MAX_BRIGHTNESS;
+            }
+            i += 1;
+        }
+        Self(cells)
+    }
+
+    /// Lit where either pattern is lit, at the brighter of the two levels.
+    pub const fn union(self, other: Self) -> Self {
+        self.combine(other, Combine::Or)
+    }
+
+    /// Lit where both patterns are lit, at the dimmer of the two levels.
+    pub const fn intersect(self, other: Self) -> Self {
+        self.combine(other, Combine::And)
+    }
+
+    /// Lit where exactly one of the patterns is lit, at its level.
+    pub const fn difference(self, other: Self) -> Self {
+        self.combine(other, Combine::Xor)
+    }
+
+    /// Turned a quarter turn clockwise.
+    pub const fn rotate_cw(self) -> Self {
+        self.remap(Remap::Clockwise)
+    }
+
+    /// Turned a quarter turn anticlockwise.
+    pub const fn rotate_ccw(self) -> Self {
+        self.remap(Remap::Anticlockwise)
+    }
+
+    /// Turned upside down.
+    pub const fn rotate_180(self) -> Self {
+        self.remap(Remap::HalfTurn)
+    }
+
+    /// Mirrored left to right.
+    pub const fn flip_horizontal(self) -> Self {
+        self.remap(Remap::FlipHorizontal)
+    }
+
+    /// Mirrored top to bottom.
+    pub const fn flip_vertical(self) -> Self {
+        self.remap(Remap::FlipVertical)
+    }
+
+    /// Moved `dx` columns right and `dy` rows down (negative for left and up).
+    /// LEDs pushed off the edge are lost and the gap is dark.
+    pub const fn shift(self, dx: i32, dy: i32) -> Self {
+        let mut cells = [[0; 5]; 5];
+        let mut i: usize = 0;
+        while i < 25 {
+            let (x, y) = ((i % 5) as i32, (i / 5) as i32);
+            let (from_x, from_y) = (x - dx, y - dy);
+            if from_x >= 0 && from_x < 5 && from_y >= 0 && from_y < 5 {
+                cells[y as usize][x as usize] = self.0[from_y as usize][from_x as usize];
+            }
+            i += 1;
+        }
+        Self(cells)
+    }
+
+    const fn combine(self, other: Self, how: Combine) -> Self {
+        let mut cells = [[0; 5]; 5];
+        let mut i = 0;
+        while i < 25 {
+            let (a, b) = (self.0[i / 5][i % 5], other.0[i / 5][i % 5]);
+            cells[i / 5][i % 5] = match how {
+                Combine::Or if a > b => a,
+                Combine::Or => b,
+                Combine::And if a < b => a,
+                Combine::And => b,
+                Combine::Xor if a == 0 => b,
+                Combine::Xor if b == 0 => a,
+                Combine::Xor => 0,
+            };
+            i += 1;
+        }
+        Self(cells)
+    }
+
+    const fn remap(self, how: Remap) -> Self {
+        let mut cells = [[0; 5]; 5];
+        let mut i = 0;
+        while i < 25 {
+            let (x, y) = (i % 5, i / 5);
+            // Which cell of `self` ends up at x, y.
+            let (from_x, from_y) = match how {
+                Remap::Clockwise => (y, 4 - x),
+                Remap::Anticlockwise => (4 - y, x),
+                Remap::HalfTurn => (4 - x, 4 - y),
+                Remap::FlipHorizontal => (4 - x, y),
+                Remap::FlipVertical => (x, 4 - y),
+            };
+            cells[y][x] = self.0[from_y][from_x];
+            i += 1;
+        }
+        Self(cells)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Combine {
+    Or,
+    And,
+    Xor,
+}
+
+#[derive(Clone, Copy)]
+enum Remap {
+    Clockwise,
+    Anticlockwise,
+    HalfTurn,
+    FlipHorizontal,
+    FlipVertical,
+}
+
+impl From<[[u8; 5]; 5]> for Pattern {
+    fn from(rows: [[u8; 5]; 5]) -> Self {
+        Self::new(rows)
+    }
+}
+
+impl From<Pattern> for [[u8; 5]; 5] {
+    fn from(pattern: Pattern) -> Self {
+        pattern.0
+    }
+}
+
+impl Not for Pattern {
+    type Output = Pattern;
+
+    fn not(self) -> Pattern {
+        self.invert()
+    }
+}
+
+impl BitOr for Pattern {
+    type Output = Pattern;
+
+    fn bitor(self, other: Pattern) -> Pattern {
+        self.union(other)
+    }
+}
+
+impl BitAnd for Pattern {
+    type Output = Pattern;
+
+    fn bitand(self, other: Pattern) -> Pattern {
+        self.intersect(other)
+    }
+}
+
+impl BitXor for Pattern {
+    type Output = Pattern;
+
+    fn bitxor(self, other: Pattern) -> Pattern {
+        self.difference(other)
+    }
+}
+
+// Icons.
+
+/// Shown at power-on to say the program started.
+pub const SMILEY: Pattern = Pattern::from_rows([".#.#.", ".#.#.", ".....", "#...#", ".###."]);
+
+/// A frown.
+pub const SAD: Pattern = Pattern::from_rows([".#.#.", ".#.#.", ".....", ".###.", "#...#"]);
+
+/// The greeting made it onto the OLED.
+pub const HEART: Pattern = Pattern::from_rows([".#.#.", "#.#.#", "#...#", ".#.#.", "..#.."]);
+
+/// A smaller heart, for beating with [`HEART`].
+pub const HEART_SMALL: Pattern = Pattern::from_rows([".....", ".#.#.", ".###.", "..#..", "....."]);
+
+/// A hollow square around the edge.
+pub const SQUARE: Pattern = Pattern::from_rows(["#####", "#...#", "#...#", "#...#", "#####"]);
+
+/// A single LED in the middle.
+pub const DOT: Pattern = Pattern::from_rows([".....", ".....", "..#..", ".....", "....."]);
+
+// Status glyphs.
+
+/// The OLED initialised; more generally, all good.
+pub const CHECK: Pattern = Pattern::from_rows(["....#", "...#.", "#.#..", ".#...", "....."]);
+
+/// Blinked when the OLED fails to initialise; more generally, an error.
+pub const CROSS: Pattern = Pattern::from_rows(["#...#", ".#.#.", "..#..", ".#.#.", "#...#"]);
+
+/// Something needs attention.
+pub const EXCLAMATION: Pattern = Pattern::from_rows(["..#..", "..#..", "..#..", ".....", "..#.."]);
+
+/// Something is unknown or undecided.
+pub const QUESTION: Pattern = Pattern::from_rows([".###.", "#...#", "..##.", ".....", "..#.."]);
+
+/// Busy; the frames of a spinner can be made by rotating it.
+pub const BUSY: Pattern = Pattern::from_rows(["..#..", "..#..", "..#..", ".....", "....."]);
+
+// Arrows, named by the compass direction they point in, up being north.
+
+/// Points up.
+pub const ARROW_N: Pattern = Pattern::from_rows(["..#..", ".###.", "#.#.#", "..#..", "..#.."]);
+/// Points right.
+pub const ARROW_E: Pattern = ARROW_N.rotate_cw();
+/// Points down.
+pub const ARROW_S: Pattern = ARROW_N.rotate_180();
+/// Points left.
+pub const ARROW_W: Pattern = ARROW_N.rotate_ccw();
+
+/// Points up and to the right.
+pub const ARROW_NE: Pattern = Pattern::from_rows(["..###", "...##", "..#.#", ".#...", "#...."]);
+/// Points down and to the right.
+pub const ARROW_SE: Pattern = ARROW_NE.rotate_cw();
+/// Points down and to the left.
+pub const ARROW_SW: Pattern = ARROW_NE.rotate_180();
+/// Points up and to the left.
+pub const ARROW_NW: Pattern = ARROW_NE.rotate_ccw();
+
+/// All eight arrows, clockwise from north.
+pub const ARROWS: [Pattern; 8] = [
+    ARROW_N, ARROW_NE, ARROW_E, ARROW_SE, ARROW_S, ARROW_SW, ARROW_W, ARROW_NW,
+];
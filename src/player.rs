@@ -0,0 +1,90 @@
+//! Plays boot [`Step`]s on a [`NonBlockingMatrix`] without blocking.
+//!
+//! Nothing waits for a pattern to finish: the caller polls the [`Player`] with
+//! the time and hands it the next step whenever it has room. Because the next
+//! step is asked for as soon as the current one starts, the work behind it
+//! (initialising the OLED, drawing and flushing) runs while the current
+//! pattern is still up, and the LED timing stays the same as when played
+//! blocking.
+
+use crate::{boot::Step, led::NonBlockingMatrix, patterns::Pattern};
+
+/// Keeps time for the step being shown and holds the one after it.
+#[derive(Clone, Debug, Default)]
+pub struct Player {
+    /// The step being shown and when it started.
+    current: Option<(Step, u32)>,
+    next: Option<Step>,
+    lit: bool,
+}
+
+impl Player {
+    /// A player with nothing to show.
+    pub const fn new() -> Self {
+        Self {
+            current: None,
+            next: None,
+            lit: false,
+        }
+    }
+
+    /// Whether [`queue`](Self::queue) would accept another step.
+    pub fn wants_step(&self) -> bool {
+        self.next.is_none()
+    }
+
+    /// Whether nothing is showing and nothing is queued.
+    pub fn is_idle(&self) -> bool {
+        self.current.is_none() && self.next.is_none()
+    }
+
+    /// How long until [`poll`](Self::poll) next changes the matrix: the step
+    /// showing ends, or the one queued can start. `None` with nothing showing
+    /// or queued.
+    pub fn due_in_ms(&self, now_ms: u32) -> Option<u32> {
+        match self.current {
+            Some((step, started_ms)) => Some(
+                step.duration_ms()
+                    .saturating_sub(now_ms.wrapping_sub(started_ms)),
+            ),
+            None => self.next.map(|_| 0),
+        }
+    }
+
+    /// Play `step` once the current one is over. A step that is already
+    /// queued is replaced.
+    pub fn queue(&mut self, step: Step) {
+        self.next = Some(step);
+    }
+
+    /// Bring the matrix up to date for `now_ms`, a millisecond clock that may
+    /// wrap. Call this often; a step ends on the first poll after its time is up.
+    pub fn poll<M: NonBlockingMatrix>(&mut self, now_ms: u32, leds: &mut M) {
+        if let Some((step, started_ms)) = self.current {
+            if now_ms.wrapping_sub(started_ms) < step.duration_ms() {
+                return;
+            }
+            self.current = None;
+        }
+
+        match self.next.take() {
+            Some(step) => {
+                match step {
+                    Step::Show { pattern, .. } => self.set(leds, pattern),
+                    Step::Wait { .. } => self.set(leds, Pattern::BLANK),
+                }
+                self.current = Some((step, now_ms));
+            }
+            None => self.set(leds, Pattern::BLANK),
+        }
+    }
+
+    fn set<M: NonBlockingMatrix>(&mut self, leds: &mut M, pattern: Pattern) {
+        // Only the first of a run of blank frames needs sending.
+        if pattern == Pattern::BLANK && !self.lit {
+            return;
+        }
+        leds.set(pattern);
+        self.lit = pattern != Pattern::BLANK;
+    }
+}
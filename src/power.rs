@@ -0,0 +1,172 @@
+//! Sleeping between events, and standby when left alone.
+//!
+//! The main loop does what's due and then sleeps with `WFE` until something
+//! wants it: an interrupt, a button going down, or the RTC at the next
+//! thing [`App`](crate::app::App) has to do. Once the greeting is up that's
+//! the OLED check every second, unless a step of the screensaver, the
+//! greeting going round or the accelerometer's reading comes sooner.
+//! After [`STANDBY_AFTER_MS`] without a press, [`Power`] puts the board in
+//! standby: the OLED turned off, the LED matrix dark and its timer stopped,
+//! the accelerometer powered down, and the RTC only waking the CPU every
+//! [`STANDBY_TICK_MS`] to keep the time. The next press brings everything
+//! back, and does nothing else. With the screensaver turned off the screen
+//! stays up for good, for panels that are meant to be read at any time.
+//!
+//! [`Mode::estimated_ua`] gives a rough current for each state, worked out
+//! from datasheet figures rather than measured, for sizing a battery.
+
+use crate::{boot::CHECK_MS, burnin::Saver, orientation::POLL_MS};
+
+/// How long without a button press before standby.
+pub const STANDBY_AFTER_MS: u32 = 30 * 60_000;
+
+/// How often the RTC wakes the main loop in standby, to keep the time. It
+/// never sleeps longer awake either.
+pub const STANDBY_TICK_MS: u32 = 1000;
+
+/// What the board is doing, for the simulator's status line and as far as
+/// current goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Booting, or going round the scanner.
+    Active,
+    /// Asleep until the next thing is due, with the OLED and the LED matrix
+    /// showing.
+    Idle,
+    /// Everything off but the RTC, waiting for a button.
+    Standby,
+}
+
+// Datasheet figures, not measurements.
+
+/// The nRF52833 running from flash at 64 MHz on its LDO regulator, which
+/// the firmware leaves in use.
+const CPU_UA: u32 = 6_000;
+/// The 16 MHz clock and the timers on it: the LED matrix's and the
+/// stopwatch for I2C transfers.
+const TIMERS_UA: u32 = 500;
+/// The nRF52833 asleep with its RAM kept and the RTC on the internal
+/// 32 kHz oscillator.
+const SLEEP_UA: u32 = 3;
+/// The accelerometer measuring 10 times a second, and powered down.
+const ACCEL_UA: u32 = 10;
+const ACCEL_OFF_UA: u32 = 2;
+/// A 128x32 SSD1306 module showing the greeting at the middle brightness.
+/// An all-lit panel takes about three times as much.
+const OLED_UA: u32 = 6_000;
+/// The panel turned off, its charge pump stopped.
+const OLED_OFF_UA: u32 = 10;
+/// The LED matrix showing the heart at full brightness, a row at a time.
+const LEDS_UA: u32 = 3_000;
+
+// What wakes the CPU when idle, from the schedule it keeps.
+
+/// The LED matrix's TIMER1 interrupts: one to move on a row every 6 ms,
+/// and one to end the row early for the greyscale.
+const LED_WAKES_PER_S: u32 = 2 * 1000 / 6;
+/// Each of those, at about 640 cycles.
+const LED_WAKE_US: u32 = 10;
+/// Reading the accelerometer's status and its three axes at 400 kHz.
+const ACCEL_READ_US: u32 = 300;
+/// Reading the OLED's status byte at 100 kHz.
+const CHECK_US: u32 = 400;
+/// How long the CPU is awake in each idle second.
+const IDLE_AWAKE_US: u32 =
+    LED_WAKES_PER_S * LED_WAKE_US + (1000 / POLL_MS) * ACCEL_READ_US + (1000 / CHECK_MS) * CHECK_US;
+
+impl Mode {
+    /// Roughly what the nRF52833, the accelerometer, the OLED and the LED
+    /// matrix draw between them, in µA. The micro:bit's USB interface chip
+    /// and regulator aren't counted, and real parts vary, so measure the
+    /// whole board before relying on it.
+    pub const fn estimated_ua(self) -> u32 {
+        match self {
+            Mode::Active => CPU_UA + TIMERS_UA + ACCEL_UA + OLED_UA + LEDS_UA,
+            Mode::Idle => {
+                CPU_UA * IDLE_AWAKE_US / 1_000_000
+                    + TIMERS_UA
+                    + SLEEP_UA
+                    + ACCEL_UA
+                    + OLED_UA
+                    + LEDS_UA
+            }
+            Mode::Standby => SLEEP_UA + ACCEL_OFF_UA + OLED_OFF_UA,
+        }
+    }
+
+    /// What it's called on the status line.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Mode::Active => "active",
+            Mode::Idle => "idle",
+            Mode::Standby => "standby",
+        }
+    }
+}
+
+/// Works out when to go into standby and when to come out.
+#[derive(Clone, Copy, Debug)]
+pub struct Power {
+    saver: Saver,
+    last_input_ms: u32,
+    standby: bool,
+}
+
+impl Power {
+    /// Awake, with `saver` chosen, counting idle time from `now_ms`.
+    pub const fn new(saver: Saver, now_ms: u32) -> Self {
+        Self {
+            saver,
+            last_input_ms: now_ms,
+            standby: false,
+        }
+    }
+
+    /// Go by the screensaver chosen on the settings screen. Turned off,
+    /// there's no standby.
+    pub fn set_saver(&mut self, saver: Saver) {
+        self.saver = saver;
+    }
+
+    /// Whether it's in standby.
+    pub const fn is_standby(&self) -> bool {
+        self.standby
+    }
+
+    /// A button was pressed at `now_ms`. Returns whether that brought it out
+    /// of standby, in which case the press should do nothing else.
+    pub fn input(&mut self, now_ms: u32) -> bool {
+        self.last_input_ms = now_ms;
+        core::mem::replace(&mut self.standby, false)
+    }
+
+    /// Returns `true`, once, when it's time to go into standby.
+    pub fn poll(&mut self, now_ms: u32) -> bool {
+        if self.standby
+            || self.saver == Saver::Off
+            || now_ms.wrapping_sub(self.last_input_ms) < STANDBY_AFTER_MS
+        {
+            return false;
+        }
+        self.standby = true;
+        true
+    }
+
+    /// How long until [`poll`](Self::poll) goes into standby, if nothing is
+    /// pressed meanwhile, or `None` if it won't.
+    pub fn due_in_ms(&self, now_ms: u32) -> Option<u32> {
+        if self.standby || self.saver == Saver::Off {
+            return None;
+        }
+        Some(STANDBY_AFTER_MS.saturating_sub(now_ms.wrapping_sub(self.last_input_ms)))
+    }
+
+    /// What it's doing, if it's awake and `busy` or not.
+    pub const fn mode(&self, busy: bool) -> Mode {
+        match (self.standby, busy) {
+            (true, _) => Mode::Standby,
+            (false, true) => Mode::Active,
+            (false, false) => Mode::Idle,
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Sending I2C writes in the background.
+//!
+//! A blocking flush keeps the CPU waiting on every byte: about 50 ms for a
+//! whole 128x32 frame at 100 kHz. The TWIM can send a write from RAM by
+//! itself with EasyDMA and raise an interrupt when it's done, so the firmware
+//! can put a flush's writes in a [`Queue`] instead and have the TWIM
+//! interrupt start each one as the last finishes. The queue keeps two
+//! batches: one going out, and one that new writes are added to meanwhile,
+//! so the next frame can be drawn and flushed while the last one is still
+//! on its way.
+//!
+//! A write that fails drops everything queued after it, since later writes
+//! only make sense after earlier ones, and the error is kept to be reported
+//! by the next blocking transfer.
+
+/// Room in each batch for writes and their headers.
+pub const BATCH_BYTES: usize = 1536;
+
+/// What each write takes in a batch on top of its bytes: the address and a
+/// two-byte length.
+const HEADER: usize = 3;
+
+/// There's no room for the write in the batch being filled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Full;
+
+/// Writes one after another, each as its address, length and bytes.
+#[derive(Clone, Debug)]
+struct Batch {
+    bytes: [u8; BATCH_BYTES],
+    len: usize,
+    /// Where the next write to send starts.
+    next: usize,
+}
+
+impl Batch {
+    const fn new() -> Self {
+        Self {
+            bytes: [0; BATCH_BYTES],
+            len: 0,
+            next: 0,
+        }
+    }
+
+    fn push(&mut self, address: u8, bytes: &[u8]) -> Result<(), Full> {
+        let end = self.len + HEADER + bytes.len();
+        if end > BATCH_BYTES {
+            return Err(Full);
+        }
+        let [low, high] = (bytes.len() as u16).to_le_bytes();
+        self.bytes[self.len..self.len + HEADER].copy_from_slice(&[address, low, high]);
+        self.bytes[self.len + HEADER..end].copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<(u8, &[u8])> {
+        if self.next >= self.len {
+            return None;
+        }
+        let [address, low, high] = [0, 1, 2].map(|i| self.bytes[self.next + i]);
+        let start = self.next + HEADER;
+        let end = start + u16::from_le_bytes([low, high]) as usize;
+        self.next = end;
+        Some((address, &self.bytes[start..end]))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Bytes of writes not yet handed out.
+    fn remaining(&self) -> usize {
+        self.len - self.next
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+}
+
+/// Writes waiting to go out on the bus, in two batches. `E` is what the bus
+/// reports when a write fails.
+#[derive(Clone, Debug)]
+pub struct Queue<E> {
+    batches: [Batch; 2],
+    /// The batch new writes are added to; the other one is being sent, if
+    /// `sending`.
+    filling: usize,
+    sending: bool,
+    /// A write has been handed out and hasn't finished.
+    in_flight: bool,
+    error: Option<E>,
+}
+
+impl<E> Queue<E> {
+    /// An empty queue.
+    pub const fn new() -> Self {
+        Self {
+            batches: [Batch::new(), Batch::new()],
+            filling: 0,
+            sending: false,
+            in_flight: false,
+            error: None,
+        }
+    }
+
+    /// Add a write of `bytes` to `address` to the batch being filled.
+    pub fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Full> {
+        self.batches[self.filling].push(address, bytes)
+    }
+
+    /// The next write to start, once the last one has
+    /// [finished](Queue::finished). When the batch being sent runs out, the
+    /// one being filled is sent next, and new writes go to the empty one.
+    pub fn start_next(&mut self) -> Option<(u8, &[u8])> {
+        debug_assert!(!self.in_flight);
+        let sending = 1 - self.filling;
+        if self.sending && self.batches[sending].remaining() == 0 {
+            self.batches[sending].clear();
+            self.sending = false;
+        }
+        if !self.sending {
+            if self.batches[self.filling].is_empty() {
+                return None;
+            }
+            self.filling = sending;
+            self.sending = true;
+        }
+        let write = self.batches[1 - self.filling].pop();
+        self.in_flight = write.is_some();
+        write
+    }
+
+    /// The write from [`start_next`](Queue::start_next) is done, with
+    /// `result`. A failure empties the queue and is kept for
+    /// [`take_error`](Queue::take_error).
+    pub fn finished(&mut self, result: Result<(), E>) {
+        self.in_flight = false;
+        if let Err(error) = result {
+            self.fail(error);
+        }
+    }
+
+    /// Drop everything queued, as if the write in flight had failed with
+    /// `error`. Only the first error is kept.
+    pub fn fail(&mut self, error: E) {
+        for batch in &mut self.batches {
+            batch.clear();
+        }
+        self.sending = false;
+        self.in_flight = false;
+        self.error.get_or_insert(error);
+    }
+
+    /// Whether a write has been handed out and hasn't finished.
+    pub const fn in_flight(&self) -> bool {
+        self.in_flight
+    }
+
+    /// Whether everything queued has gone out.
+    pub fn is_idle(&self) -> bool {
+        !self.in_flight && self.queued() == 0
+    }
+
+    /// Bytes still to go out, counting their headers. A transfer that has to
+    /// wait for them can allow for them in its time limit.
+    pub fn queued(&self) -> usize {
+        let sending = if self.sending {
+            self.batches[1 - self.filling].remaining()
+        } else {
+            0
+        };
+        sending + self.batches[self.filling].len
+    }
+
+    /// The error a background write failed with, if one has since the last
+    /// call.
+    pub fn take_error(&mut self) -> Option<E> {
+        self.error.take()
+    }
+}
+
+impl<E> Default for Queue<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,124 @@
+//! Freeing an I2C bus that a device is holding.
+//!
+//! A device reset or glitched in the middle of sending a byte keeps driving
+//! SDA low, waiting for clocks that never come, and the TWIM can't start a
+//! transfer on a bus like that. The standard fix (UM10204 §3.1.16) is to
+//! clock SCL by hand up to nine times until the device lets go of SDA, then
+//! send a STOP. [`recover`] does that on the raw pins before they are handed
+//! to the TWIM, and says what it found.
+
+use embedded_hal::blocking::delay::DelayUs;
+
+use crate::error::Error;
+
+/// Half an SCL period at 100 kHz.
+const HALF_PERIOD_US: u32 = 5;
+/// How long SCL may be held low (clock stretching) before it counts as stuck.
+const STRETCH_LIMIT_US: u32 = 1000;
+/// Clocks that are always enough to finish any byte plus its ACK.
+pub const MAX_CLOCKS: u8 = 9;
+
+/// One bus line driven open-drain by hand: pulled low, or let go so the
+/// pull-up can take it high.
+pub trait OpenDrain {
+    /// Stop driving the line.
+    fn release(&mut self);
+    /// Drive the line low.
+    fn pull_low(&mut self);
+    /// Whether the line is high right now.
+    fn is_high(&mut self) -> bool;
+}
+
+/// What [`recover`] found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Recovery {
+    /// Both lines were already high.
+    Idle,
+    /// A device was holding SDA and let go after `clocks` clocks; a STOP
+    /// was sent after it.
+    Recovered { clocks: u8 },
+    /// SDA was still low after [`MAX_CLOCKS`] clocks, or after the STOP.
+    SdaStuck,
+    /// SCL never went high: shorted, or missing its pull-up.
+    SclStuck,
+}
+
+impl Recovery {
+    /// The error to report, if the bus is unusable.
+    pub const fn error(self) -> Option<Error> {
+        match self {
+            Recovery::Idle | Recovery::Recovered { .. } => None,
+            Recovery::SdaStuck | Recovery::SclStuck => Some(Error::BusStuck),
+        }
+    }
+}
+
+/// Check the lines are idle, and if a device is holding SDA, clock it free
+/// and send a STOP. Both lines are released when this returns.
+pub fn recover<SCL, SDA, D>(scl: &mut SCL, sda: &mut SDA, delay: &mut D) -> Recovery
+where
+    SCL: OpenDrain,
+    SDA: OpenDrain,
+    D: DelayUs<u32>,
+{
+    scl.release();
+    sda.release();
+    delay.delay_us(HALF_PERIOD_US);
+
+    if !wait_for_high(scl, delay) {
+        return Recovery::SclStuck;
+    }
+    if sda.is_high() {
+        return Recovery::Idle;
+    }
+
+    for clocks in 1..=MAX_CLOCKS {
+        scl.pull_low();
+        delay.delay_us(HALF_PERIOD_US);
+        scl.release();
+        if !wait_for_high(scl, delay) {
+            return Recovery::SclStuck;
+        }
+        delay.delay_us(HALF_PERIOD_US);
+        if sda.is_high() {
+            return if stop(scl, sda, delay) {
+                Recovery::Recovered { clocks }
+            } else {
+                Recovery::SdaStuck
+            };
+        }
+    }
+    Recovery::SdaStuck
+}
+
+/// Send a STOP (SDA rising while SCL is high), starting with SCL high.
+/// Returns whether both lines ended up high.
+fn stop<SCL, SDA, D>(scl: &mut SCL, sda: &mut SDA, delay: &mut D) -> bool
+where
+    SCL: OpenDrain,
+    SDA: OpenDrain,
+    D: DelayUs<u32>,
+{
+    scl.pull_low();
+    delay.delay_us(HALF_PERIOD_US);
+    sda.pull_low();
+    delay.delay_us(HALF_PERIOD_US);
+    scl.release();
+    delay.delay_us(HALF_PERIOD_US);
+    sda.release();
+    delay.delay_us(HALF_PERIOD_US);
+    scl.is_high() && sda.is_high()
+}
+
+/// Let a device stretch the clock, up to [`STRETCH_LIMIT_US`].
+fn wait_for_high<L: OpenDrain, D: DelayUs<u32>>(line: &mut L, delay: &mut D) -> bool {
+    let mut waited_us = 0;
+    while !line.is_high() {
+        if waited_us >= STRETCH_LIMIT_US {
+            return false;
+        }
+        delay.delay_us(HALF_PERIOD_US);
+        waited_us += HALF_PERIOD_US;
+    }
+    true
+}
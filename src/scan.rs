@@ -0,0 +1,201 @@
+//! I2C bus scanner, for checking wiring without a multimeter.
+//!
+//! Holding button A at reset starts the firmware in this mode instead of the
+//! boot sequence. [`Scanner`] reads one byte from every 7-bit address on the
+//! bus, over and over, and lists whatever answers on the OLED if one of the
+//! responders is an OLED that comes up, or scrolls the addresses across the
+//! LED matrix if not. Parts this project is likely to meet are named.
+
+use core::{cell::RefCell, fmt::Write};
+
+use embedded_hal::blocking::i2c::Read;
+
+use crate::{
+    boot::Step,
+    bus,
+    display::Oled,
+    font, screens,
+    scroll::{Scroll, DEFAULT_COLUMN_MS},
+    text::TextBuf,
+};
+
+/// Lowest address scanned; below it is reserved (general call, CBUS, ...).
+pub const FIRST_ADDRESS: u8 = 0x08;
+/// Highest address scanned; above it is reserved (10-bit addressing, ...).
+pub const LAST_ADDRESS: u8 = 0x77;
+
+/// How long a result stays up on the matrix before the bus is scanned again,
+/// when the list is on the OLED.
+pub const RESCAN_MS: u32 = 1000;
+/// Pause after the addresses have scrolled by, before scanning again.
+pub const SCROLL_PAUSE_MS: u32 = 500;
+
+/// Characters of scrolling text: room for 32 addresses.
+const SCROLL_CHARS: usize = 3 * 32;
+
+/// The set of addresses that answered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Responders(u128);
+
+impl Responders {
+    /// Nobody.
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// Add `address` to the set.
+    pub const fn with(self, address: u8) -> Self {
+        Self(self.0 | 1 << (address & 0x7F))
+    }
+
+    /// Whether `address` answered.
+    pub const fn contains(&self, address: u8) -> bool {
+        self.0 & 1 << (address & 0x7F) != 0
+    }
+
+    /// How many addresses answered.
+    pub const fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Whether nothing answered.
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// The addresses that answered, lowest first.
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..=0x7F).filter(|address| self.contains(*address))
+    }
+}
+
+/// The part usually found at `address`, if it is one this project knows.
+pub const fn label(address: u8) -> Option<&'static str> {
+    match address {
+        0x3C | 0x3D => Some("SSD1306"),
+        0x76 | 0x77 => Some("BME280"),
+        0x68 => Some("DS3231"),
+        0x50..=0x57 => Some("AT24Cxx"),
+        _ => None,
+    }
+}
+
+/// Find out who answers on `bus`, by reading a byte from each address from
+/// [`FIRST_ADDRESS`] to [`LAST_ADDRESS`]. A read rather than a write, because
+/// it can't change anything on the device, and the nRF TWIM can't send an
+/// empty write.
+pub fn scan<I: Read>(bus: &mut I) -> Responders {
+    let mut found = Responders::new();
+    let mut byte = [0];
+    for address in FIRST_ADDRESS..=LAST_ADDRESS {
+        if bus.read(address, &mut byte).is_ok() {
+            found = found.with(address);
+        }
+    }
+    found
+}
+
+/// The scanner mode, stepped like [`Boot`](crate::boot::Boot).
+#[derive(Clone, Debug)]
+pub struct Scanner {
+    found: Responders,
+    /// Whether the bus needs scanning before the next step.
+    stale: bool,
+    /// Whether the OLED shows the current list.
+    on_oled: bool,
+    /// The addresses as scrolling text, for when there is no OLED.
+    text: TextBuf<SCROLL_CHARS>,
+    /// The next scroll frame to show.
+    frame: usize,
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scanner {
+    /// A scanner that hasn't looked at the bus yet.
+    pub const fn new() -> Self {
+        Self {
+            found: Responders::new(),
+            stale: true,
+            on_oled: false,
+            text: TextBuf::new(),
+            frame: 0,
+        }
+    }
+
+    /// What answered in the last scan.
+    pub fn found(&self) -> Responders {
+        self.found
+    }
+
+    /// Whether the last list went up on the OLED.
+    pub fn is_on_oled(&self) -> bool {
+        self.on_oled
+    }
+
+    /// Scan `bus` if it's time, and return what to show on the matrix next.
+    /// `display` sits on the same bus.
+    pub fn step<I: Read, D: Oled>(&mut self, bus: &RefCell<I>, display: &mut D) -> Step {
+        if self.stale {
+            self.rescan(bus, display);
+        }
+
+        if self.on_oled {
+            // The list is on screen; just show how long it is.
+            self.stale = true;
+            let count = match self.found.len() {
+                count @ 0..=9 => font::DIGITS[count],
+                _ => font::glyph('+').unwrap_or_default(),
+            };
+            return Step::Show {
+                pattern: count,
+                duration_ms: RESCAN_MS,
+            };
+        }
+
+        match Scroll::new(self.text.as_str(), DEFAULT_COLUMN_MS).nth(self.frame) {
+            Some(frame) => {
+                self.frame += 1;
+                frame.into()
+            }
+            None => {
+                self.stale = true;
+                Step::Wait {
+                    duration_ms: SCROLL_PAUSE_MS,
+                }
+            }
+        }
+    }
+
+    fn rescan<I: Read, D: Oled>(&mut self, bus: &RefCell<I>, display: &mut D) {
+        let found = scan(&mut *bus.borrow_mut());
+        let changed = found != self.found;
+        self.found = found;
+        self.stale = false;
+        self.frame = 0;
+
+        self.text.clear();
+        if found.is_empty() {
+            self.text.write_str("NONE").ok();
+        }
+        for (i, address) in found.iter().enumerate() {
+            let gap = if i == 0 { "" } else { " " };
+            write!(self.text, "{gap}{address:02X}").ok();
+        }
+
+        let oled_answered = bus::ADDRESSES.iter().any(|a| found.contains(*a));
+        if !oled_answered {
+            self.on_oled = false;
+        } else if !self.on_oled || changed {
+            // Only (re)initialise when the OLED has just turned up, so the
+            // list doesn't flicker on every pass.
+            let init = if self.on_oled { Ok(()) } else { display.init() };
+            self.on_oled =
+                init.is_ok() && screens::scan(display, &found).is_ok() && display.flush().is_ok();
+        }
+    }
+}
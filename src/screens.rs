@@ -0,0 +1,445 @@
+//! What gets drawn on the OLED.
+//!
+//! Nothing here assumes a panel size: each screen works out a [`Layout`] from
+//! the display it's given, wraps or shortens its text to fit across, and
+//! leaves out the lines that don't fit down.
+
+use core::fmt::Write;
+
+use embedded_graphics::{
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::Rectangle,
+    text::Text,
+};
+
+use crate::{
+    burnin::{self, Saver},
+    error::Error,
+    marquee::{Direction, Interval, Scroll},
+    menu::Item,
+    patterns,
+    scan::{self, Responders},
+    settings::Settings,
+    speed::Link,
+    text::TextBuf,
+};
+
+/// The text of the greeting screen.
+pub const GREETING: &str = "Hello Tony of Time!";
+
+/// How the greeting goes round, where it does: the first line's pages, a
+/// column every 25 frames (about 17 a second on a 16-row panel).
+pub const GREETING_SCROLL: Scroll = Scroll::horizontal(0, 1, Direction::Left, Interval::Frames25);
+
+/// Characters of `FONT_6X10` that fit across the widest (128-pixel) panel.
+pub const LINE_CHARS: usize = 21;
+
+/// One line of text on the panel.
+type Line = TextBuf<LINE_CHARS>;
+
+/// Width of a `FONT_6X10` character.
+const CHAR_WIDTH: u32 = 6;
+/// Distance between the baselines of two lines of text.
+const LINE_HEIGHT: u32 = 11;
+/// Baseline of the first line of text.
+const FIRST_BASELINE: i32 = 8;
+/// Baseline of the greeting's first line, which sits a little lower.
+const GREETING_BASELINE: i32 = 10;
+/// How far above its baseline a line of text reaches.
+const ASCENT: i32 = 9;
+
+/// How much text fits on a panel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+    /// Lines of text that fit down the panel, at least one.
+    pub lines: usize,
+    /// Characters that fit across, at most [`LINE_CHARS`].
+    pub chars: usize,
+    /// The panel's width in pixels.
+    pub width: u32,
+}
+
+impl Layout {
+    /// The layout for a panel of `size`.
+    pub const fn new(size: Size) -> Self {
+        // The last line needs no gap under it.
+        let lines = (size.height + 1) / LINE_HEIGHT;
+        let chars = (size.width / CHAR_WIDTH) as usize;
+        Self {
+            lines: if lines == 0 { 1 } else { lines as usize },
+            chars: if chars > LINE_CHARS {
+                LINE_CHARS
+            } else {
+                chars
+            },
+            width: size.width,
+        }
+    }
+
+    /// The layout for `display`.
+    pub fn of<D: Dimensions>(display: &D) -> Self {
+        Self::new(display.bounding_box().size)
+    }
+
+    /// Where line `line` of text starts.
+    pub const fn baseline(&self, line: usize) -> Point {
+        Point::new(0, FIRST_BASELINE + (LINE_HEIGHT as usize * line) as i32)
+    }
+}
+
+/// Clear the screen and draw the greeting, wrapped onto as many lines as it
+/// needs.
+pub fn hello<D>(display: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let layout = Layout::of(display);
+    display.clear(BinaryColor::Off)?;
+    let offset = Point::new(0, GREETING_BASELINE - FIRST_BASELINE);
+    for (line, text) in wrap(GREETING, layout.chars).take(layout.lines).enumerate() {
+        text_at(display, layout.baseline(line) + offset, text)?;
+    }
+    Ok(())
+}
+
+/// Whether all of the greeting fits on `display`. Where it doesn't, it can
+/// go round as a [`Marquee`](crate::marquee::Marquee) instead.
+pub fn hello_fits<D: Dimensions>(display: &D) -> bool {
+    let layout = Layout::of(display);
+    wrap(GREETING, layout.chars).count() <= layout.lines
+}
+
+/// Whether the greeting goes round on `display`: where it's all on one
+/// line, which an SSD1306 can scroll by itself, or where it doesn't fit.
+/// Wrapped onto the lines it needs, it stays still.
+pub fn hello_goes_round<D: Dimensions>(display: &D) -> bool {
+    let layout = Layout::of(display);
+    let lines = wrap(GREETING, layout.chars).count();
+    lines == 1 || lines > layout.lines
+}
+
+/// Clear the screen and show `error`: its code (the same as the LED blink
+/// count), what went wrong and what to check, as far as they fit.
+pub fn error<D>(display: &mut D, error: &Error) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let layout = Layout::of(display);
+    display.clear(BinaryColor::Off)?;
+
+    let mut line = Line::new();
+    write!(line, "ERROR E{}", error.code()).ok();
+    text_at(
+        display,
+        layout.baseline(0),
+        clip(line.as_str(), layout.chars),
+    )?;
+
+    let mut message = Line::new();
+    write!(message, "{error}").ok();
+    let next = paragraph(display, &layout, 1, message.as_str())?;
+    paragraph(display, &layout, next, error.hint())?;
+    Ok(())
+}
+
+/// Put the bus, its speed and how long a full frame took to flush on the bottom
+/// line, over whatever was there. Panels with room for only one line keep
+/// it for the greeting.
+pub fn link<D>(display: &mut D, link: Link, frame_us: u32) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let layout = Layout::of(display);
+    if layout.lines < 2 {
+        return Ok(());
+    }
+    let baseline = layout.baseline(layout.lines - 1);
+    display.fill_solid(
+        &Rectangle::new(
+            baseline - Point::new(0, ASCENT),
+            Size::new(layout.width, LINE_HEIGHT),
+        ),
+        BinaryColor::Off,
+    )?;
+
+    let mut line = Line::new();
+    let tenths_ms = (frame_us + 50) / 100;
+    write!(line, "{link} {}.{}ms", tenths_ms / 10, tenths_ms % 10).ok();
+    text_at(display, baseline, clip(line.as_str(), layout.chars))
+}
+
+/// Characters an item on the settings screen needs for its long name.
+const LONG_ITEM_CHARS: usize = 16;
+
+/// Clear the screen and list `settings` with their values, `picked`
+/// highlighted. A title goes on top if there's room for it as well, and on
+/// panels too short for every item the list starts far enough down to show
+/// the one picked.
+pub fn settings<D>(display: &mut D, picked: Item, settings: &Settings) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let layout = Layout::of(display);
+    display.clear(BinaryColor::Off)?;
+
+    let first = if layout.lines > Item::ALL.len() {
+        text_at(display, layout.baseline(0), clip("SETTINGS", layout.chars))?;
+        1
+    } else {
+        0
+    };
+    let shown = layout.lines - first;
+    let skip = (picked.index() + 1).saturating_sub(shown);
+    for (line, &item) in (first..layout.lines).zip(Item::ALL.iter().skip(skip)) {
+        let long = layout.chars >= LONG_ITEM_CHARS;
+        let level = settings.brightness.unwrap_or_default().number();
+        let night = on_off(settings.night_mode.unwrap_or(false));
+        let saver = settings.saver.unwrap_or_default().name();
+        let mut text = Line::new();
+        match (item, long) {
+            (Item::Brightness, true) => write!(text, "Brightness {level}/5"),
+            (Item::Brightness, false) => write!(text, "Bright {level}/5"),
+            (Item::NightMode, true) => write!(text, "Night mode {night}"),
+            (Item::NightMode, false) => write!(text, "Night {night}"),
+            (Item::Saver, true) => write!(text, "Screensaver {saver}"),
+            (Item::Saver, false) => write!(text, "Idle {saver}"),
+        }
+        .ok();
+        let at = layout.baseline(line);
+        let color = if item == picked {
+            display.fill_solid(
+                &Rectangle::new(
+                    at - Point::new(0, ASCENT),
+                    Size::new(layout.width, LINE_HEIGHT),
+                ),
+                BinaryColor::On,
+            )?;
+            BinaryColor::Off
+        } else {
+            BinaryColor::On
+        };
+        let text_style = MonoTextStyle::new(&FONT_6X10, color);
+        Text::new(clip(text.as_str(), layout.chars), at, text_style).draw(display)?;
+    }
+    Ok(())
+}
+
+const fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// How many pixels across each LED of the logo is.
+const LOGO_SCALE: u32 = 3;
+
+/// Clear the screen and draw frame `step` of `saver`: nothing, the heart
+/// from the LED matrix, or the time `uptime_ms` as hours, minutes and
+/// seconds. The heart and the clock move a pixel a frame, bouncing off the
+/// edges.
+pub fn saver<D>(display: &mut D, saver: Saver, step: u32, uptime_ms: u32) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    display.clear(BinaryColor::Off)?;
+    let size = display.bounding_box().size;
+    let at = |width: u32, height: u32| {
+        Point::new(
+            burnin::bounce(step, size.width.saturating_sub(width)) as i32,
+            burnin::bounce(step, size.height.saturating_sub(height)) as i32,
+        )
+    };
+    match saver {
+        Saver::Off | Saver::Blank => Ok(()),
+        Saver::Logo => {
+            let origin = at(5 * LOGO_SCALE, 5 * LOGO_SCALE);
+            for (y, row) in patterns::HEART.rows().into_iter().enumerate() {
+                for (x, led) in row.into_iter().enumerate() {
+                    if led == 0 {
+                        continue;
+                    }
+                    let corner = Point::new(x as i32, y as i32) * LOGO_SCALE as i32;
+                    display.fill_solid(
+                        &Rectangle::new(origin + corner, Size::new_equal(LOGO_SCALE)),
+                        BinaryColor::On,
+                    )?;
+                }
+            }
+            Ok(())
+        }
+        Saver::Clock => {
+            let seconds = uptime_ms / 1000;
+            let mut text = Line::new();
+            write!(
+                text,
+                "{}:{:02}:{:02}",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            )
+            .ok();
+            let width = text.as_str().len() as u32 * CHAR_WIDTH;
+            let origin = at(width, LINE_HEIGHT - 1);
+            text_at(
+                display,
+                origin + Point::new(0, FIRST_BASELINE),
+                text.as_str(),
+            )
+        }
+    }
+}
+
+/// Blank space after a marquee's text before it comes round again, when
+/// it's too long for the panel.
+const MARQUEE_GAP: u32 = 3 * CHAR_WIDTH;
+
+/// Columns a marquee of `text` on `display` moves through before it's back
+/// where it started: the panel's width if the text fits across, as when the
+/// controller scrolls it round, or else all of the text and a gap.
+pub fn marquee_span<D: Dimensions>(display: &D, text: &str) -> u32 {
+    let width = Layout::of(display).width;
+    let text_width = text.len() as u32 * CHAR_WIDTH;
+    if text_width <= width {
+        width
+    } else {
+        text_width + MARQUEE_GAP
+    }
+}
+
+/// Blank the pages `scroll` moves and draw `text` on them, `columns` along
+/// the way it goes. What goes off one side comes back on the other, after
+/// [`marquee_span`] columns. The text sits in the middle of the strip, or
+/// at the top of one too short for it.
+pub fn marquee<D>(
+    display: &mut D,
+    text: &str,
+    scroll: &Scroll,
+    columns: u32,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let layout = Layout::of(display);
+    let top = scroll.first_page as u32 * 8;
+    let height = (scroll.last_page.saturating_sub(scroll.first_page) as u32 + 1) * 8;
+    display.fill_solid(
+        &Rectangle::new(Point::new(0, top as i32), Size::new(layout.width, height)),
+        BinaryColor::Off,
+    )?;
+
+    let span = marquee_span(display, text) as i32;
+    let x = match scroll.direction {
+        Direction::Left => -(columns as i32 % span),
+        Direction::Right => columns as i32 % span,
+    };
+    let baseline = top as i32 + FIRST_BASELINE + height.saturating_sub(LINE_HEIGHT) as i32 / 2;
+    for x in [x - span, x, x + span] {
+        text_at(display, Point::new(x, baseline), text)?;
+    }
+    Ok(())
+}
+
+/// Characters in the widest scan entry, "3C SSD1306".
+const SLOT_CHARS: usize = 10;
+
+/// Clear the screen and list the `found` addresses: a count, then one or two
+/// to a line, as the width allows, with the part's name where it's known. If
+/// they don't all fit, the last slot says how many more there are.
+pub fn scan<D>(display: &mut D, found: &Responders) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let layout = Layout::of(display);
+    display.clear(BinaryColor::Off)?;
+
+    let mut line = Line::new();
+    match found.len() {
+        0 => write!(line, "I2C: nothing found"),
+        1 => write!(line, "I2C: 1 device"),
+        n => write!(line, "I2C: {n} devices"),
+    }
+    .ok();
+    text_at(
+        display,
+        layout.baseline(0),
+        clip(line.as_str(), layout.chars),
+    )?;
+
+    let columns = if layout.chars > 2 * SLOT_CHARS { 2 } else { 1 };
+    let slots = columns * (layout.lines - 1);
+    let column_x = layout.width as i32 / 2 + 2;
+    for (slot, address) in found.iter().enumerate().take(slots) {
+        let mut text = Line::new();
+        if slot == slots - 1 && found.len() > slots {
+            write!(text, "+{} more", found.len() - (slots - 1)).ok();
+        } else {
+            write!(text, "{address:02X} {}", scan::label(address).unwrap_or("")).ok();
+        }
+        let at =
+            layout.baseline(1 + slot / columns) + Point::new(column_x * (slot % columns) as i32, 0);
+        text_at(display, at, clip(text.as_str(), layout.chars / columns))?;
+    }
+    Ok(())
+}
+
+/// Draw one line of `text` with its baseline at `at`.
+fn text_at<D>(display: &mut D, at: Point, text: &str) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let text_style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    Text::new(text, at, text_style).draw(display)?;
+    Ok(())
+}
+
+/// Draw `text` wrapped from line `first` on, as far down as fits, and return
+/// the line after it.
+fn paragraph<D>(
+    display: &mut D,
+    layout: &Layout,
+    first: usize,
+    text: &str,
+) -> Result<usize, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let mut line = first;
+    for text in wrap(text, layout.chars) {
+        if line >= layout.lines {
+            break;
+        }
+        text_at(display, layout.baseline(line), text)?;
+        line += 1;
+    }
+    Ok(line)
+}
+
+/// The first `chars` characters of ASCII `text`.
+fn clip(text: &str, chars: usize) -> &str {
+    &text[..text.len().min(chars)]
+}
+
+/// `text` broken into lines of at most `chars` characters, between words
+/// where it can be and mid-word where a word is too long for a line.
+fn wrap(text: &str, chars: usize) -> impl Iterator<Item = &str> {
+    let mut rest = text.trim();
+    core::iter::from_fn(move || {
+        if rest.is_empty() || chars == 0 {
+            return None;
+        }
+        let (line, next) = if rest.len() <= chars {
+            (rest, "")
+        } else {
+            match rest[..=chars].rfind(' ') {
+                Some(space) if space > 0 => (&rest[..space], &rest[space..]),
+                _ => rest.split_at(chars),
+            }
+        };
+        rest = next.trim_start();
+        Some(line.trim_end())
+    })
+}
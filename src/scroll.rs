@@ -0,0 +1,104 @@
+//! Text scrolling across the LED matrix, right to left, one column at a time.
+//!
+//! For when there is no OLED to put a message on: status codes, readings and
+//! error names scroll by in the [`font`](crate::font).
+
+use core::{iter::Peekable, str::Chars};
+
+use crate::{font, led::Frame, patterns::Pattern};
+
+/// A comfortable reading speed: how long each column step stays up.
+pub const DEFAULT_COLUMN_MS: u32 = 150;
+
+/// The frames that scroll `text` across the matrix.
+///
+/// The first frame has the first column of text at the right edge, the last
+/// has the last column at the left edge. Characters missing from the font are
+/// drawn as `?`.
+#[derive(Clone, Debug)]
+pub struct Scroll<'a> {
+    columns: Columns<'a>,
+    window: Pattern,
+    column_ms: u32,
+    /// Blank columns still to feed in once the text has run out, to push the
+    /// end of it over to the left edge. `None` until the text has started.
+    tail: Option<usize>,
+}
+
+impl<'a> Scroll<'a> {
+    /// Scroll `text`, moving one column every `column_ms`.
+    pub fn new(text: &'a str, column_ms: u32) -> Self {
+        Self {
+            columns: Columns {
+                chars: text.chars().peekable(),
+                glyph: None,
+                column: 0,
+            },
+            window: Pattern::BLANK,
+            column_ms,
+            tail: None,
+        }
+    }
+}
+
+impl Iterator for Scroll<'_> {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Frame> {
+        let column = match (self.columns.next(), &mut self.tail) {
+            (Some(column), tail) => {
+                *tail = Some(4);
+                column
+            }
+            (None, Some(left)) if *left > 0 => {
+                *left -= 1;
+                [false; 5]
+            }
+            (None, _) => return None,
+        };
+
+        let mut window = self.window.shift(-1, 0);
+        for (y, lit) in column.into_iter().enumerate() {
+            window = window.with(4, y, lit);
+        }
+        self.window = window;
+
+        Some(Frame {
+            pattern: window,
+            duration_ms: self.column_ms,
+        })
+    }
+}
+
+/// The columns of `text` set in the font, one blank column between characters.
+#[derive(Clone, Debug)]
+struct Columns<'a> {
+    chars: Peekable<Chars<'a>>,
+    glyph: Option<(Pattern, usize)>,
+    column: usize,
+}
+
+impl Iterator for Columns<'_> {
+    type Item = [bool; 5];
+
+    fn next(&mut self) -> Option<[bool; 5]> {
+        loop {
+            if let Some((glyph, width)) = self.glyph {
+                if self.column < width {
+                    let x = self.column;
+                    self.column += 1;
+                    return Some(core::array::from_fn(|y| glyph.is_lit(x, y)));
+                }
+                self.glyph = None;
+                if self.chars.peek().is_some() {
+                    return Some([false; 5]);
+                }
+            }
+
+            let c = self.chars.next()?;
+            let glyph = font::glyph(c).or(font::glyph('?')).unwrap_or_default();
+            self.glyph = Some((glyph, font::width(glyph)));
+            self.column = 0;
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Settings that survive a reset, kept in a page of flash.
+//!
+//! [`Settings`] is stored as one small record at the start of the page: a
+//! magic number (which doubles as a version), then one byte per setting. A
+//! byte left at the erased value `0xFF` means "not set", so a blank page, a
+//! page from an older layout or a failed read all load as the defaults. New
+//! settings go on the end, where older records left the flash erased.
+
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+
+use crate::{brightness::Level, burnin::Saver, speed::Speed};
+
+/// Marks a page as holding settings in this layout.
+pub const MAGIC: [u8; 4] = *b"MBO1";
+
+/// Bytes a record takes up in flash.
+pub const RECORD_LEN: usize = 12;
+
+/// What erased flash reads back as; a setting with this value is unset.
+const UNSET: u8 = 0xFF;
+
+/// Everything the firmware remembers between resets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Settings {
+    /// The I2C address the OLED was last found at.
+    pub oled_address: Option<u8>,
+    /// The I2C clock speed to start at.
+    pub i2c_speed: Option<Speed>,
+    /// The OLED's brightness.
+    pub brightness: Option<Level>,
+    /// Whether the OLED is kept at its dimmest.
+    pub night_mode: Option<bool>,
+    /// What takes over the OLED when it's left alone.
+    pub saver: Option<Saver>,
+}
+
+impl Settings {
+    /// Nothing set.
+    pub const fn new() -> Self {
+        Self {
+            oled_address: None,
+            i2c_speed: None,
+            brightness: None,
+            night_mode: None,
+            saver: None,
+        }
+    }
+
+    /// The settings as stored in flash.
+    pub const fn to_bytes(&self) -> [u8; RECORD_LEN] {
+        let mut bytes = [UNSET; RECORD_LEN];
+        bytes[0] = MAGIC[0];
+        bytes[1] = MAGIC[1];
+        bytes[2] = MAGIC[2];
+        bytes[3] = MAGIC[3];
+        if let Some(address) = self.oled_address {
+            bytes[4] = address;
+        }
+        if let Some(speed) = self.i2c_speed {
+            bytes[5] = speed.index();
+        }
+        if let Some(level) = self.brightness {
+            bytes[6] = level.index();
+        }
+        if let Some(night) = self.night_mode {
+            bytes[7] = night as u8;
+        }
+        if let Some(saver) = self.saver {
+            bytes[8] = saver.index();
+        }
+        bytes
+    }
+
+    /// Settings from a stored record, or `None` if `bytes` isn't one.
+    pub fn from_bytes(bytes: &[u8; RECORD_LEN]) -> Option<Self> {
+        if bytes[..4] != MAGIC {
+            return None;
+        }
+        Some(Self {
+            // Only the 7-bit addresses the bus can actually use.
+            oled_address: Some(bytes[4]).filter(|address| *address < 0x80),
+            i2c_speed: Speed::from_index(bytes[5]),
+            brightness: Level::from_index(bytes[6]),
+            night_mode: match bytes[7] {
+                0 => Some(false),
+                1 => Some(true),
+                _ => None,
+            },
+            saver: Saver::from_index(bytes[8]),
+        })
+    }
+
+    /// The settings at the start of `flash`, or the defaults if there are
+    /// none or they can't be read.
+    pub fn load<F: ReadNorFlash>(flash: &mut F) -> Self {
+        let mut bytes = [0; RECORD_LEN];
+        match flash.read(0, &mut bytes) {
+            Ok(()) => Self::from_bytes(&bytes).unwrap_or_default(),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Erase the first page of `flash` and write the settings there. Flash
+    /// wears out, so only call this when something has changed.
+    pub fn save<F: NorFlash>(&self, flash: &mut F) -> Result<(), F::Error> {
+        flash.erase(0, F::ERASE_SIZE as u32)?;
+        flash.write(0, &self.to_bytes())
+    }
+}
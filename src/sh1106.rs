@@ -0,0 +1,142 @@
+//! A buffered driver for the SH1106, the controller on most 1.3" modules.
+//!
+//! It takes most SSD1306 commands, but its RAM is 132 columns wide with the
+//! 128-pixel panel in the middle, and it only has page addressing: there is
+//! no horizontal mode to stream a whole frame into, so each page is sent on
+//! its own after setting the page and column. The SSD1306 driver's frames
+//! come out shifted and wrapped on it. [`Sh1106`] keeps its own
+//! [`Frame`] instead and sends each changed run of columns after setting the
+//! page and column for it.
+
+use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*, Pixel};
+
+use crate::{brightness::Drive, display::Oled, error::Error, frame::Frame};
+
+/// Width of the panel in pixels.
+pub const WIDTH: u32 = 128;
+/// Height of the panel in pixels.
+pub const HEIGHT: u32 = 64;
+/// Where the panel's first column sits in the 132-column RAM.
+pub const COLUMN_OFFSET: u8 = 2;
+
+/// Everything up to switching the display on, one command at a time: clock,
+/// 1/64 multiplex, no offset, DC-DC converter on, mirrored to match SSD1306
+/// modules, and the contrast, pre-charge and VCOM levels the module makers
+/// use.
+const INIT: [&[u8]; 15] = [
+    &[0xAE],       // display off
+    &[0xD5, 0x80], // clock divider
+    &[0xA8, 0x3F], // multiplex
+    &[0xD3, 0x00], // display offset
+    &[0x40],       // start line
+    &[0xAD, 0x8B], // DC-DC on
+    &[0x32],       // pump voltage 8.0 V
+    &[0xA1],       // segment remap
+    &[0xC8],       // COM scan direction
+    &[0xDA, 0x12], // COM pins
+    &[0x81, 0x80], // contrast
+    &[0xD9, 0x1F], // pre-charge
+    &[0xDB, 0x40], // VCOM deselect
+    &[0xA4],       // resume from RAM
+    &[0xA6],       // normal (not inverted)
+];
+
+/// An SH1106 with a 128x64 frame buffer.
+#[derive(Debug)]
+pub struct Sh1106<DI> {
+    interface: DI,
+    frame: Frame,
+}
+
+impl<DI: WriteOnlyDataCommand> Sh1106<DI> {
+    /// A driver on `interface`, not yet initialised.
+    pub const fn new(interface: DI) -> Self {
+        Self {
+            interface,
+            frame: Frame::new(Size::new(WIDTH, HEIGHT)),
+        }
+    }
+
+    /// Send the init sequence, fill the RAM with the kept frame (the SH1106
+    /// doesn't blank it on reset) and switch the display on.
+    pub fn init(&mut self) -> Result<(), DisplayError> {
+        for command in INIT {
+            self.interface.send_commands(DataFormat::U8(command))?;
+        }
+        self.frame.invalidate();
+        self.flush()?;
+        self.interface.send_commands(DataFormat::U8(&[0xAF]))
+    }
+
+    /// Send the runs of columns that changed since the last flush.
+    pub fn flush(&mut self) -> Result<(), DisplayError> {
+        let interface = &mut self.interface;
+        self.frame.flush(|page, column, bytes| {
+            let column = column + COLUMN_OFFSET;
+            interface.send_commands(DataFormat::U8(&[
+                0xB0 | page,
+                column & 0x0F,
+                0x10 | column >> 4,
+            ]))?;
+            interface.send_data(DataFormat::U8(bytes))
+        })
+    }
+
+    /// The driver's interface, for anything this driver doesn't cover.
+    pub fn interface(&mut self) -> &mut DI {
+        &mut self.interface
+    }
+}
+
+impl<DI> OriginDimensions for Sh1106<DI> {
+    fn size(&self) -> Size {
+        self.frame.size()
+    }
+}
+
+impl<DI: WriteOnlyDataCommand> DrawTarget for Sh1106<DI> {
+    type Color = BinaryColor;
+    type Error = DisplayError;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            self.frame.set_pixel(point, color.is_on());
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.frame.fill(color.is_on());
+        Ok(())
+    }
+}
+
+impl<DI: WriteOnlyDataCommand> Oled for Sh1106<DI> {
+    fn init(&mut self) -> Result<(), Error> {
+        Sh1106::init(self).map_err(Error::DisplayInit)
+    }
+
+    fn flush(&mut self) -> Result<(), DisplayError> {
+        Sh1106::flush(self)
+    }
+
+    fn flushed_bytes(&self) -> Option<usize> {
+        Some(self.frame.flushed_bytes())
+    }
+
+    /// Only the contrast: the SH1106's `0xD9` has its two periods the other
+    /// way round, and modules are tuned for the pre-charge set at init.
+    fn set_brightness(&mut self, drive: Drive) -> Result<(), DisplayError> {
+        self.interface
+            .send_commands(DataFormat::U8(&[0x81, drive.contrast]))
+    }
+
+    fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        self.interface
+            .send_commands(DataFormat::U8(&[0xAE | on as u8]))
+    }
+}
@@ -0,0 +1,242 @@
+//! How fast the I2C bus runs.
+//!
+//! 400 kHz pushes a frame out four times faster than 100 kHz, but long jumper
+//! wires round the edges of the clock off until bytes start going missing.
+//! The speed to start at is kept in [`Settings`](crate::settings::Settings), and
+//! [`Fallback`] steps down on its own when transfers keep failing at it.
+//! [`Metered`] times each full-frame flush, so an installation can be tried
+//! at each speed and left at the fastest one that holds up.
+
+use core::fmt;
+
+use display_interface::DisplayError;
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*, primitives::Rectangle, Pixel};
+
+use crate::{
+    boot::State,
+    display::{forward_oled, Oled},
+    error::Error,
+    timeout::Micros,
+};
+
+/// An I2C clock speed the TWIM can run at.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Speed {
+    /// Standard mode, what works on any wiring.
+    #[default]
+    K100,
+    K250,
+    /// Fast mode, the most the SSD1306 is specified for.
+    K400,
+}
+
+impl Speed {
+    /// Every speed, slowest first.
+    pub const ALL: [Speed; 3] = [Speed::K100, Speed::K250, Speed::K400];
+
+    /// The clock frequency in kHz.
+    pub const fn khz(self) -> u32 {
+        match self {
+            Speed::K100 => 100,
+            Speed::K250 => 250,
+            Speed::K400 => 400,
+        }
+    }
+
+    /// The next speed down, if there is one.
+    pub const fn slower(self) -> Option<Speed> {
+        match self {
+            Speed::K100 => None,
+            Speed::K250 => Some(Speed::K100),
+            Speed::K400 => Some(Speed::K250),
+        }
+    }
+
+    /// The next speed up, going round to the slowest after the fastest.
+    pub const fn next(self) -> Speed {
+        match self {
+            Speed::K100 => Speed::K250,
+            Speed::K250 => Speed::K400,
+            Speed::K400 => Speed::K100,
+        }
+    }
+
+    /// The speed's position in [`ALL`](Speed::ALL), as it's stored in flash.
+    pub const fn index(self) -> u8 {
+        self as u8
+    }
+
+    /// The speed at `index` in [`ALL`](Speed::ALL).
+    pub const fn from_index(index: u8) -> Option<Speed> {
+        match index {
+            0 => Some(Speed::K100),
+            1 => Some(Speed::K250),
+            2 => Some(Speed::K400),
+            _ => None,
+        }
+    }
+}
+
+/// The bus the OLED is on and how fast it runs, as the link line shows it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Link {
+    /// I2C at `Speed`.
+    I2c(Speed),
+    /// SPI with the clock at `mhz`.
+    Spi { mhz: u32 },
+}
+
+impl fmt::Display for Link {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Link::I2c(speed) => write!(f, "I2C {}kHz", speed.khz()),
+            Link::Spi { mhz } => write!(f, "SPI {mhz}MHz"),
+        }
+    }
+}
+
+/// Failed attempts in a row at one speed before [`Fallback`] steps down.
+pub const FALLBACK_AFTER: u32 = 3;
+
+/// Steps the bus down a speed when the OLED keeps failing in ways a slower
+/// clock can fix.
+#[derive(Clone, Copy, Debug)]
+pub struct Fallback {
+    speed: Speed,
+    /// Failures already counted against a faster speed.
+    counted: u32,
+}
+
+impl Fallback {
+    /// Start at `speed`.
+    pub const fn new(speed: Speed) -> Self {
+        Self { speed, counted: 0 }
+    }
+
+    /// The speed the bus should be running at.
+    pub const fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Look at the boot sequence after a step, and say which speed to switch
+    /// to if it's time to slow down. Only errors a marginal signal causes
+    /// count: a NACKed address is as likely to be an unplugged module.
+    pub fn observe(&mut self, state: &State) -> Option<Speed> {
+        match state {
+            State::Running => {
+                self.counted = 0;
+                None
+            }
+            State::Failed { error, retry, .. }
+                if is_signalling(error) && retry.failures >= self.counted + FALLBACK_AFTER =>
+            {
+                self.counted = retry.failures;
+                self.speed = self.speed.slower()?;
+                Some(self.speed)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Whether `error` is the kind a marginal signal causes.
+const fn is_signalling(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::DataNack | Error::BusTimeout | Error::DisplayInit(_)
+    )
+}
+
+/// An OLED that times its flushes. Only flushes that send a whole frame
+/// count, since the drivers only send what changed: going by
+/// [`flushed_bytes`](Oled::flushed_bytes) for displays that count them, and
+/// otherwise by whether the screen was [cleared](DrawTarget::clear) first.
+#[derive(Debug)]
+pub struct Metered<D, C> {
+    display: D,
+    clock: C,
+    /// The next flush sends a whole frame.
+    whole: bool,
+    frame_us: Option<u32>,
+}
+
+impl<D: Oled, C: Micros> Metered<D, C> {
+    /// Time `display`'s flushes with `clock`.
+    pub const fn new(display: D, clock: C) -> Self {
+        Self {
+            display,
+            clock,
+            whole: false,
+            frame_us: None,
+        }
+    }
+
+    /// How long the last full frame took to flush, once one has.
+    pub const fn frame_us(&self) -> Option<u32> {
+        self.frame_us
+    }
+
+    /// The display underneath.
+    pub fn inner(&mut self) -> &mut D {
+        &mut self.display
+    }
+}
+
+impl<D: Oled, C> OriginDimensions for Metered<D, C> {
+    fn size(&self) -> Size {
+        self.display.bounding_box().size
+    }
+}
+
+impl<D: Oled, C> DrawTarget for Metered<D, C> {
+    type Color = BinaryColor;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.display.draw_iter(pixels)
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.display.fill_solid(area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.whole = true;
+        self.display.clear(color)
+    }
+}
+
+impl<D: Oled, C: Micros> Oled for Metered<D, C> {
+    fn init(&mut self) -> Result<(), Error> {
+        self.display.init()
+    }
+
+    fn flush(&mut self) -> Result<(), DisplayError> {
+        let start_us = self.clock.now_us();
+        self.display.flush()?;
+        let elapsed_us = self.clock.now_us().wrapping_sub(start_us);
+        let cleared = core::mem::take(&mut self.whole);
+        let size = self.size();
+        let whole = match self.display.flushed_bytes() {
+            Some(bytes) => bytes == (size.width * size.height / 8) as usize,
+            None => cleared,
+        };
+        if whole {
+            self.frame_us = Some(elapsed_us);
+        }
+        Ok(())
+    }
+
+    forward_oled!(
+        check,
+        address,
+        flushed_bytes,
+        set_brightness,
+        start_scroll,
+        stop_scroll,
+        set_display_on
+    );
+}
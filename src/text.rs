@@ -0,0 +1,56 @@
+//! Fixed-size text buffers for formatting without an allocator.
+
+use core::fmt;
+
+/// Up to `N` ASCII characters of formatted text. Anything past the end is
+/// dropped, and so is anything that isn't ASCII, so the fonts can draw all of it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextBuf<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Default for TextBuf<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> TextBuf<N> {
+    /// An empty buffer.
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+        }
+    }
+
+    /// The text so far.
+    pub fn as_str(&self) -> &str {
+        // Only whole ASCII characters are ever copied in.
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or_default()
+    }
+
+    /// Whether the buffer is full.
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Empty the buffer.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl<const N: usize> fmt::Write for TextBuf<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars().filter(char::is_ascii) {
+            if self.is_full() {
+                break;
+            }
+            self.buf[self.len] = c as u8;
+            self.len += 1;
+        }
+        Ok(())
+    }
+}
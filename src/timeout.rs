@@ -0,0 +1,131 @@
+//! Giving up on I2C transfers that never finish.
+//!
+//! The TWIM's blocking transfers spin until the peripheral says it has
+//! stopped, and on a bus that is held low it never does, so a single
+//! `flush()` could freeze the whole device. [`Deadline`] wraps a bus whose
+//! transfers can be abandoned and gives each one a time budget. One that runs
+//! over is aborted and fails with [`Timed::Timeout`], which diagnoses as
+//! [`BusFault::Timeout`], so the boot sequence reports
+//! [`Error::BusTimeout`](crate::error::Error::BusTimeout) and keeps retrying
+//! instead of locking up.
+
+use embedded_hal::blocking::i2c::{Read, Write};
+
+use crate::bus::{BusFault, Diagnose};
+
+/// Time one byte plus its ACK takes at 100 kHz, the slowest clock in use.
+pub const BYTE_US: u32 = 90;
+/// Allowance on top of the bytes themselves, for start and stop conditions
+/// and for the odd interrupt while waiting.
+pub const SLACK_US: u32 = 1000;
+
+/// How long a transfer of `len` bytes may take before it is abandoned: twice
+/// what the bytes and the address take at 100 kHz, plus [`SLACK_US`].
+pub const fn budget_us(len: usize) -> u32 {
+    SLACK_US + (len as u32 + 1) * 2 * BYTE_US
+}
+
+/// A bus error, or a transfer that took too long.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timed<E> {
+    /// The bus reported `E`.
+    Bus(E),
+    /// The transfer was aborted after running over its budget.
+    Timeout,
+}
+
+/// An I2C bus whose transfers can be abandoned part way through.
+pub trait Abortable {
+    /// What the bus reports when a transfer fails.
+    type Error;
+
+    /// Write `bytes` to `address`, calling `expired` while waiting and
+    /// aborting the transfer as soon as it returns true. The bus must be
+    /// ready for another transfer afterwards either way.
+    fn write_until(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        expired: &mut dyn FnMut() -> bool,
+    ) -> Result<(), Timed<Self::Error>>;
+
+    /// Read into `buffer` from `address`, aborting like
+    /// [`write_until`](Abortable::write_until).
+    fn read_until(
+        &mut self,
+        address: u8,
+        buffer: &mut [u8],
+        expired: &mut dyn FnMut() -> bool,
+    ) -> Result<(), Timed<Self::Error>>;
+
+    /// What `error`, returned by this bus, means.
+    fn diagnose(error: &Self::Error) -> BusFault;
+
+    /// Bytes of earlier writes still waiting to go out ahead of the next
+    /// transfer, for buses that send in the background.
+    fn queued(&self) -> usize {
+        0
+    }
+}
+
+/// A free-running microsecond counter, allowed to wrap.
+pub trait Micros {
+    /// Microseconds since some fixed point.
+    fn now_us(&mut self) -> u32;
+}
+
+/// A bus that gives up on any transfer that takes longer than
+/// [`budget_us`] says it should, counting any bytes
+/// [queued](Abortable::queued) ahead of it.
+#[derive(Debug)]
+pub struct Deadline<B, C> {
+    bus: B,
+    clock: C,
+}
+
+impl<B: Abortable, C: Micros> Deadline<B, C> {
+    /// Time the transfers on `bus` with `clock`.
+    pub const fn new(bus: B, clock: C) -> Self {
+        Self { bus, clock }
+    }
+
+    /// The bus underneath.
+    pub fn inner(&mut self) -> &mut B {
+        &mut self.bus
+    }
+}
+
+/// A check for [`Abortable`] that says whether a transfer of `len` bytes,
+/// started now, has run out of time.
+fn expiry<C: Micros>(clock: &mut C, len: usize) -> impl FnMut() -> bool + '_ {
+    let start_us = clock.now_us();
+    let budget_us = budget_us(len);
+    move || clock.now_us().wrapping_sub(start_us) > budget_us
+}
+
+impl<B: Abortable, C: Micros> Write for Deadline<B, C> {
+    type Error = Timed<B::Error>;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        let mut expired = expiry(&mut self.clock, self.bus.queued() + bytes.len());
+        self.bus.write_until(address, bytes, &mut expired)
+    }
+}
+
+impl<B: Abortable, C: Micros> Read for Deadline<B, C> {
+    type Error = Timed<B::Error>;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let mut expired = expiry(&mut self.clock, self.bus.queued() + buffer.len());
+        self.bus.read_until(address, buffer, &mut expired)
+    }
+}
+
+impl<B: Abortable, C: Micros> Diagnose for Deadline<B, C> {
+    fn diagnose(error: &Timed<B::Error>) -> BusFault {
+        match error {
+            Timed::Bus(error) => B::diagnose(error),
+            Timed::Timeout => BusFault::Timeout,
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! The main loop, from reset to standby, against the fake SSD1306.
+#![cfg(feature = "std")]
+
+use std::cell::RefCell;
+
+use embedded_graphics::prelude::*;
+use microbit_oled::{
+    app::{App, Platform},
+    boot::{Step, CHECK_MS},
+    brightness::{Level, DIM_AFTER_MS},
+    burnin::SAVER_AFTER_MS,
+    display::{Oled, Panel},
+    emulator::Framebuffer,
+    font,
+    i2c_mock::MockSsd1306,
+    input::DEBOUNCE_MS,
+    led::RecordingMatrix,
+    lsm303agr::Accel,
+    menu::Item,
+    orientation::{Orientation, Rotated},
+    patterns::{self, Pattern},
+    power::{Mode, STANDBY_AFTER_MS, STANDBY_TICK_MS},
+    screens,
+    settings::Settings,
+    speed::{Link, Speed},
+    timeout::Micros,
+};
+use ssd1306::prelude::*;
+
+/// A clock that moves on 10 µs every time it's read, so every frame takes
+/// 10 µs to flush.
+#[derive(Default)]
+struct Ticking(u32);
+
+impl Micros for Ticking {
+    fn now_us(&mut self) -> u32 {
+        self.0 = self.0.wrapping_add(10);
+        self.0
+    }
+}
+
+/// The board, keeping track of what it was asked to do.
+#[derive(Default)]
+struct Board {
+    saved: Vec<Settings>,
+    scans: usize,
+    accel: Option<Accel>,
+    standby: bool,
+}
+
+impl Platform for Board {
+    fn save(&mut self, settings: &Settings) {
+        self.saved.push(*settings);
+    }
+
+    fn set_speed(&mut self, _: Speed) {}
+
+    fn scan<D: Oled>(&mut self, _: &mut D) -> Step {
+        self.scans += 1;
+        Step::Wait { duration_ms: 1000 }
+    }
+
+    fn accel(&mut self) -> Option<Accel> {
+        self.accel
+    }
+
+    fn standby(&mut self) {
+        self.standby = true;
+    }
+
+    fn wake(&mut self) {
+        self.standby = false;
+    }
+}
+
+type Display<'a> = Panel<'a, MockSsd1306, DisplaySize128x32>;
+
+/// The app on the fake SSD1306, polled as the firmware does it.
+struct Rig<'a> {
+    app: App<Display<'a>, Ticking, RecordingMatrix>,
+    board: Board,
+    bus: &'a RefCell<MockSsd1306>,
+    now_ms: u32,
+    down: (bool, bool),
+}
+
+impl<'a> Rig<'a> {
+    fn new(bus: &'a RefCell<MockSsd1306>, settings: Settings) -> Self {
+        let display = Panel::new(bus, 0x3C, DisplaySize128x32);
+        Self {
+            app: App::new(
+                display,
+                Ticking::default(),
+                RecordingMatrix::new(),
+                settings,
+                0,
+            ),
+            board: Board::default(),
+            bus,
+            now_ms: 0,
+            down: (false, false),
+        }
+    }
+
+    /// Poll until `end_ms`, sleeping as long as the app asks each time, and
+    /// return how many polls that took.
+    fn run_until(&mut self, end_ms: u32) -> usize {
+        let mut polls = 0;
+        while self.now_ms < end_ms {
+            polls += 1;
+            let (a_down, b_down) = self.down;
+            self.app.leds().set_now_ms(self.now_ms);
+            self.app.poll(self.now_ms, a_down, b_down, &mut self.board);
+            self.now_ms += self
+                .app
+                .sleep_ms(self.now_ms)
+                .clamp(1, end_ms - self.now_ms);
+        }
+        polls
+    }
+
+    /// Through the boot sequence, to the greeting.
+    fn booted(bus: &'a RefCell<MockSsd1306>) -> Self {
+        let mut rig = Self::new(bus, Settings::new());
+        rig.run_until(10_000);
+        assert!(rig.app.is_running());
+        rig
+    }
+
+    /// Press A, or else B, and let it go.
+    fn press(&mut self, a: bool) {
+        self.down = (a, !a);
+        self.run_until(self.now_ms + 2 * DEBOUNCE_MS);
+        self.down = (false, false);
+        self.run_until(self.now_ms + 2 * DEBOUNCE_MS);
+    }
+
+    fn screen(&self) -> String {
+        self.bus.borrow().state().to_ascii(Size::new(128, 32))
+    }
+}
+
+/// What `draw` puts on an upright 128x32 panel.
+fn expected(draw: impl FnOnce(&mut Framebuffer)) -> String {
+    let mut fb = Framebuffer::default();
+    fb.init().unwrap();
+    draw(&mut fb);
+    fb.flush().unwrap();
+    fb.to_ascii()
+}
+
+/// The greeting with the link line, the way the app draws it.
+fn greeting(fb: &mut impl Oled) {
+    screens::hello(fb).ok();
+    screens::link(fb, Link::I2c(Speed::K100), 10).ok();
+}
+
+#[test]
+fn boots_to_the_greeting_with_the_link_line() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let rig = Rig::booted(&bus);
+
+    assert_eq!(rig.screen(), expected(greeting));
+    // Found where it was looked for first, which is new to the flash.
+    assert_eq!(rig.board.saved.len(), 1);
+    assert_eq!(rig.board.saved[0].oled_address, Some(0x3C));
+}
+
+#[test]
+fn sleeps_until_the_next_thing_is_due() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut rig = Rig::booted(&bus);
+
+    // Left alone, only the OLED check each second wants it.
+    assert!(rig.run_until(rig.now_ms + 10 * CHECK_MS) <= 11);
+
+    // A button held down is watched until it's let go.
+    rig.down = (false, true);
+    rig.run_until(rig.now_ms + 1);
+    assert_eq!(rig.app.sleep_ms(rig.now_ms), DEBOUNCE_MS);
+    rig.down = (false, false);
+
+    // The press put standby off.
+    rig.run_until(STANDBY_AFTER_MS + 20_000);
+    rig.run_until(rig.now_ms + 1);
+    assert_eq!(rig.app.mode(), Mode::Standby);
+    assert_eq!(rig.app.sleep_ms(rig.now_ms), STANDBY_TICK_MS);
+}
+
+#[test]
+fn the_controller_scrolls_the_greeting_by_itself() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut rig = Rig::booted(&bus);
+
+    // Set up right to left on the first line's pages, and started.
+    let opcodes = bus.borrow().state().opcodes();
+    assert!(opcodes.windows(2).any(|pair| pair == [0x27, 0x2F]));
+    assert!(!opcodes.contains(&0x26));
+    assert!(bus.borrow().state().is_scrolling());
+    assert_eq!(rig.screen(), expected(greeting));
+
+    // Nothing more goes over the bus for it, or wakes the CPU.
+    bus.borrow().clear_log();
+    assert!(rig.run_until(rig.now_ms + 10 * CHECK_MS) <= 11);
+    assert_eq!(bus.borrow().state().data_bytes(), 0);
+    assert!(bus.borrow().state().is_scrolling());
+
+    // The settings screen stops it, and closing it starts it again.
+    rig.press(true);
+    assert!(!bus.borrow().state().is_scrolling());
+    for _ in Item::ALL {
+        rig.press(true);
+    }
+    assert!(bus.borrow().state().is_scrolling());
+}
+
+#[test]
+fn on_an_sh1106_the_greeting_is_moved_by_hand() {
+    let bus = RefCell::new(MockSsd1306::sh1106(0x3C));
+    let mut rig = Rig::booted(&bus);
+
+    let opcodes = bus.borrow().state().opcodes();
+    assert!(!opcodes.iter().any(|op| [0x26, 0x27, 0x2F].contains(op)));
+    assert!(!bus.borrow().state().is_scrolling());
+
+    let before = rig.screen();
+    assert!(rig.app.sleep_ms(rig.now_ms) < CHECK_MS);
+    rig.run_until(rig.now_ms + 1_000);
+    assert_ne!(rig.screen(), before);
+}
+
+#[test]
+fn a_works_the_settings_and_b_changes_them() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut rig = Rig::booted(&bus);
+    let contrast = bus.borrow().state().contrast();
+
+    rig.press(true);
+    assert_eq!(
+        rig.screen(),
+        expected(|fb| screens::settings(fb, Item::Brightness, &Settings::new()).unwrap())
+    );
+
+    rig.press(false);
+    let brighter = Settings {
+        brightness: Some(Level::default().next()),
+        ..Settings::new()
+    };
+    assert_eq!(
+        rig.screen(),
+        expected(|fb| screens::settings(fb, Item::Brightness, &brighter).unwrap())
+    );
+    assert!(bus.borrow().state().contrast() > contrast);
+    // Nothing is written until the menu closes.
+    assert_eq!(rig.board.saved.len(), 1);
+
+    for _ in Item::ALL {
+        rig.press(true);
+    }
+    assert_eq!(rig.screen(), expected(greeting));
+    assert_eq!(rig.board.saved.len(), 2);
+    assert_eq!(rig.board.saved[1].brightness, brighter.brightness);
+}
+
+#[test]
+fn left_alone_it_dims_then_saves_the_screen_then_goes_into_standby() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut rig = Rig::booted(&bus);
+    let contrast = bus.borrow().state().contrast();
+
+    rig.run_until(DIM_AFTER_MS + 1_000);
+    assert!(bus.borrow().state().contrast() < contrast);
+
+    rig.run_until(SAVER_AFTER_MS + 1_000);
+    assert_ne!(rig.screen(), expected(greeting));
+
+    rig.run_until(STANDBY_AFTER_MS + 1_000);
+    assert!(!bus.borrow().state().is_display_on());
+    assert!(rig.board.standby);
+    assert_eq!(rig.app.leds().lit(), Pattern::BLANK);
+
+    // The press that wakes it does nothing else: the next one opens the
+    // settings.
+    let shown = rig.app.leds().patterns().rev().nth(1).unwrap();
+    rig.press(true);
+    assert!(bus.borrow().state().is_display_on());
+    assert!(!rig.board.standby);
+    assert_eq!(rig.app.leds().lit(), shown);
+    let woken = rig.screen();
+    rig.press(true);
+    assert_ne!(rig.screen(), woken);
+    assert_eq!(rig.board.saved.len(), 1);
+}
+
+#[test]
+fn turned_over_the_screen_and_the_leds_turn_with_it() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut rig = Rig::new(&bus, Settings::new());
+    rig.app = rig.app.with_accelerometer(true);
+    rig.board.accel = Some(Accel {
+        x: 0,
+        y: -1000,
+        z: 0,
+    });
+    rig.run_until(10_000);
+
+    let mut upside_down = Rotated::new(Framebuffer::default());
+    upside_down.set(Orientation::UpsideDown);
+    upside_down.init().unwrap();
+    greeting(&mut upside_down);
+    upside_down.flush().unwrap();
+    assert_eq!(rig.screen(), upside_down.inner().to_ascii());
+    let lit = rig
+        .app
+        .leds()
+        .patterns()
+        .rev()
+        .find(|&p| p != Pattern::BLANK);
+    assert_eq!(lit, Some(patterns::HEART.rotate_180()));
+}
+
+#[test]
+fn a_held_through_reset_runs_the_scanner() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut rig = Rig::new(&bus, Settings::new());
+    rig.app = rig.app.held_at_reset(true, false, 0);
+    rig.down = (true, false);
+    rig.run_until(5_000);
+
+    assert!(rig.board.scans >= 4);
+    assert!(!rig.app.is_running());
+    assert!(!bus.borrow().state().is_display_on());
+}
+
+#[test]
+fn b_held_through_reset_shows_the_speed_first() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let settings = Settings {
+        i2c_speed: Some(Speed::K250),
+        ..Settings::new()
+    };
+    let mut rig = Rig::new(&bus, settings);
+    rig.app = rig.app.held_at_reset(false, true, 0);
+    rig.down = (false, true);
+    rig.run_until(500);
+
+    assert!(rig.app.leds().patterns().eq([font::glyph('2').unwrap()]));
+}
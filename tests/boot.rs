@@ -0,0 +1,199 @@
+use display_interface::DisplayError;
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+use microbit_oled::{
+    boot::{
+        Boot, Retry, State, Step, BLINK_MS, CHECK_MS, GAP_MS, PAUSE_MS, RETRY_MAX_MS, RETRY_MIN_MS,
+    },
+    display::Oled,
+    error::Error,
+    patterns,
+};
+
+/// Counts what the boot sequence does to the display.
+#[derive(Default)]
+struct FakeOled {
+    fail_init: Option<Error>,
+    inits: usize,
+    flushes: usize,
+    lit_pixels: usize,
+}
+
+impl DrawTarget for FakeOled {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<BinaryColor>>,
+    {
+        self.lit_pixels += pixels.into_iter().filter(|p| p.1.is_on()).count();
+        Ok(())
+    }
+}
+
+impl OriginDimensions for FakeOled {
+    fn size(&self) -> Size {
+        Size::new(128, 32)
+    }
+}
+
+impl Oled for FakeOled {
+    fn init(&mut self) -> Result<(), Error> {
+        self.inits += 1;
+        match &self.fail_init {
+            Some(error) => Err(error.clone()),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), DisplayError> {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+fn show(pattern: patterns::Pattern, duration_ms: u32) -> Step {
+    Step::Show {
+        pattern,
+        duration_ms,
+    }
+}
+
+#[test]
+fn successful_boot_shows_smiley_check_heart() {
+    let mut display = FakeOled::default();
+    let mut boot = Boot::new();
+
+    let steps: Vec<_> = (0..5).map(|_| boot.step(&mut display)).collect();
+
+    assert_eq!(
+        steps,
+        [
+            show(patterns::SMILEY, 1000),
+            show(patterns::CHECK, 1000),
+            show(patterns::HEART, 2000),
+            Step::Wait { duration_ms: 1000 },
+            Step::Wait { duration_ms: 1000 },
+        ]
+    );
+    assert!(matches!(boot.state(), State::Running));
+    assert_eq!(display.inits, 1);
+    assert_eq!(display.flushes, 1);
+    assert!(display.lit_pixels > 0);
+}
+
+fn wait(duration_ms: u32) -> Step {
+    Step::Wait { duration_ms }
+}
+
+#[test]
+fn failed_init_blinks_its_code_between_retries() {
+    let mut display = FakeOled {
+        fail_init: Some(Error::BusStuck),
+        ..Default::default()
+    };
+    let mut boot = Boot::new();
+
+    let steps: Vec<_> = (0..9).map(|_| boot.step(&mut display)).collect();
+
+    let blink = show(patterns::CROSS, BLINK_MS);
+    assert_eq!(
+        steps,
+        [
+            show(patterns::SMILEY, 1000),
+            blink,
+            wait(GAP_MS),
+            blink,
+            wait(GAP_MS),
+            blink,
+            wait(PAUSE_MS),
+            blink,
+            wait(GAP_MS),
+        ]
+    );
+    assert!(matches!(
+        boot.state(),
+        State::Failed {
+            error: Error::BusStuck,
+            ..
+        }
+    ));
+    // Once after the smiley, once more after the first round of blinks.
+    assert_eq!(display.inits, 2);
+    assert_eq!(display.flushes, 0);
+    assert_eq!(display.lit_pixels, 0);
+}
+
+#[test]
+fn late_oled_is_picked_up_by_a_retry() {
+    let mut display = FakeOled {
+        fail_init: Some(Error::AddressNack { address: 0x3C }),
+        ..Default::default()
+    };
+    let mut boot = Boot::new();
+
+    boot.step(&mut display);
+    boot.step(&mut display);
+    display.fail_init = None;
+    let steps: Vec<_> = (0..4).map(|_| boot.step(&mut display)).collect();
+
+    assert_eq!(
+        steps,
+        [
+            wait(PAUSE_MS),
+            show(patterns::CHECK, 1000),
+            show(patterns::HEART, 2000),
+            wait(CHECK_MS),
+        ]
+    );
+    assert!(matches!(boot.state(), State::Running));
+    assert_eq!(display.flushes, 1);
+}
+
+#[test]
+fn retries_back_off() {
+    let backoff = |failures| {
+        Retry {
+            failures,
+            waited_ms: 0,
+        }
+        .backoff_ms()
+    };
+
+    assert_eq!(backoff(1), RETRY_MIN_MS);
+    assert_eq!(backoff(2), 2 * RETRY_MIN_MS);
+    assert_eq!(backoff(3), 4 * RETRY_MIN_MS);
+    assert_eq!(backoff(5), RETRY_MAX_MS);
+    assert_eq!(backoff(u32::MAX), RETRY_MAX_MS);
+}
+
+#[test]
+fn failure_goes_on_screen_when_the_oled_answers() {
+    let mut display = FakeOled {
+        fail_init: Some(Error::WrongController { status: 0x08 }),
+        ..Default::default()
+    };
+    let mut boot = Boot::new();
+
+    boot.step(&mut display);
+    boot.step(&mut display);
+
+    assert_eq!(display.flushes, 1);
+    assert!(display.lit_pixels > 0);
+}
+
+#[test]
+fn error_codes_are_distinct() {
+    let errors = [
+        Error::AddressNack { address: 0x3C },
+        Error::DataNack,
+        Error::BusStuck,
+        Error::BusTimeout,
+        Error::WrongController { status: 0x08 },
+        Error::DisplayInit(DisplayError::BusWriteError),
+    ];
+    let mut codes: Vec<_> = errors.iter().map(Error::code).collect();
+    codes.sort();
+    codes.dedup();
+    assert_eq!(codes, [1, 2, 3, 4, 5, 6]);
+}
@@ -0,0 +1,110 @@
+//! Brightness levels, dimming when left alone and keeping the level through
+//! an init.
+#![cfg(feature = "std")]
+
+use std::cell::RefCell;
+
+use microbit_oled::{
+    brightness::{Dimmable, Dimmer, Drive, Level, DIM_AFTER_MS, DIM_LEVEL},
+    display::{Oled, Panel},
+    emulator::Framebuffer,
+    i2c_mock::MockSsd1306,
+};
+use ssd1306::prelude::*;
+
+#[test]
+fn levels_get_brighter_and_go_round() {
+    let drives = Level::ALL.map(Level::drive);
+    assert!(drives
+        .windows(2)
+        .all(|pair| pair[0].contrast < pair[1].contrast));
+    assert!(Drive::NIGHT.contrast < drives[0].contrast);
+    // The default is what the driver's init sets anyway.
+    assert_eq!(
+        Level::default().drive(),
+        Drive {
+            contrast: 0x5F,
+            precharge: 2
+        }
+    );
+
+    assert_eq!(Level::Highest.next(), Level::Lowest);
+    for level in Level::ALL {
+        assert_eq!(Level::from_index(level.index()), Some(level));
+    }
+    assert_eq!(Level::from_index(5), None);
+}
+
+#[test]
+fn dims_when_left_alone_and_wakes_on_a_press() {
+    let mut dimmer = Dimmer::new(Level::High, false, 1_000);
+    assert_eq!(dimmer.poll(1_000), Some(Level::High.drive()));
+    assert_eq!(dimmer.poll(1_000 + DIM_AFTER_MS - 1), None);
+
+    assert_eq!(dimmer.poll(1_000 + DIM_AFTER_MS), Some(DIM_LEVEL.drive()));
+    assert!(dimmer.is_dimmed());
+
+    // The press only wakes it.
+    assert!(dimmer.input(90_000));
+    assert_eq!(dimmer.poll(90_000), Some(Level::High.drive()));
+    assert_eq!(dimmer.poll(90_000 + DIM_AFTER_MS - 1), None);
+}
+
+#[test]
+fn presses_count_when_dimming_changes_nothing() {
+    let mut dimmer = Dimmer::new(Level::Lowest, false, 0);
+    dimmer.poll(DIM_AFTER_MS);
+    assert!(!dimmer.input(DIM_AFTER_MS + 1));
+
+    let mut dimmer = Dimmer::new(Level::Highest, true, 0);
+    assert_eq!(dimmer.poll(0), Some(Drive::NIGHT));
+    assert_eq!(dimmer.poll(DIM_AFTER_MS), None);
+    assert!(!dimmer.input(DIM_AFTER_MS + 1));
+}
+
+#[test]
+fn idle_time_survives_the_clock_wrapping() {
+    let mut dimmer = Dimmer::new(Level::Medium, false, u32::MAX - 10);
+    dimmer.poll(u32::MAX - 10);
+
+    assert_eq!(dimmer.poll(5), None);
+    assert_eq!(dimmer.poll(DIM_AFTER_MS), Some(DIM_LEVEL.drive()));
+}
+
+#[test]
+fn level_is_sent_again_after_an_init() {
+    let mut display = Dimmable::new(Framebuffer::default());
+    display.init().unwrap();
+    assert_eq!(display.inner().brightness(), None);
+
+    display.set_brightness(Drive::NIGHT).unwrap();
+    display.init().unwrap();
+
+    assert_eq!(display.inner().brightness(), Some(Drive::NIGHT));
+}
+
+#[test]
+fn ssd1306_gets_contrast_and_precharge() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    Oled::init(&mut display).unwrap();
+
+    display.set_brightness(Level::Lowest.drive()).unwrap();
+
+    assert_eq!(bus.borrow().state().contrast(), 0x0F);
+    // Phase 2 in the high nibble, phase 1 left at one clock.
+    assert_eq!(bus.borrow().state().precharge(), 0x11);
+}
+
+#[test]
+fn sh1106_only_gets_contrast() {
+    let bus = RefCell::new(MockSsd1306::sh1106(0x3C));
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x64);
+    Oled::init(&mut display).unwrap();
+    let precharge = bus.borrow().state().precharge();
+
+    display.set_brightness(Level::Highest.drive()).unwrap();
+
+    assert_eq!(bus.borrow().state().contrast(), 0xFF);
+    assert_eq!(bus.borrow().state().precharge(), precharge);
+}
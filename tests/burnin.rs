@@ -0,0 +1,178 @@
+//! Shifting, inverting and the screensaver, against the emulator.
+#![cfg(feature = "std")]
+
+use embedded_graphics::prelude::*;
+use microbit_oled::{
+    burnin::{bounce, Due, Guard, Saver, Shifted, INVERT_MS, SAVER_AFTER_MS, SHIFTS, SHIFT_MS},
+    display::Oled,
+    emulator::Framebuffer,
+    patterns, screens,
+};
+
+fn hello(offset: Point, inverted: bool) -> Framebuffer {
+    let mut display = Shifted::new(Framebuffer::default());
+    display.set(offset, inverted);
+    display.init().unwrap();
+    screens::hello(&mut display).unwrap();
+    display.flush().unwrap();
+    display.inner().clone()
+}
+
+fn lit(fb: &Framebuffer) -> usize {
+    let size = fb.size();
+    (0..size.height as i32)
+        .flat_map(|y| (0..size.width as i32).map(move |x| Point::new(x, y)))
+        .filter(|&point| fb.pixel(point))
+        .count()
+}
+
+#[test]
+fn frame_moves_by_the_offset() {
+    let still = hello(Point::zero(), false);
+    let offset = Point::new(1, -1);
+    let moved = hello(offset, false);
+
+    let Size { width, height } = still.size();
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let point = Point::new(x, y);
+            assert_eq!(
+                moved.pixel(point),
+                still.pixel(point - offset),
+                "at {point}"
+            );
+        }
+    }
+}
+
+#[test]
+fn inverted_frame_swaps_lit_and_dark() {
+    let still = hello(Point::zero(), false);
+    let inverted = hello(Point::zero(), true);
+
+    assert!(lit(&still) > 0);
+    assert_eq!(lit(&still) + lit(&inverted), 128 * 32);
+}
+
+#[test]
+fn inverted_frame_is_kept_through_an_init() {
+    let mut display = Shifted::new(Framebuffer::default());
+    display.set(Point::zero(), true);
+    display.init().unwrap();
+    screens::hello(&mut display).unwrap();
+
+    display.init().unwrap();
+    display.flush().unwrap();
+
+    assert_eq!(
+        display.inner().screen(),
+        hello(Point::zero(), true).screen()
+    );
+}
+
+#[test]
+fn shifts_step_round_and_inversion_comes_on_the_hour() {
+    let mut guard = Guard::new(Saver::Off, 0);
+    assert_eq!(guard.poll(SHIFT_MS - 1), Due::Nothing);
+
+    let mut offsets = Vec::new();
+    for step in 1..=SHIFTS.len() as u32 {
+        assert_eq!(guard.poll(step * SHIFT_MS), Due::Redraw);
+        offsets.push(guard.offset());
+    }
+    // Round every offset and back to the start.
+    assert_eq!(offsets.last(), Some(&Point::zero()));
+    offsets.sort_by_key(|point| (point.x, point.y));
+    offsets.dedup();
+    assert_eq!(offsets.len(), SHIFTS.len());
+
+    assert!(!guard.inverted());
+    assert_eq!(guard.poll(INVERT_MS), Due::Redraw);
+    assert!(guard.inverted());
+}
+
+#[test]
+fn saver_starts_when_left_alone_and_a_press_ends_it() {
+    let mut guard = Guard::new(Saver::Logo, 0);
+    // Shifting goes on meanwhile.
+    assert_eq!(guard.poll(SAVER_AFTER_MS - 1), Due::Redraw);
+    assert!(!guard.input(SAVER_AFTER_MS - 1));
+
+    let start = 2 * SAVER_AFTER_MS;
+    assert_eq!(guard.poll(start - 1), Due::Saver);
+    assert_eq!(guard.frames(), 0);
+    assert_eq!(guard.poll(start), Due::Nothing);
+    assert_eq!(guard.poll(start + 49), Due::Saver);
+    assert_eq!(guard.frames(), 1);
+
+    assert!(guard.input(start + 100));
+    assert!(!guard.is_saving());
+    assert_eq!(guard.poll(start + 200), Due::Nothing);
+}
+
+#[test]
+fn says_when_there_is_something_to_draw_next() {
+    let mut guard = Guard::new(Saver::Logo, 0);
+    assert_eq!(guard.due_in_ms(0), SHIFT_MS);
+
+    assert_eq!(guard.poll(SHIFT_MS), Due::Redraw);
+    assert_eq!(guard.due_in_ms(SHIFT_MS), SAVER_AFTER_MS - SHIFT_MS);
+
+    assert_eq!(guard.poll(SAVER_AFTER_MS), Due::Saver);
+    assert_eq!(
+        guard.due_in_ms(SAVER_AFTER_MS),
+        Saver::Logo.frame_ms().unwrap()
+    );
+}
+
+#[test]
+fn saver_is_never_shifted_or_inverted() {
+    let mut guard = Guard::new(Saver::Blank, 0);
+    guard.poll(INVERT_MS);
+    assert!(guard.is_saving());
+
+    assert_eq!(guard.offset(), Point::zero());
+    assert!(!guard.inverted());
+    // Blank is drawn once and left.
+    assert_eq!(guard.poll(INVERT_MS + 60_000), Due::Nothing);
+}
+
+#[test]
+fn no_saver_when_turned_off() {
+    let mut guard = Guard::new(Saver::Off, 0);
+
+    assert_eq!(guard.poll(SAVER_AFTER_MS), Due::Redraw);
+    assert!(!guard.is_saving());
+}
+
+#[test]
+fn bounces_off_both_ends() {
+    let path: Vec<u32> = (0..8).map(|step| bounce(step, 3)).collect();
+    assert_eq!(path, [0, 1, 2, 3, 2, 1, 0, 1]);
+    assert_eq!(bounce(5, 0), 0);
+}
+
+#[test]
+fn logo_stays_whole_on_every_panel() {
+    let leds = patterns::HEART
+        .rows()
+        .iter()
+        .flatten()
+        .filter(|&&led| led > 0)
+        .count();
+    for size in [
+        Size::new(128, 32),
+        Size::new(128, 64),
+        Size::new(96, 16),
+        Size::new(72, 40),
+        Size::new(64, 48),
+    ] {
+        let mut fb = Framebuffer::new(size);
+        fb.init().unwrap();
+        for step in 0..300 {
+            screens::saver(&mut fb, Saver::Logo, step, 0).unwrap();
+            fb.flush().unwrap();
+            assert_eq!(lit(&fb), leds * 9, "{size} at step {step}");
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Which runs of columns a frame sends.
+
+use embedded_graphics::prelude::*;
+use microbit_oled::frame::{Frame, MERGE_GAP};
+
+/// Flush `frame` and return the runs it sent as (page, first column, length).
+fn runs(frame: &mut Frame) -> Vec<(u8, u8, usize)> {
+    let mut runs = Vec::new();
+    frame
+        .flush(|page, column, bytes| {
+            runs.push((page, column, bytes.len()));
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+    runs
+}
+
+#[test]
+fn first_flush_sends_every_page_whole() {
+    let mut frame = Frame::new(Size::new(128, 32));
+
+    assert_eq!(
+        runs(&mut frame),
+        [(0, 0, 128), (1, 0, 128), (2, 0, 128), (3, 0, 128)]
+    );
+    assert_eq!(frame.flushed_bytes(), 512);
+    assert_eq!(runs(&mut frame), []);
+}
+
+#[test]
+fn nearby_changes_share_a_run() {
+    let mut frame = Frame::new(Size::new(128, 32));
+    runs(&mut frame);
+
+    frame.set_pixel(Point::new(10, 3), true);
+    frame.set_pixel(Point::new(10 + MERGE_GAP as i32, 3), true);
+    frame.set_pixel(Point::new(100, 3), true);
+    frame.set_pixel(Point::new(5, 20), true);
+
+    assert_eq!(
+        runs(&mut frame),
+        [(0, 10, MERGE_GAP + 1), (0, 100, 1), (2, 5, 1)]
+    );
+    assert_eq!(frame.flushed_bytes(), MERGE_GAP + 3);
+}
+
+#[test]
+fn setting_a_pixel_back_is_no_change() {
+    let mut frame = Frame::new(Size::new(64, 48));
+    runs(&mut frame);
+
+    frame.set_pixel(Point::new(3, 3), true);
+    frame.set_pixel(Point::new(3, 3), false);
+    frame.fill(false);
+
+    assert_eq!(runs(&mut frame), []);
+}
+
+#[test]
+fn failed_runs_go_again() {
+    let mut frame = Frame::new(Size::new(128, 32));
+    runs(&mut frame);
+    frame.set_pixel(Point::new(1, 1), true);
+    frame.set_pixel(Point::new(1, 9), true);
+
+    let failed = frame.flush(|page, _, _| if page == 1 { Err(()) } else { Ok(()) });
+
+    assert_eq!(failed, Err(()));
+    assert_eq!(runs(&mut frame), [(1, 1, 1)]);
+}
+
+#[test]
+fn invalidated_frame_goes_out_whole() {
+    let mut frame = Frame::new(Size::new(96, 16));
+    runs(&mut frame);
+
+    frame.invalidate();
+
+    assert_eq!(runs(&mut frame), [(0, 0, 96), (1, 0, 96)]);
+}
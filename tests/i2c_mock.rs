@@ -0,0 +1,486 @@
+//! The real SSD1306 driver talking to the fake controller on a mock I2C bus.
+#![cfg(feature = "std")]
+
+use std::cell::RefCell;
+
+use embedded_graphics::{
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    text::Text,
+};
+use embedded_hal::blocking::delay::DelayMs;
+use microbit_oled::{
+    boot::{Boot, State, Step, CHECK_MS},
+    bus::Controller,
+    display::{Oled, Panel, SpiPanel},
+    emulator::{Framebuffer, ASCII_ON},
+    error::Error,
+    font,
+    i2c_mock::{AddrMode, Line, MockSsd1306},
+    menu::Item,
+    screens,
+    settings::Settings,
+    speed::{Link, Speed},
+};
+use ssd1306::{prelude::*, I2CDisplayInterface, Ssd1306};
+
+type Display = Ssd1306<
+    I2CInterface<MockSsd1306>,
+    DisplaySize128x32,
+    ssd1306::mode::BufferedGraphicsMode<DisplaySize128x32>,
+>;
+
+fn display(bus: &MockSsd1306) -> Display {
+    let interface = I2CDisplayInterface::new(bus.clone());
+    Ssd1306::new(interface, DisplaySize128x32, DisplayRotation::Rotate0)
+        .into_buffered_graphics_mode()
+}
+
+#[test]
+fn init_sends_commands_in_order() {
+    let bus = MockSsd1306::default();
+    let mut display = display(&bus);
+
+    Oled::init(&mut display).unwrap();
+
+    assert_eq!(
+        bus.state().opcodes(),
+        [
+            0xAE, // display off
+            0xD5, // clock divider
+            0xA8, // multiplex
+            0xD3, // display offset
+            0x40, // start line
+            0x8D, // charge pump
+            0x20, // addressing mode
+            0xDA, // COM pins
+            0xA1, // segment remap
+            0xC8, // COM scan direction
+            0xD9, // pre-charge
+            0x81, // contrast
+            0xDB, // Vcomh
+            0xA4, // resume from RAM
+            0xA6, // normal (not inverted)
+            0x2E, // scroll off
+            0xAF, // display on
+        ]
+    );
+}
+
+#[test]
+fn init_leaves_controller_configured() {
+    let bus = MockSsd1306::default();
+    let mut display = display(&bus);
+
+    Oled::init(&mut display).unwrap();
+
+    let state = bus.state();
+    assert!(state.is_display_on());
+    assert!(state.is_charge_pump_on());
+    assert!(!state.is_inverted());
+    assert!(!state.is_scrolling());
+    assert_eq!(state.addr_mode(), AddrMode::Horizontal);
+    assert_eq!(state.rows(), 32);
+    assert_eq!(state.contrast(), 0x5F);
+    assert_eq!(state.data_bytes(), 0);
+}
+
+#[test]
+fn flush_puts_hello_screen_in_gddram() {
+    let bus = MockSsd1306::default();
+    let mut display = display(&bus);
+    Oled::init(&mut display).unwrap();
+    bus.clear_log();
+
+    screens::hello(&mut display).unwrap();
+    Oled::flush(&mut display).unwrap();
+
+    let state = bus.state();
+    // Column and page window, then the whole 128x32 buffer.
+    assert_eq!(state.commands()[0], [0x21, 0, 127]);
+    assert_eq!(state.commands()[1], [0x22, 0, 3]);
+    assert_eq!(state.data_bytes(), 128 * 32 / 8);
+    assert_eq!(
+        state.to_ascii(Size::new(128, 32)),
+        include_str!("golden/hello.txt")
+    );
+}
+
+#[test]
+fn boot_fails_when_nothing_answers() {
+    let bus = RefCell::new(MockSsd1306::default());
+    bus.borrow().set_present(false);
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    let mut boot = Boot::new();
+
+    boot.step(&mut display);
+    boot.step(&mut display);
+
+    assert!(matches!(
+        boot.state(),
+        State::Failed {
+            error: Error::AddressNack { address: 0x3C },
+            ..
+        }
+    ));
+    assert_eq!(bus.borrow().state().transactions(), 0);
+}
+
+#[test]
+fn panel_checks_the_controller_before_init() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+
+    Oled::init(&mut display).unwrap();
+
+    let bus = bus.borrow();
+    let state = bus.state();
+    // The no-op probe and the status read, then the usual init sequence.
+    assert_eq!(state.opcodes()[..2], [0xE3, 0xAE]);
+    assert_eq!(state.transactions(), 2 + 17);
+    assert!(state.is_display_on());
+}
+
+#[test]
+fn wrong_controller_is_reported_on_screen() {
+    let bus = RefCell::new(MockSsd1306::default());
+    bus.borrow().set_status(Some(0x08));
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32).only(Some(Controller::Ssd1306));
+    let mut boot = Boot::new();
+
+    boot.step(&mut display);
+    boot.step(&mut display);
+
+    assert!(matches!(
+        boot.state(),
+        State::Failed {
+            error: Error::WrongController { status: 0x08 },
+            ..
+        }
+    ));
+    assert_eq!(
+        bus.borrow().state().to_ascii(Size::new(128, 32)),
+        include_str!("golden/error.txt")
+    );
+}
+
+/// The greeting as it should look on a 128x64 panel.
+fn hello_128x64() -> String {
+    let mut fb = Framebuffer::new(Size::new(128, 64));
+    fb.init().unwrap();
+    screens::hello(&mut fb).unwrap();
+    fb.flush().unwrap();
+    fb.to_ascii()
+}
+
+#[test]
+fn sh1106_is_detected_and_drawn_in_place() {
+    let bus = RefCell::new(MockSsd1306::sh1106(0x3C));
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    let mut boot = Boot::new();
+    for _ in 0..4 {
+        boot.step(&mut display);
+    }
+
+    assert!(matches!(boot.state(), State::Running));
+    assert_eq!(display.controller(), Controller::Sh1106);
+    assert_eq!(display.size(), Size::new(128, 64));
+    let bus = bus.borrow();
+    assert!(bus.state().is_display_on());
+    assert_eq!(bus.state().to_ascii(Size::new(128, 64)), hello_128x64());
+}
+
+#[test]
+fn ssd1306_driver_garbles_an_sh1106() {
+    let bus = MockSsd1306::sh1106(0x3C);
+    let interface = I2CDisplayInterface::new(bus.clone());
+    let mut display = Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate0)
+        .into_buffered_graphics_mode();
+
+    Oled::init(&mut display).unwrap();
+    screens::hello(&mut display).unwrap();
+    Oled::flush(&mut display).unwrap();
+
+    assert_ne!(bus.state().to_ascii(Size::new(128, 64)), hello_128x64());
+}
+
+#[test]
+fn sh1106_sends_only_what_changed() {
+    let bus = RefCell::new(MockSsd1306::sh1106(0x3C));
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    Oled::init(&mut display).unwrap();
+    bus.borrow().clear_log();
+
+    Pixel(Point::new(10, 20), BinaryColor::On)
+        .draw(&mut display)
+        .unwrap();
+    Oled::flush(&mut display).unwrap();
+
+    let bus = bus.borrow();
+    // Page 2, column 10 + 2.
+    assert_eq!(bus.state().commands(), [vec![0xB2], vec![0x0C], vec![0x10]]);
+    assert_eq!(bus.state().data_bytes(), 1);
+    assert!(bus.state().lit(Point::new(10, 20)));
+}
+
+/// A panel on `bus` showing the greeting, with the bus log cleared.
+fn panel_showing_hello(bus: &RefCell<MockSsd1306>) -> Panel<'_, MockSsd1306, DisplaySize128x32> {
+    let mut display = Panel::new(bus, 0x3C, DisplaySize128x32);
+    Oled::init(&mut display).unwrap();
+    screens::hello(&mut display).unwrap();
+    Oled::flush(&mut display).unwrap();
+    assert_eq!(display.flushed_bytes(), Some(128 * 32 / 8));
+    bus.borrow().clear_log();
+    display
+}
+
+#[test]
+fn one_character_sends_tens_of_bytes() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut display = panel_showing_hello(&bus);
+
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    Text::new("!", Point::new(60, 30), style)
+        .draw(&mut display)
+        .unwrap();
+    Oled::flush(&mut display).unwrap();
+
+    let bytes = display.flushed_bytes().unwrap();
+    assert!((1..=2 * 6).contains(&bytes), "sent {bytes} bytes");
+    assert_eq!(bus.borrow().state().data_bytes(), bytes);
+    let mut expected = Framebuffer::default();
+    expected.init().unwrap();
+    screens::hello(&mut expected).unwrap();
+    Text::new("!", Point::new(60, 30), style)
+        .draw(&mut expected)
+        .unwrap();
+    expected.flush().unwrap();
+    assert_eq!(
+        bus.borrow().state().to_ascii(Size::new(128, 32)),
+        expected.to_ascii()
+    );
+}
+
+#[test]
+fn redrawing_the_same_screen_sends_nothing() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut display = panel_showing_hello(&bus);
+
+    screens::hello(&mut display).unwrap();
+    Oled::flush(&mut display).unwrap();
+
+    assert_eq!(display.flushed_bytes(), Some(0));
+    assert_eq!(bus.borrow().state().transactions(), 0);
+}
+
+#[test]
+fn link_line_sends_only_the_figures_that_changed() {
+    let bus = RefCell::new(MockSsd1306::new(0x3C));
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x64);
+    Oled::init(&mut display).unwrap();
+    screens::hello(&mut display).unwrap();
+    screens::link(&mut display, Link::I2c(Speed::K400), 12_300).unwrap();
+    Oled::flush(&mut display).unwrap();
+    bus.borrow().clear_log();
+
+    // 12.3ms to 12.4ms: one character.
+    screens::link(&mut display, Link::I2c(Speed::K400), 12_400).unwrap();
+    Oled::flush(&mut display).unwrap();
+
+    let bytes = display.flushed_bytes().unwrap();
+    assert!((1..=2 * 6).contains(&bytes), "sent {bytes} bytes");
+    assert_eq!(bus.borrow().state().data_bytes(), bytes);
+}
+
+#[test]
+fn swapped_module_is_driven_as_itself() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    let mut boot = Boot::new();
+    for _ in 0..4 {
+        boot.step(&mut display);
+    }
+
+    *bus.borrow_mut() = MockSsd1306::sh1106(0x3C);
+    boot.step(&mut display);
+    // A different panel, so the greeting is laid out for it.
+    assert!(boot.take_recovered());
+    screens::hello(&mut display).unwrap();
+    display.flush().unwrap();
+
+    assert!(matches!(boot.state(), State::Running));
+    assert_eq!(display.controller(), Controller::Sh1106);
+    assert_eq!(
+        bus.borrow().state().to_ascii(Size::new(128, 64)),
+        hello_128x64()
+    );
+}
+
+#[test]
+fn wrong_address_is_nacked() {
+    let bus = MockSsd1306::new(0x3D);
+    let mut display = display(&bus);
+
+    assert!(Oled::init(&mut display).is_err());
+    assert!(!bus.state().is_display_on());
+}
+
+#[test]
+fn unplugged_oled_comes_back_with_its_screen() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    let mut boot = Boot::new();
+    for _ in 0..4 {
+        boot.step(&mut display);
+    }
+    assert!(matches!(boot.state(), State::Running));
+
+    bus.borrow().set_present(false);
+    boot.step(&mut display);
+    assert!(matches!(
+        boot.state(),
+        State::Failed {
+            error: Error::AddressNack { .. },
+            ..
+        }
+    ));
+
+    // Plugged back in, so it comes up blank and switched off.
+    bus.borrow().power_cycle();
+    bus.borrow().set_present(true);
+    for _ in 0..3 {
+        boot.step(&mut display);
+    }
+
+    assert!(matches!(boot.state(), State::Running));
+    let bus = bus.borrow();
+    assert!(bus.state().is_display_on());
+    assert_eq!(
+        bus.state().to_ascii(Size::new(128, 32)),
+        include_str!("golden/hello.txt")
+    );
+}
+
+#[test]
+fn power_blip_is_recovered_in_one_step() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    let mut boot = Boot::new();
+    for _ in 0..4 {
+        boot.step(&mut display);
+    }
+
+    bus.borrow().power_cycle();
+    let step = boot.step(&mut display);
+
+    assert_eq!(
+        step,
+        Step::Wait {
+            duration_ms: CHECK_MS
+        }
+    );
+    assert!(matches!(boot.state(), State::Running));
+    assert_eq!(
+        bus.borrow().state().to_ascii(Size::new(128, 32)),
+        include_str!("golden/hello.txt")
+    );
+}
+
+#[test]
+fn power_blip_brings_back_whatever_was_showing() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    let mut boot = Boot::new();
+    for _ in 0..4 {
+        boot.step(&mut display);
+    }
+    assert!(!boot.take_recovered());
+    screens::settings(&mut display, Item::Saver, &Settings::default()).unwrap();
+    display.flush().unwrap();
+    let settings = bus.borrow().state().to_ascii(Size::new(128, 32));
+
+    bus.borrow().power_cycle();
+    boot.step(&mut display);
+
+    assert!(boot.take_recovered());
+    assert!(!boot.take_recovered());
+    assert_eq!(bus.borrow().state().to_ascii(Size::new(128, 32)), settings);
+}
+
+#[test]
+fn oled_strapped_to_0x3d_is_found() {
+    let bus = RefCell::new(MockSsd1306::new(0x3D));
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    assert_eq!(display.address(), None);
+
+    let mut boot = Boot::new();
+    let steps: Vec<_> = (0..4).map(|_| boot.step(&mut display)).collect();
+
+    assert_eq!(display.address(), Some(0x3D));
+    assert_eq!(
+        steps[2],
+        Step::Show {
+            pattern: font::glyph('D').unwrap(),
+            duration_ms: 1000
+        }
+    );
+    assert!(matches!(boot.state(), State::Running));
+    assert!(bus.borrow().state().is_display_on());
+}
+
+struct NoDelay;
+
+impl DelayMs<u8> for NoDelay {
+    fn delay_ms(&mut self, _ms: u8) {}
+}
+
+#[test]
+fn spi_panel_resets_then_draws() {
+    let oled = MockSsd1306::default();
+    let mut display = SpiPanel::new(
+        oled.spi(),
+        oled.line(Line::Dc),
+        oled.line(Line::Cs),
+        oled.line(Line::Rst),
+        NoDelay,
+        DisplaySize128x32,
+    );
+    let mut boot = Boot::new();
+    for _ in 0..4 {
+        boot.step(&mut display);
+    }
+
+    assert!(matches!(boot.state(), State::Running));
+    assert_eq!(oled.resets(), 1);
+    assert_eq!(display.address(), None);
+    assert_eq!(
+        oled.state().to_ascii(Size::new(128, 32)),
+        include_str!("golden/hello.txt")
+    );
+}
+
+#[test]
+fn spi_panel_reinit_starts_from_reset() {
+    let oled = MockSsd1306::default();
+    let mut display = SpiPanel::new(
+        oled.spi(),
+        oled.line(Line::Dc),
+        oled.line(Line::Cs),
+        oled.line(Line::Rst),
+        NoDelay,
+        DisplaySize128x32,
+    );
+    Oled::init(&mut display).unwrap();
+    screens::hello(&mut display).unwrap();
+    Oled::flush(&mut display).unwrap();
+
+    // The reset wipes the screen, and init sets the controller up again.
+    Oled::init(&mut display).unwrap();
+
+    let state = oled.state();
+    assert_eq!(oled.resets(), 2);
+    assert!(state.is_display_on());
+    assert_eq!(state.addr_mode(), AddrMode::Horizontal);
+    assert!(!state.to_ascii(Size::new(128, 32)).contains(ASCII_ON));
+}
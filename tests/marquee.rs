@@ -0,0 +1,226 @@
+//! Hardware scrolling against the fake controller, and the marquee moved by
+//! hand where there isn't any.
+#![cfg(feature = "std")]
+
+use std::cell::RefCell;
+
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+use microbit_oled::{
+    display::{Oled, Panel},
+    emulator::Framebuffer,
+    i2c_mock::MockSsd1306,
+    marquee::{Direction, Interval, Marquee, Scroll},
+    screens::{self, GREETING, GREETING_SCROLL},
+};
+use ssd1306::prelude::*;
+
+#[test]
+fn ssd1306_scrolls_by_itself() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize96x16);
+    display.init().unwrap();
+    let mut marquee = Marquee::new("Hello Tony", GREETING_SCROLL);
+
+    marquee.start(&mut display, 0).unwrap();
+
+    assert!(marquee.in_hardware());
+    let state = bus.borrow().state().clone();
+    assert!(state.is_scrolling());
+    let commands = state.commands();
+    assert_eq!(
+        commands[commands.len() - 2..],
+        [vec![0x27, 0x00, 0, 0b110, 1, 0x00, 0xFF], vec![0x2F]]
+    );
+
+    // Nothing more goes over the bus while it goes round.
+    bus.borrow().clear_log();
+    for now_ms in (0..5_000).step_by(10) {
+        assert!(!marquee.poll(&mut display, now_ms));
+        display.flush().unwrap();
+    }
+    assert_eq!(bus.borrow().state().transactions(), 0);
+}
+
+#[test]
+fn diagonal_scroll_moves_the_whole_panel_up() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    display.init().unwrap();
+    bus.borrow().clear_log();
+
+    let scroll = Scroll::horizontal(1, 2, Direction::Right, Interval::Frames2).diagonal(1);
+    assert!(display.start_scroll(scroll).unwrap());
+
+    assert_eq!(
+        bus.borrow().state().commands(),
+        [
+            vec![0x2E],
+            vec![0xA3, 0, 32],
+            vec![0x29, 0x00, 1, 0b111, 2, 1],
+            vec![0x2F],
+        ]
+    );
+}
+
+#[test]
+fn drawing_stops_the_scroll_and_sends_the_whole_frame() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    display.init().unwrap();
+    screens::hello(&mut display).unwrap();
+    display.flush().unwrap();
+    display
+        .start_scroll(Scroll::horizontal(0, 3, Direction::Left, Interval::Frames5))
+        .unwrap();
+
+    // A flush with nothing new leaves it going.
+    display.flush().unwrap();
+    assert!(bus.borrow().state().is_scrolling());
+
+    Pixel(Point::new(5, 30), BinaryColor::On)
+        .draw(&mut display)
+        .unwrap();
+    bus.borrow().clear_log();
+    display.flush().unwrap();
+
+    let state = bus.borrow().state().clone();
+    assert!(!state.is_scrolling());
+    assert_eq!(state.commands()[0], [0x2E]);
+    assert_eq!(state.data_bytes(), 128 * 32 / 8);
+    assert_eq!(display.flushed_bytes(), Some(128 * 32 / 8));
+}
+
+#[test]
+fn stopping_sends_the_whole_frame_next_time() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    display.init().unwrap();
+    display.flush().unwrap();
+    display.start_scroll(GREETING_SCROLL).unwrap();
+
+    display.stop_scroll().unwrap();
+    display.flush().unwrap();
+
+    assert!(!bus.borrow().state().is_scrolling());
+    assert_eq!(display.flushed_bytes(), Some(128 * 32 / 8));
+}
+
+#[test]
+fn sh1106_is_moved_by_hand() {
+    let bus = RefCell::new(MockSsd1306::sh1106(0x3C));
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x64);
+    display.init().unwrap();
+    let mut marquee = Marquee::new(GREETING, GREETING_SCROLL);
+
+    marquee.start(&mut display, 1_000).unwrap();
+
+    assert!(!marquee.in_hardware());
+    assert!(!bus.borrow().state().opcodes().contains(&0x2F));
+    let before = bus.borrow().state().to_ascii(Size::new(128, 16));
+
+    let step_ms = GREETING_SCROLL.interval.step_us(64).div_ceil(1000);
+    assert!(!marquee.poll(&mut display, 1_000 + step_ms - 1));
+    assert!(marquee.poll(&mut display, 1_000 + step_ms));
+    display.flush().unwrap();
+    assert!(!marquee.poll(&mut display, 1_000 + step_ms));
+
+    // One column to the left, with the first coming round on the right.
+    let after = bus.borrow().state().to_ascii(Size::new(128, 16));
+    for (before, after) in before.lines().zip(after.lines()) {
+        assert_eq!(after, format!("{}{}", &before[1..], &before[..1]));
+    }
+}
+
+#[test]
+fn text_too_long_for_the_panel_goes_round_in_full() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize96x16);
+    display.init().unwrap();
+    let mut marquee = Marquee::new(GREETING, GREETING_SCROLL);
+
+    // The controller would only go round with what's in its RAM.
+    marquee.start(&mut display, 0).unwrap();
+    assert!(!marquee.in_hardware());
+    assert!(!bus.borrow().state().is_scrolling());
+
+    // Every column of it comes past, then the gap, then it starts again.
+    let text_width = 6 * GREETING.len() as u32;
+    let span = screens::marquee_span(&display, GREETING);
+    assert!(span > text_width);
+    let draw = |width, columns| {
+        let mut fb = Framebuffer::new(Size::new(width, 16));
+        fb.init().unwrap();
+        screens::marquee(&mut fb, GREETING, &GREETING_SCROLL, columns).unwrap();
+        fb.flush().unwrap();
+        fb
+    };
+    let whole = draw(text_width, 0);
+    for columns in [0, 10, text_width - 96] {
+        let strip = draw(96, columns);
+        for y in 0..16 {
+            for x in 0..96 {
+                let point = Point::new(x, y);
+                assert_eq!(
+                    strip.pixel(point),
+                    whole.pixel(point + Point::new(columns as i32, 0)),
+                    "{columns} along, at {point}"
+                );
+            }
+        }
+    }
+    assert_eq!(draw(96, 0).screen(), draw(96, span).screen());
+}
+
+#[test]
+fn strip_comes_back_round() {
+    let scroll = Scroll::horizontal(1, 2, Direction::Right, Interval::Frames5);
+    let draw = |columns| {
+        let mut fb = Framebuffer::new(Size::new(96, 32));
+        fb.init().unwrap();
+        fb.clear(BinaryColor::On).unwrap();
+        screens::marquee(&mut fb, "Hello", &scroll, columns).unwrap();
+        fb.flush().unwrap();
+        fb
+    };
+
+    assert_eq!(draw(0).screen(), draw(96).screen());
+    assert_ne!(draw(0).screen(), draw(1).screen());
+    // Only its own pages are touched.
+    let fb = draw(40);
+    assert!((0..96).all(|x| fb.pixel(Point::new(x, 7)) && fb.pixel(Point::new(x, 24))));
+    assert!((0..96).any(|x| fb.pixel(Point::new(x, 16))));
+}
+
+#[test]
+fn only_short_panels_need_the_greeting_to_go_round() {
+    assert!(!screens::hello_fits(&Framebuffer::new(Size::new(96, 16))));
+    for size in [
+        Size::new(128, 32),
+        Size::new(128, 64),
+        Size::new(72, 40),
+        Size::new(64, 48),
+    ] {
+        assert!(screens::hello_fits(&Framebuffer::new(size)), "{size}");
+    }
+}
+
+#[test]
+fn the_greeting_goes_round_on_one_line_and_stays_still_wrapped() {
+    for (size, round) in [
+        (Size::new(128, 32), true),
+        (Size::new(128, 64), true),
+        (Size::new(96, 16), true),
+        (Size::new(72, 40), false),
+        (Size::new(64, 48), false),
+    ] {
+        let display = Framebuffer::new(size);
+        assert_eq!(screens::hello_goes_round(&display), round, "{size}");
+    }
+}
+
+#[test]
+fn steps_take_longer_on_taller_panels() {
+    let interval = Interval::Frames25;
+    assert!(interval.step_us(64) > interval.step_us(32));
+    assert!(Interval::Frames256.step_us(64) > Interval::Frames2.step_us(64));
+}
@@ -0,0 +1,96 @@
+//! The settings menu and the button presses that work it.
+
+use microbit_oled::{
+    brightness::Level,
+    burnin::Saver,
+    input::{Button, Presses, DEBOUNCE_MS},
+    menu::{Item, Menu},
+    settings::Settings,
+};
+
+#[test]
+fn a_steps_through_the_items_and_closes() {
+    let mut settings = Settings::new();
+    let mut menu = Menu::new();
+
+    menu.press(Button::B, &mut settings);
+    assert_eq!(menu.item(), None);
+    assert_eq!(settings, Settings::new());
+
+    menu.press(Button::A, &mut settings);
+    assert_eq!(menu.item(), Some(Item::Brightness));
+    menu.press(Button::A, &mut settings);
+    assert_eq!(menu.item(), Some(Item::NightMode));
+    menu.press(Button::A, &mut settings);
+    assert_eq!(menu.item(), Some(Item::Saver));
+    menu.press(Button::A, &mut settings);
+    assert!(!menu.is_open());
+}
+
+#[test]
+fn b_changes_the_item_picked() {
+    let mut settings = Settings {
+        brightness: Some(Level::High),
+        ..Settings::new()
+    };
+    let mut menu = Menu::new();
+    menu.press(Button::A, &mut settings);
+
+    menu.press(Button::B, &mut settings);
+    assert_eq!(settings.brightness, Some(Level::Highest));
+    menu.press(Button::B, &mut settings);
+    assert_eq!(settings.brightness, Some(Level::Lowest));
+
+    menu.press(Button::A, &mut settings);
+    menu.press(Button::B, &mut settings);
+    assert_eq!(settings.night_mode, Some(true));
+    assert_eq!(settings.brightness, Some(Level::Lowest));
+}
+
+#[test]
+fn unset_settings_change_from_their_defaults() {
+    let mut settings = Settings::new();
+    let mut menu = Menu::new();
+    for _ in Item::ALL {
+        menu.press(Button::A, &mut settings);
+        menu.press(Button::B, &mut settings);
+    }
+
+    assert_eq!(settings.brightness, Some(Level::default().next()));
+    assert_eq!(settings.night_mode, Some(true));
+    assert_eq!(settings.saver, Some(Saver::default().next()));
+}
+
+#[test]
+fn presses_ride_out_bounce() {
+    let mut presses = Presses::new(false, false, 0);
+
+    assert_eq!(presses.update(100, true, false), Some(Button::A));
+    // Bouncing open and shut straight after.
+    assert_eq!(presses.update(101, false, false), None);
+    assert_eq!(presses.update(102, true, false), None);
+    // Let go for good.
+    assert_eq!(presses.update(100 + DEBOUNCE_MS, false, false), None);
+    assert_eq!(
+        presses.update(100 + 2 * DEBOUNCE_MS, true, false),
+        Some(Button::A)
+    );
+}
+
+#[test]
+fn buttons_held_from_the_start_need_pressing_again() {
+    let mut presses = Presses::new(true, false, 0);
+
+    assert_eq!(presses.update(500, true, false), None);
+    assert_eq!(presses.update(600, false, false), None);
+    assert_eq!(presses.update(700, true, false), Some(Button::A));
+}
+
+#[test]
+fn both_at_once_come_out_one_after_the_other() {
+    let mut presses = Presses::new(false, false, 0);
+
+    assert_eq!(presses.update(100, true, true), Some(Button::A));
+    assert_eq!(presses.update(101, true, true), Some(Button::B));
+    assert_eq!(presses.update(102, true, true), None);
+}
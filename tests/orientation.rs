@@ -0,0 +1,244 @@
+//! The accelerometer against a fake one, and the OLED and LEDs turned round
+//! when the board is upside down.
+#![cfg(feature = "std")]
+
+use std::cell::RefCell;
+
+use embedded_graphics::prelude::*;
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use microbit_oled::{
+    display::{Oled, Panel},
+    emulator::Framebuffer,
+    i2c_mock::MockSsd1306,
+    led::{NonBlockingMatrix, RecordingMatrix},
+    lsm303agr::{Accel, AccelError, Lsm303agr, ADDRESS},
+    marquee::{Direction, Interval, Scroll},
+    orientation::{Orientation, Rotated, RotatedLeds, Tracker, POLL_MS, SETTLE_MS},
+    patterns, screens,
+};
+use ssd1306::prelude::*;
+
+/// An LSM303AGR lying still, reading `accel`.
+struct FakeAccel {
+    who_am_i: u8,
+    accel: Accel,
+    writes: Vec<Vec<u8>>,
+}
+
+impl FakeAccel {
+    fn new(accel: Accel) -> Self {
+        Self {
+            who_am_i: 0x33,
+            accel,
+            writes: Vec::new(),
+        }
+    }
+}
+
+impl Write for FakeAccel {
+    type Error = ();
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), ()> {
+        assert_eq!(address, ADDRESS);
+        self.writes.push(bytes.to_vec());
+        Ok(())
+    }
+}
+
+impl WriteRead for FakeAccel {
+    type Error = ();
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), ()> {
+        assert_eq!(address, ADDRESS);
+        match bytes {
+            [0x0F] => buffer[0] = self.who_am_i,
+            [0xA8] => {
+                // Left-justified, as in high resolution mode.
+                let Accel { x, y, z } = self.accel;
+                for (i, axis) in [x, y, z].into_iter().enumerate() {
+                    buffer[2 * i..2 * i + 2].copy_from_slice(&(axis << 4).to_le_bytes());
+                }
+            }
+            _ => return Err(()),
+        }
+        Ok(())
+    }
+}
+
+const UPRIGHT: Accel = Accel {
+    x: 0,
+    y: 1000,
+    z: 0,
+};
+const UPSIDE_DOWN: Accel = Accel {
+    x: 0,
+    y: -1000,
+    z: 0,
+};
+const FLAT: Accel = Accel {
+    x: 0,
+    y: 0,
+    z: 1000,
+};
+
+#[test]
+fn accelerometer_is_checked_set_up_and_read() {
+    let reading = Accel {
+        x: -20,
+        y: 985,
+        z: 130,
+    };
+    let mut accel = Lsm303agr::new(FakeAccel::new(reading));
+
+    accel.init().unwrap();
+
+    assert_eq!(accel.accel(), Ok(reading));
+    assert_eq!(accel.release().writes, [vec![0x20, 0x27], vec![0x23, 0x88]]);
+}
+
+#[test]
+fn something_else_at_the_address_is_refused() {
+    let mut fake = FakeAccel::new(UPRIGHT);
+    fake.who_am_i = 0x40;
+    let mut accel = Lsm303agr::new(fake);
+
+    assert_eq!(accel.init(), Err(AccelError::WrongChip { who_am_i: 0x40 }));
+    assert!(accel.release().writes.is_empty());
+}
+
+#[test]
+fn only_a_board_stood_up_has_a_way_up() {
+    assert_eq!(Orientation::of(UPRIGHT), Some(Orientation::Upright));
+    assert_eq!(Orientation::of(UPSIDE_DOWN), Some(Orientation::UpsideDown));
+    assert_eq!(Orientation::of(FLAT), None);
+    // On its side, or hardly tilted.
+    assert_eq!(
+        Orientation::of(Accel {
+            x: 900,
+            y: -600,
+            z: 0
+        }),
+        None
+    );
+    assert_eq!(
+        Orientation::of(Accel {
+            x: 0,
+            y: -300,
+            z: 950
+        }),
+        None
+    );
+}
+
+#[test]
+fn turning_over_waits_for_the_board_to_settle() {
+    let mut tracker = Tracker::new(0);
+    assert!(tracker.is_due(0));
+
+    let mut turned = None;
+    let mut now_ms = 0;
+    while turned.is_none() {
+        assert!(now_ms <= SETTLE_MS, "never turned");
+        turned = tracker.update(now_ms, UPSIDE_DOWN);
+        now_ms += POLL_MS;
+        assert!(!tracker.is_due(now_ms - 1));
+    }
+
+    assert_eq!(turned, Some(Orientation::UpsideDown));
+    assert_eq!(now_ms - POLL_MS, SETTLE_MS);
+    assert_eq!(tracker.orientation(), Orientation::UpsideDown);
+}
+
+#[test]
+fn a_knock_or_lying_down_turns_nothing() {
+    let mut tracker = Tracker::new(0);
+    let readings = [UPSIDE_DOWN, UPRIGHT].into_iter().cycle();
+    for (step, reading) in readings.take(40).enumerate() {
+        assert_eq!(tracker.update(step as u32 * POLL_MS, reading), None);
+    }
+
+    let mut tracker = Tracker::new(0);
+    for step in 0..40 {
+        let reading = if step == 5 { UPSIDE_DOWN } else { FLAT };
+        assert_eq!(tracker.update(step * POLL_MS, reading), None);
+    }
+    assert_eq!(tracker.orientation(), Orientation::Upright);
+}
+
+#[test]
+fn upside_down_frame_is_the_upright_one_turned_round() {
+    let hello = |orientation| {
+        let mut display = Rotated::new(Framebuffer::default());
+        display.set(orientation);
+        display.init().unwrap();
+        screens::hello(&mut display).unwrap();
+        display.flush().unwrap();
+        display.inner().clone()
+    };
+    let upright = hello(Orientation::Upright);
+    let turned = hello(Orientation::UpsideDown);
+
+    let Size { width, height } = upright.size();
+    let corner = Point::new(width as i32 - 1, height as i32 - 1);
+    assert_ne!(upright.screen(), turned.screen());
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let point = Point::new(x, y);
+            assert_eq!(
+                turned.pixel(point),
+                upright.pixel(corner - point),
+                "at {point}"
+            );
+        }
+    }
+}
+
+#[test]
+fn upside_down_scroll_uses_the_other_pages_and_goes_the_other_way() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut display = Rotated::new(Panel::new(&bus, 0x3C, DisplaySize128x32));
+    display.init().unwrap();
+    display.set(Orientation::UpsideDown);
+    bus.borrow().clear_log();
+
+    let scroll = Scroll::horizontal(0, 1, Direction::Left, Interval::Frames25);
+    assert!(display.start_scroll(scroll).unwrap());
+
+    assert_eq!(
+        bus.borrow().state().commands(),
+        [
+            vec![0x2E],
+            vec![0x26, 0x00, 2, 0b110, 3, 0x00, 0xFF],
+            vec![0x2F],
+        ]
+    );
+}
+
+#[test]
+fn leds_turn_round_with_the_board() {
+    let mut leds = RotatedLeds::new(RecordingMatrix::new());
+    leds.set(patterns::ARROW_N);
+    assert_eq!(leds.inner().lit(), patterns::ARROW_N);
+
+    // What's showing turns straight away, and so does what comes after.
+    leds.set_orientation(Orientation::UpsideDown);
+    assert_eq!(leds.inner().lit(), patterns::ARROW_S);
+    leds.set(patterns::ARROW_E);
+    assert_eq!(leds.inner().lit(), patterns::ARROW_W);
+
+    leds.set_orientation(Orientation::Upright);
+    assert_eq!(leds.inner().lit(), patterns::ARROW_E);
+}
+
+#[test]
+fn accelerometer_powers_down_until_set_up_again() {
+    let mut accel = Lsm303agr::new(FakeAccel::new(UPRIGHT));
+
+    accel.power_down().unwrap();
+    accel.init().unwrap();
+
+    assert_eq!(
+        accel.release().writes,
+        [vec![0x20, 0x07], vec![0x20, 0x27], vec![0x23, 0x88]]
+    );
+}
@@ -0,0 +1,96 @@
+use microbit_oled::{
+    font,
+    patterns::{self, Pattern, MAX_BRIGHTNESS},
+};
+
+#[test]
+fn parses_rows_into_cells() {
+    assert_eq!(
+        patterns::CHECK.rows(),
+        [
+            [0, 0, 0, 0, 9],
+            [0, 0, 0, 9, 0],
+            [9, 0, 9, 0, 0],
+            [0, 9, 0, 0, 0],
+            [0, 0, 0, 0, 0],
+        ]
+    );
+}
+
+#[test]
+fn digits_are_brightness_levels() {
+    let glow = Pattern::from_rows(["..1..", ".353.", "15#51", ".353.", "..1.."]);
+
+    assert_eq!(glow.brightness(2, 2), MAX_BRIGHTNESS);
+    assert_eq!(glow.brightness(1, 2), 5);
+    assert_eq!(glow.brightness(2, 0), 1);
+    assert_eq!(glow.lit_count(), 13);
+    assert_eq!(glow.dimmed(3).brightness(2, 2), 3);
+    assert_eq!(glow.dimmed(3).brightness(2, 0), 1);
+    assert_eq!(Pattern::new([[12; 5]; 5]), Pattern::FULL);
+    assert_eq!(
+        (glow | glow.dimmed(4)).brightness(1, 1),
+        glow.brightness(1, 1)
+    );
+    assert_eq!((glow & glow.dimmed(4)).brightness(1, 1), 3);
+}
+
+#[test]
+fn quarter_turns_walk_round_the_compass() {
+    assert_eq!(patterns::ARROW_N.rotate_cw(), patterns::ARROW_E);
+    assert_eq!(patterns::ARROW_E.rotate_cw(), patterns::ARROW_S);
+    assert_eq!(patterns::ARROW_S.rotate_cw(), patterns::ARROW_W);
+    assert_eq!(patterns::ARROW_W.rotate_cw(), patterns::ARROW_N);
+    assert_eq!(patterns::ARROW_NE.rotate_ccw(), patterns::ARROW_NW);
+    assert_eq!(
+        patterns::HEART.rotate_180(),
+        patterns::HEART.flip_vertical()
+    );
+    assert_eq!(
+        patterns::ARROW_E,
+        Pattern::from_rows(["..#..", "...#.", "#####", "...#.", "..#.."])
+    );
+}
+
+#[test]
+fn invert_and_combine() {
+    assert_eq!(!Pattern::BLANK, Pattern::FULL);
+    assert_eq!(patterns::CROSS.invert().lit_count(), 25 - 9);
+    assert_eq!(patterns::CROSS | !patterns::CROSS, Pattern::FULL);
+    assert_eq!(patterns::CROSS & !patterns::CROSS, Pattern::BLANK);
+    assert_eq!(patterns::SMILEY ^ patterns::SMILEY, Pattern::BLANK);
+    assert_eq!(
+        patterns::SMILEY & patterns::SAD,
+        Pattern::from_rows([".#.#.", ".#.#.", ".....", ".....", "....."])
+    );
+}
+
+#[test]
+fn shift_drops_what_falls_off() {
+    assert_eq!(patterns::DOT.shift(2, -2), Pattern::BLANK.with(4, 0, true));
+    assert_eq!(patterns::DOT.shift(3, 0), Pattern::BLANK);
+    assert_eq!(
+        patterns::SQUARE.shift(1, 1).shift(-1, -1),
+        Pattern::from_rows(["####.", "#....", "#....", "#....", "....."])
+    );
+}
+
+#[test]
+fn font_covers_digits_letters_and_punctuation() {
+    for c in ('0'..='9')
+        .chain('A'..='Z')
+        .chain(font::PUNCTUATION_CHARS.chars())
+    {
+        let glyph = font::glyph(c).unwrap_or_else(|| panic!("no glyph for {c:?}"));
+        if c != ' ' {
+            assert!((0..5).any(|y| glyph.is_lit(0, y)), "{c:?} not left-aligned");
+        }
+    }
+    assert_eq!(font::glyph('q'), font::glyph('Q'));
+    assert_eq!(font::glyph('~'), None);
+    assert_eq!(font::width(font::glyph('1').unwrap()), 3);
+    assert_eq!(font::width(font::glyph('M').unwrap()), 5);
+    assert_eq!(font::width(font::glyph(' ').unwrap()), 3);
+    assert_eq!(font::hex_digit(0x3C), font::glyph('C').unwrap());
+    assert_eq!(font::hex_digit(0x09), font::glyph('9').unwrap());
+}
@@ -0,0 +1,108 @@
+//! The boot sequence on the non-blocking matrix, against a simulated clock.
+#![cfg(feature = "std")]
+
+use std::cell::RefCell;
+
+use microbit_oled::{
+    boot::{Boot, Step, BLINK_MS, PAUSE_MS},
+    display::{Oled, Panel},
+    emulator::Framebuffer,
+    i2c_mock::MockSsd1306,
+    led::RecordingMatrix,
+    patterns::{self, Pattern},
+    player::Player,
+};
+use ssd1306::prelude::*;
+
+/// What the boot sequence lights in its first `duration_ms`, polled every
+/// 10 ms.
+fn boot(oled: &mut impl Oled, duration_ms: u32) -> Vec<(u32, Pattern)> {
+    let mut leds = RecordingMatrix::new();
+    let mut player = Player::new();
+    let mut boot = Boot::new();
+
+    for now_ms in (0..duration_ms).step_by(10) {
+        leds.set_now_ms(now_ms);
+        if player.wants_step() {
+            player.queue(boot.step(oled));
+        }
+        player.poll(now_ms, &mut leds);
+    }
+    leds.changes().to_vec()
+}
+
+#[test]
+fn boot_keeps_its_timing_without_blocking() {
+    assert_eq!(
+        boot(&mut Framebuffer::default(), 5000),
+        [
+            (0, patterns::SMILEY),
+            (1000, patterns::CHECK),
+            (2000, patterns::HEART),
+            (4000, Pattern::BLANK),
+        ]
+    );
+}
+
+#[test]
+fn smiley_then_one_blink_code_without_oled() {
+    let bus = RefCell::new(MockSsd1306::default());
+    bus.borrow().set_present(false);
+    let mut oled = Panel::new(&bus, 0x3C, DisplaySize128x32);
+
+    let blink_ms = BLINK_MS + PAUSE_MS;
+    assert_eq!(
+        boot(&mut oled, 1000 + 3 * blink_ms),
+        [
+            (0, patterns::SMILEY),
+            (1000, patterns::CROSS),
+            (1000 + BLINK_MS, Pattern::BLANK),
+            (1000 + blink_ms, patterns::CROSS),
+            (1000 + blink_ms + BLINK_MS, Pattern::BLANK),
+            (1000 + 2 * blink_ms, patterns::CROSS),
+            (1000 + 2 * blink_ms + BLINK_MS, Pattern::BLANK),
+        ]
+    );
+}
+
+#[test]
+fn oled_work_happens_while_a_pattern_is_up() {
+    let mut oled = Framebuffer::default();
+    let mut leds = RecordingMatrix::new();
+    let mut player = Player::new();
+    let mut boot = Boot::new();
+
+    player.queue(boot.step(&mut oled));
+    player.poll(0, &mut leds);
+    assert!(player.wants_step());
+    player.queue(boot.step(&mut oled));
+
+    // The smiley is still up, but the OLED is already initialised.
+    assert_eq!(leds.changes(), [(0, patterns::SMILEY)]);
+    assert!(oled.is_initialised());
+
+    player.poll(999, &mut leds);
+    assert!(!player.wants_step());
+    player.poll(1000, &mut leds);
+    assert_eq!(leds.changes().last(), Some(&(0, patterns::CHECK)));
+}
+
+#[test]
+fn survives_clock_wrap() {
+    let mut leds = RecordingMatrix::new();
+    let mut player = Player::new();
+    let start = u32::MAX - 500;
+
+    player.queue(Step::Show {
+        pattern: patterns::HEART,
+        duration_ms: 1000,
+    });
+    player.poll(start, &mut leds);
+    player.poll(start.wrapping_add(999), &mut leds);
+    assert!(!player.is_idle());
+    player.poll(start.wrapping_add(1000), &mut leds);
+
+    assert!(player.is_idle());
+    assert_eq!(leds.changes().len(), 2);
+    assert_eq!(leds.changes()[1].1, Pattern::BLANK);
+}
@@ -0,0 +1,105 @@
+//! Standby's timing, and the panel turned off and on through the drivers.
+#![cfg(feature = "std")]
+
+use std::cell::RefCell;
+
+use microbit_oled::{
+    brightness::{Dimmable, Level},
+    burnin::Saver,
+    display::{Health, Oled, Panel},
+    emulator::Framebuffer,
+    i2c_mock::MockSsd1306,
+    power::{Mode, Power, STANDBY_AFTER_MS},
+};
+use ssd1306::prelude::*;
+
+#[test]
+fn standby_comes_once_when_left_alone() {
+    let mut power = Power::new(Saver::Logo, 0);
+    assert!(!power.poll(STANDBY_AFTER_MS - 1));
+    assert_eq!(power.due_in_ms(STANDBY_AFTER_MS - 1), Some(1));
+
+    assert!(power.poll(STANDBY_AFTER_MS));
+    assert!(!power.poll(STANDBY_AFTER_MS + 1));
+    assert!(power.is_standby());
+    assert_eq!(power.mode(false), Mode::Standby);
+    assert_eq!(power.due_in_ms(STANDBY_AFTER_MS + 1), None);
+}
+
+#[test]
+fn a_press_wakes_it_up_and_starts_the_wait_again() {
+    let mut power = Power::new(Saver::Blank, 0);
+    assert!(!power.input(1_000));
+    assert!(!power.poll(STANDBY_AFTER_MS));
+
+    let standby_ms = 1_000 + STANDBY_AFTER_MS;
+    assert!(power.poll(standby_ms));
+    assert!(power.input(standby_ms + 5_000));
+    assert!(!power.is_standby());
+    assert_eq!(power.mode(true), Mode::Active);
+    assert!(!power.poll(standby_ms + 6_000));
+}
+
+#[test]
+fn no_standby_with_the_screensaver_off() {
+    let mut power = Power::new(Saver::Off, 0);
+    assert!(!power.poll(10 * STANDBY_AFTER_MS));
+    assert_eq!(power.due_in_ms(0), None);
+
+    power.set_saver(Saver::Clock);
+    assert!(power.poll(10 * STANDBY_AFTER_MS));
+}
+
+#[test]
+fn each_mode_draws_less() {
+    assert!(Mode::Active.estimated_ua() > Mode::Idle.estimated_ua());
+    assert!(Mode::Idle.estimated_ua() > Mode::Standby.estimated_ua());
+    // Years on a pair of AAA cells, rather than days.
+    assert!(Mode::Standby.estimated_ua() < 100);
+}
+
+#[test]
+fn panel_turned_off_keeps_its_frame_and_isnt_taken_for_reset() {
+    let bus = RefCell::new(MockSsd1306::default());
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    display.init().unwrap();
+    bus.borrow().clear_log();
+
+    display.set_display_on(false).unwrap();
+
+    let state = bus.borrow().state().clone();
+    assert_eq!(state.commands(), [vec![0xAE]]);
+    assert!(!state.is_display_on());
+    assert_eq!(display.check().unwrap(), Health::Ok);
+
+    display.set_display_on(true).unwrap();
+    assert!(bus.borrow().state().is_display_on());
+    assert_eq!(display.check().unwrap(), Health::Ok);
+}
+
+#[test]
+fn sh1106_turns_off_too() {
+    let bus = RefCell::new(MockSsd1306::sh1106(0x3C));
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x64);
+    display.init().unwrap();
+
+    display.set_display_on(false).unwrap();
+
+    assert!(!bus.borrow().state().is_display_on());
+    assert_eq!(display.check().unwrap(), Health::Ok);
+}
+
+#[test]
+fn panel_stays_off_through_an_init() {
+    let mut display = Dimmable::new(Framebuffer::default());
+    display.init().unwrap();
+    display.set_brightness(Level::Low.drive()).unwrap();
+    display.set_display_on(false).unwrap();
+
+    display.init().unwrap();
+
+    assert!(!display.inner().is_display_on());
+    assert_eq!(display.inner().brightness(), Some(Level::Low.drive()));
+    display.set_display_on(true).unwrap();
+    assert!(display.inner().is_display_on());
+}
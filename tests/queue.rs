@@ -0,0 +1,206 @@
+//! Frames flushed in the background, with the TWIM interrupt played by hand.
+#![cfg(feature = "std")]
+
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use embedded_graphics::prelude::*;
+use embedded_hal::blocking::i2c::{Read, Write};
+use microbit_oled::{
+    bus::{BusFault, Diagnose},
+    display::{Health, Oled, Panel},
+    emulator::Framebuffer,
+    error::Error,
+    i2c_mock::{MockError, MockSsd1306},
+    queue::{Full, Queue, BATCH_BYTES},
+    screens,
+    speed::{Link, Speed},
+};
+use ssd1306::prelude::*;
+
+/// The fake SSD1306 behind a queue, the way the firmware's bus puts one in
+/// front of the TWIM. Writes are queued while `background` is set; anything
+/// else waits for the queue to empty first.
+#[derive(Clone, Default)]
+struct Background {
+    oled: MockSsd1306,
+    queue: Rc<RefCell<Queue<MockError>>>,
+    background: Rc<Cell<bool>>,
+}
+
+impl Background {
+    /// Let up to `writes` queued writes go out, as the interrupt would, and
+    /// say how many did.
+    fn pump(&self, writes: usize) -> usize {
+        let mut queue = self.queue.borrow_mut();
+        for sent in 0..writes {
+            let Some((address, bytes)) = queue.start_next() else {
+                return sent;
+            };
+            let result = self.oled.clone().write(address, bytes);
+            queue.finished(result);
+        }
+        writes
+    }
+
+    /// Wait for the queue to empty, and report what went wrong in it.
+    fn drain(&self) -> Result<(), MockError> {
+        while self.pump(1) > 0 {}
+        self.queue.borrow_mut().take_error().map_or(Ok(()), Err)
+    }
+}
+
+impl Write for Background {
+    type Error = MockError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), MockError> {
+        if !self.background.get() {
+            self.drain()?;
+            return self.oled.write(address, bytes);
+        }
+        while self.queue.borrow_mut().write(address, bytes) == Err(Full) {
+            // Room is made as the batch going out finishes.
+            assert!(self.pump(1) > 0, "write doesn't fit in a batch");
+        }
+        Ok(())
+    }
+}
+
+impl Read for Background {
+    type Error = MockError;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), MockError> {
+        self.drain()?;
+        self.oled.read(address, buffer)
+    }
+}
+
+impl Diagnose for Background {
+    fn diagnose(error: &MockError) -> BusFault {
+        MockSsd1306::diagnose(error)
+    }
+}
+
+fn expected(draw: impl FnOnce(&mut Framebuffer)) -> String {
+    let mut fb = Framebuffer::default();
+    fb.init().unwrap();
+    draw(&mut fb);
+    fb.flush().unwrap();
+    fb.to_ascii()
+}
+
+#[test]
+fn writes_come_out_in_order_across_batches() {
+    let mut queue = Queue::<()>::new();
+    queue.write(0x3C, &[1]).unwrap();
+    queue.write(0x3C, &[2, 3]).unwrap();
+    assert_eq!(queue.start_next(), Some((0x3C, &[1][..])));
+
+    // Goes in the other batch, after the one being sent.
+    queue.write(0x3D, &[4]).unwrap();
+    queue.finished(Ok(()));
+    assert_eq!(queue.start_next(), Some((0x3C, &[2, 3][..])));
+    queue.finished(Ok(()));
+    assert_eq!(queue.start_next(), Some((0x3D, &[4][..])));
+    queue.finished(Ok(()));
+    assert_eq!(queue.start_next(), None);
+    assert!(queue.is_idle());
+}
+
+#[test]
+fn full_batch_waits_for_the_other() {
+    let mut queue = Queue::<()>::new();
+    let big = [0; BATCH_BYTES / 2];
+    queue.write(0x3C, &big).unwrap();
+    assert_eq!(queue.write(0x3C, &big), Err(Full));
+
+    queue.start_next().unwrap();
+
+    assert_eq!(queue.write(0x3C, &big), Ok(()));
+    assert_eq!(queue.write(0x3C, &big), Err(Full));
+}
+
+#[test]
+fn failure_drops_the_rest_and_is_kept() {
+    let mut queue = Queue::new();
+    queue.write(0x3C, &[1]).unwrap();
+    queue.write(0x3C, &[2]).unwrap();
+    queue.start_next().unwrap();
+
+    queue.finished(Err("nack"));
+
+    assert!(queue.is_idle());
+    assert_eq!(queue.start_next(), None);
+    assert_eq!(queue.take_error(), Some("nack"));
+    assert_eq!(queue.take_error(), None);
+}
+
+#[test]
+fn background_flush_returns_before_anything_is_sent() {
+    let bus = RefCell::new(Background::default());
+    let oled = bus.borrow().clone();
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    Oled::init(&mut display).unwrap();
+    oled.oled.clear_log();
+
+    oled.background.set(true);
+    screens::hello(&mut display).unwrap();
+    Oled::flush(&mut display).unwrap();
+
+    assert_eq!(oled.oled.state().data_bytes(), 0);
+    assert!(!oled.queue.borrow().is_idle());
+    oled.drain().unwrap();
+    assert_eq!(
+        oled.oled.state().to_ascii(Size::new(128, 32)),
+        include_str!("golden/hello.txt")
+    );
+}
+
+#[test]
+fn next_frame_is_drawn_while_the_last_goes_out() {
+    let bus = RefCell::new(Background::default());
+    let oled = bus.borrow().clone();
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    Oled::init(&mut display).unwrap();
+    oled.background.set(true);
+    screens::hello(&mut display).unwrap();
+    Oled::flush(&mut display).unwrap();
+    oled.pump(1);
+
+    screens::link(&mut display, Link::I2c(Speed::K400), 12_300).unwrap();
+    Oled::flush(&mut display).unwrap();
+    assert_ne!(
+        oled.oled.state().to_ascii(Size::new(128, 32)),
+        include_str!("golden/hello.txt")
+    );
+
+    oled.drain().unwrap();
+    assert_eq!(
+        oled.oled.state().to_ascii(Size::new(128, 32)),
+        expected(|fb| {
+            screens::hello(fb).unwrap();
+            screens::link(fb, Link::I2c(Speed::K400), 12_300).unwrap();
+        })
+    );
+}
+
+#[test]
+fn failed_background_write_shows_up_in_the_next_check() {
+    let bus = RefCell::new(Background::default());
+    let oled = bus.borrow().clone();
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    Oled::init(&mut display).unwrap();
+    oled.background.set(true);
+    screens::hello(&mut display).unwrap();
+    Oled::flush(&mut display).unwrap();
+
+    oled.oled.set_present(false);
+    oled.pump(1);
+    oled.oled.set_present(true);
+
+    assert!(matches!(display.check(), Err(Error::AddressNack { .. })));
+    assert!(oled.queue.borrow().is_idle());
+    assert!(matches!(display.check(), Ok(Health::Ok)));
+}
@@ -0,0 +1,168 @@
+//! Bus recovery against a simulated pair of open-drain lines.
+
+use std::{cell::RefCell, rc::Rc};
+
+use embedded_hal::blocking::delay::DelayUs;
+use microbit_oled::{
+    boot::{Boot, State, Step, BLINK_MS},
+    emulator::Framebuffer,
+    error::Error,
+    patterns,
+    recovery::{self, OpenDrain, Recovery, MAX_CLOCKS},
+};
+
+/// Both lines and a device on them. Each line is low if anyone pulls it low.
+#[derive(Default)]
+struct Wires {
+    scl_pulled: bool,
+    sda_pulled: bool,
+    /// The device holds SDA low until it has seen this many clocks.
+    device_holds_sda_for: Option<u8>,
+    /// SCL is shorted to ground.
+    scl_shorted: bool,
+    clocks: u8,
+    /// Whether a STOP was seen: SDA rising while SCL is high.
+    stop: bool,
+}
+
+impl Wires {
+    fn scl(&self) -> bool {
+        !self.scl_pulled && !self.scl_shorted
+    }
+
+    fn sda(&self) -> bool {
+        !self.sda_pulled && self.device_holds_sda_for.is_none_or(|n| self.clocks >= n)
+    }
+}
+
+struct Scl(Rc<RefCell<Wires>>);
+struct Sda(Rc<RefCell<Wires>>);
+
+impl OpenDrain for Scl {
+    fn release(&mut self) {
+        let mut wires = self.0.borrow_mut();
+        if wires.scl_pulled && !wires.scl_shorted {
+            wires.clocks += 1;
+        }
+        wires.scl_pulled = false;
+    }
+
+    fn pull_low(&mut self) {
+        self.0.borrow_mut().scl_pulled = true;
+    }
+
+    fn is_high(&mut self) -> bool {
+        self.0.borrow().scl()
+    }
+}
+
+impl OpenDrain for Sda {
+    fn release(&mut self) {
+        let mut wires = self.0.borrow_mut();
+        let was_low = !wires.sda();
+        wires.sda_pulled = false;
+        if was_low && wires.sda() && wires.scl() {
+            wires.stop = true;
+        }
+    }
+
+    fn pull_low(&mut self) {
+        self.0.borrow_mut().sda_pulled = true;
+    }
+
+    fn is_high(&mut self) -> bool {
+        self.0.borrow().sda()
+    }
+}
+
+struct NoDelay;
+
+impl DelayUs<u32> for NoDelay {
+    fn delay_us(&mut self, _us: u32) {}
+}
+
+fn recover(wires: Wires) -> (Recovery, Wires) {
+    let wires = Rc::new(RefCell::new(wires));
+    let outcome = recovery::recover(
+        &mut Scl(wires.clone()),
+        &mut Sda(wires.clone()),
+        &mut NoDelay,
+    );
+    (outcome, Rc::into_inner(wires).unwrap().into_inner())
+}
+
+#[test]
+fn idle_bus_is_left_alone() {
+    let (outcome, wires) = recover(Wires::default());
+
+    assert_eq!(outcome, Recovery::Idle);
+    assert_eq!(wires.clocks, 0);
+}
+
+#[test]
+fn device_holding_sda_is_clocked_free() {
+    let (outcome, wires) = recover(Wires {
+        device_holds_sda_for: Some(5),
+        ..Default::default()
+    });
+
+    assert_eq!(outcome, Recovery::Recovered { clocks: 5 });
+    assert!(wires.stop);
+    assert!(wires.scl() && wires.sda());
+}
+
+#[test]
+fn gives_up_after_nine_clocks() {
+    let (outcome, wires) = recover(Wires {
+        device_holds_sda_for: Some(MAX_CLOCKS + 1),
+        ..Default::default()
+    });
+
+    assert_eq!(outcome, Recovery::SdaStuck);
+    assert_eq!(wires.clocks, MAX_CLOCKS);
+    assert_eq!(outcome.error().map(|e| e.code()), Some(3));
+}
+
+#[test]
+fn shorted_scl_is_reported() {
+    let (outcome, wires) = recover(Wires {
+        scl_shorted: true,
+        ..Default::default()
+    });
+
+    assert_eq!(outcome, Recovery::SclStuck);
+    assert_eq!(wires.clocks, 0);
+}
+
+#[test]
+fn boot_reports_the_outcome() {
+    let mut oled = Framebuffer::default();
+    let mut boot = Boot::new().with_recovery(Recovery::Recovered { clocks: 2 });
+    boot.step(&mut oled);
+    assert_eq!(
+        boot.step(&mut oled),
+        Step::Show {
+            pattern: patterns::EXCLAMATION,
+            duration_ms: 1000
+        }
+    );
+    assert!(!oled.is_initialised());
+
+    let mut boot = Boot::new().with_recovery(Recovery::SdaStuck);
+    boot.step(&mut oled);
+    assert_eq!(
+        boot.step(&mut oled),
+        Step::Show {
+            pattern: patterns::CROSS,
+            duration_ms: BLINK_MS
+        }
+    );
+    assert!(matches!(
+        boot.state(),
+        State::Failed {
+            error: Error::BusStuck,
+            ..
+        }
+    ));
+    assert!(!oled.is_initialised());
+}
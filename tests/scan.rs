@@ -0,0 +1,146 @@
+//! The bus scanner against a bus with the fake SSD1306 and a few other parts.
+#![cfg(feature = "std")]
+
+use std::cell::RefCell;
+
+use embedded_graphics::prelude::*;
+use embedded_hal::blocking::i2c::{Read, Write};
+use microbit_oled::{
+    boot::Step,
+    bus::{BusFault, Diagnose},
+    display::{Oled, Panel},
+    emulator::Framebuffer,
+    font,
+    i2c_mock::{MockError, MockSsd1306},
+    scan::{self, Scanner, RESCAN_MS, SCROLL_PAUSE_MS},
+    screens,
+    scroll::{Scroll, DEFAULT_COLUMN_MS},
+};
+use ssd1306::prelude::*;
+
+/// The fake OLED plus other parts that only answer reads.
+struct Parts {
+    oled: MockSsd1306,
+    others: Vec<u8>,
+}
+
+impl Write for Parts {
+    type Error = MockError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), MockError> {
+        if self.others.contains(&address) {
+            return Ok(());
+        }
+        self.oled.write(address, bytes)
+    }
+}
+
+impl Read for Parts {
+    type Error = MockError;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), MockError> {
+        if self.others.contains(&address) {
+            buffer.fill(0);
+            return Ok(());
+        }
+        self.oled.read(address, buffer)
+    }
+}
+
+impl Diagnose for Parts {
+    fn diagnose(error: &MockError) -> BusFault {
+        MockSsd1306::diagnose(error)
+    }
+}
+
+fn bus(others: &[u8]) -> (MockSsd1306, RefCell<Parts>) {
+    let oled = MockSsd1306::default();
+    let parts = Parts {
+        oled: oled.clone(),
+        others: others.to_vec(),
+    };
+    (oled, RefCell::new(parts))
+}
+
+#[test]
+fn finds_every_part() {
+    let (_, bus) = bus(&[0x50, 0x68, 0x76]);
+
+    let found = scan::scan(&mut *bus.borrow_mut());
+
+    assert_eq!(found.iter().collect::<Vec<_>>(), [0x3C, 0x50, 0x68, 0x76]);
+    assert_eq!(found.len(), 4);
+}
+
+#[test]
+fn reserved_addresses_are_skipped() {
+    let (oled, bus) = bus(&[0x00, 0x7F]);
+    oled.set_present(false);
+
+    assert!(scan::scan(&mut *bus.borrow_mut()).is_empty());
+}
+
+#[test]
+fn known_parts_are_named() {
+    assert_eq!(scan::label(0x3D), Some("SSD1306"));
+    assert_eq!(scan::label(0x77), Some("BME280"));
+    assert_eq!(scan::label(0x68), Some("DS3231"));
+    assert_eq!(scan::label(0x53), Some("AT24Cxx"));
+    assert_eq!(scan::label(0x42), None);
+}
+
+#[test]
+fn list_goes_on_the_oled() {
+    let (oled, bus) = bus(&[0x50, 0x68]);
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    let mut scanner = Scanner::new();
+
+    let step = scanner.step(&bus, &mut display);
+
+    assert!(scanner.is_on_oled());
+    assert_eq!(
+        step,
+        Step::Show {
+            pattern: font::DIGITS[3],
+            duration_ms: RESCAN_MS
+        }
+    );
+    let mut expected = Framebuffer::default();
+    expected.init().unwrap();
+    screens::scan(&mut expected, &scanner.found()).unwrap();
+    expected.flush().unwrap();
+    assert_eq!(oled.state().to_ascii(expected.size()), expected.to_ascii());
+
+    // Nothing changed, so the next pass leaves the screen alone.
+    oled.clear_log();
+    scanner.step(&bus, &mut display);
+    assert_eq!(oled.state().data_bytes(), 0);
+}
+
+#[test]
+fn addresses_scroll_without_an_oled() {
+    let (oled, bus) = bus(&[0x68]);
+    oled.set_present(false);
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    let mut scanner = Scanner::new();
+
+    let mut steps = vec![scanner.step(&bus, &mut display)];
+    while !matches!(steps.last(), Some(Step::Wait { .. })) {
+        steps.push(scanner.step(&bus, &mut display));
+    }
+
+    assert!(!scanner.is_on_oled());
+    assert_eq!(
+        steps.last(),
+        Some(&Step::Wait {
+            duration_ms: SCROLL_PAUSE_MS
+        })
+    );
+    let frames: Vec<_> = Scroll::new("68", DEFAULT_COLUMN_MS)
+        .map(|frame| Step::Show {
+            pattern: frame.pattern,
+            duration_ms: frame.duration_ms,
+        })
+        .collect();
+    assert_eq!(steps[..steps.len() - 1], frames);
+}
@@ -0,0 +1,220 @@
+//! Golden-image tests for everything drawn on the OLED.
+//!
+//! Run with `UPDATE_GOLDEN=1 cargo host-test` to rewrite the files in
+//! `tests/golden/` after an intended change, then review the diff.
+#![cfg(feature = "std")]
+
+use std::{fs, path::Path};
+
+use embedded_graphics::prelude::*;
+use microbit_oled::{
+    boot::Boot,
+    brightness::Level,
+    burnin::Saver,
+    display::Oled,
+    emulator::Framebuffer,
+    error::Error,
+    menu::Item,
+    scan::Responders,
+    screens::{self, Layout},
+    settings::Settings,
+    speed::{Link, Speed},
+};
+
+fn assert_golden(name: &str, fb: &Framebuffer) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    let actual = fb.to_ascii();
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &actual).unwrap();
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {e} (run with UPDATE_GOLDEN=1)", path.display()));
+    assert!(
+        actual == expected,
+        "{name} differs from the golden image\n--- expected\n{expected}--- actual\n{actual}"
+    );
+}
+
+#[test]
+fn hello_screen() {
+    let mut fb = Framebuffer::default();
+    fb.init().unwrap();
+    screens::hello(&mut fb).unwrap();
+    fb.flush().unwrap();
+
+    assert_golden("hello.txt", &fb);
+}
+
+#[test]
+fn link_line_under_the_greeting() {
+    let mut fb = Framebuffer::default();
+    fb.init().unwrap();
+    screens::hello(&mut fb).unwrap();
+    screens::link(&mut fb, Link::I2c(Speed::K250), 99_999).unwrap();
+    // Drawn again, the new figure replaces the old one.
+    screens::link(&mut fb, Link::I2c(Speed::K400), 12_345).unwrap();
+    fb.flush().unwrap();
+
+    assert_golden("link.txt", &fb);
+}
+
+#[test]
+fn link_line_on_spi() {
+    let mut fb = Framebuffer::default();
+    fb.init().unwrap();
+    screens::hello(&mut fb).unwrap();
+    screens::link(&mut fb, Link::Spi { mhz: 8 }, 2_150).unwrap();
+    fb.flush().unwrap();
+
+    assert_golden("link-spi.txt", &fb);
+}
+
+#[test]
+fn settings_screen() {
+    let mut fb = Framebuffer::default();
+    fb.init().unwrap();
+    let settings = Settings {
+        brightness: Some(Level::High),
+        ..Settings::new()
+    };
+    screens::settings(&mut fb, Item::Brightness, &settings).unwrap();
+    fb.flush().unwrap();
+
+    assert_golden("settings.txt", &fb);
+}
+
+#[test]
+fn saver_screens() {
+    let mut fb = Framebuffer::default();
+    fb.init().unwrap();
+
+    screens::saver(&mut fb, Saver::Logo, 20, 0).unwrap();
+    fb.flush().unwrap();
+    assert_golden("saver-logo.txt", &fb);
+
+    // An hour, two minutes and three seconds in, five steps along.
+    screens::saver(&mut fb, Saver::Clock, 5, 3_723_000).unwrap();
+    fb.flush().unwrap();
+    assert_golden("saver-clock.txt", &fb);
+}
+
+#[test]
+fn error_screen() {
+    let mut fb = Framebuffer::default();
+    fb.init().unwrap();
+    screens::error(&mut fb, &Error::WrongController { status: 0x08 }).unwrap();
+    fb.flush().unwrap();
+
+    assert_golden("error.txt", &fb);
+}
+
+#[test]
+fn scan_screen() {
+    let found = [0x3C, 0x50, 0x68, 0x76, 0x77]
+        .into_iter()
+        .fold(Responders::new(), Responders::with);
+    let mut fb = Framebuffer::default();
+    fb.init().unwrap();
+    screens::scan(&mut fb, &found).unwrap();
+    fb.flush().unwrap();
+
+    assert_golden("scan.txt", &fb);
+}
+
+/// The other panels the firmware can be built for.
+const OTHER_SIZES: [Size; 4] = [
+    Size::new(128, 64),
+    Size::new(96, 16),
+    Size::new(72, 40),
+    Size::new(64, 48),
+];
+
+#[test]
+fn layouts_fit_the_panel() {
+    let lines_and_chars = OTHER_SIZES.map(|size| {
+        let layout = Layout::new(size);
+        (layout.lines, layout.chars)
+    });
+    assert_eq!(lines_and_chars, [(5, 21), (1, 16), (3, 12), (4, 10)]);
+    assert_eq!(
+        Layout::new(Size::new(128, 32)).baseline(2),
+        Point::new(0, 30)
+    );
+}
+
+#[test]
+fn screens_on_other_panels() {
+    let found = [0x3C, 0x50, 0x68, 0x76, 0x77]
+        .into_iter()
+        .fold(Responders::new(), Responders::with);
+    for size in OTHER_SIZES {
+        let name = |screen| format!("{screen}-{}x{}.txt", size.width, size.height);
+        let mut fb = Framebuffer::new(size);
+        fb.init().unwrap();
+
+        screens::hello(&mut fb).unwrap();
+        screens::link(&mut fb, Link::I2c(Speed::K400), 12_345).unwrap();
+        fb.flush().unwrap();
+        assert_golden(&name("hello"), &fb);
+
+        screens::error(&mut fb, &Error::AddressNack { address: 0x3C }).unwrap();
+        fb.flush().unwrap();
+        assert_golden(&name("error"), &fb);
+
+        screens::scan(&mut fb, &found).unwrap();
+        fb.flush().unwrap();
+        assert_golden(&name("scan"), &fb);
+
+        // The last item, so short panels have to scroll down to it.
+        let settings = Settings {
+            brightness: Some(Level::Lowest),
+            night_mode: Some(true),
+            saver: Some(Saver::Clock),
+            ..Settings::new()
+        };
+        screens::settings(&mut fb, Item::Saver, &settings).unwrap();
+        fb.flush().unwrap();
+        assert_golden(&name("settings"), &fb);
+    }
+}
+
+#[test]
+fn boot_sequence_ends_on_hello_screen() {
+    let mut fb = Framebuffer::default();
+    let mut boot = Boot::new();
+    for _ in 0..3 {
+        boot.step(&mut fb);
+    }
+
+    assert_eq!(fb.flushes(), 1);
+    assert_golden("hello.txt", &fb);
+}
+
+#[test]
+fn nothing_shows_until_flushed() {
+    let mut fb = Framebuffer::default();
+    fb.init().unwrap();
+    screens::hello(&mut fb).unwrap();
+
+    assert!(fb.screen().iter().all(|&b| b == 0));
+    assert!(!fb.pixel(Point::new(1, 5)));
+}
+
+#[test]
+fn exports_pbm_and_png() {
+    let mut fb = Framebuffer::default();
+    fb.init().unwrap();
+    screens::hello(&mut fb).unwrap();
+    fb.flush().unwrap();
+
+    let mut pbm = Vec::new();
+    fb.write_pbm(&mut pbm).unwrap();
+    assert!(pbm.starts_with(b"P4\n128 32\n"));
+    assert_eq!(pbm.len(), b"P4\n128 32\n".len() + 16 * 32);
+
+    let mut png = Vec::new();
+    fb.write_png(&mut png).unwrap();
+    assert!(png.starts_with(b"\x89PNG"));
+}
@@ -0,0 +1,77 @@
+#![cfg(feature = "std")]
+
+use microbit_oled::{
+    boot::Step, font, led::RecordingMatrix, patterns::Pattern, player::Player, scroll::Scroll,
+};
+
+fn patterns(text: &str) -> Vec<Pattern> {
+    Scroll::new(text, 100).map(|f| f.pattern).collect()
+}
+
+#[test]
+fn single_character_enters_right_and_leaves_left() {
+    let i = font::glyph('I').unwrap();
+
+    assert_eq!(
+        patterns("I"),
+        [
+            i.shift(4, 0),
+            i.shift(3, 0),
+            i.shift(2, 0),
+            i.shift(1, 0),
+            i,
+            i.shift(-1, 0),
+            i.shift(-2, 0),
+        ]
+    );
+}
+
+#[test]
+fn characters_are_one_column_apart() {
+    let a = font::glyph('A').unwrap();
+    let b = font::glyph('B').unwrap();
+    let frames = patterns("AB");
+
+    // 4 columns of A, a gap, 4 of B, then 4 to move B over to the left edge.
+    assert_eq!(frames.len(), 4 + 1 + 4 + 4);
+    assert_eq!(frames[4], a);
+    assert_eq!(frames[5], a.shift(-1, 0) | b.shift(4, 0));
+    assert_eq!(frames[8], b.shift(1, 0));
+    assert_eq!(frames[12], b.shift(-3, 0));
+}
+
+#[test]
+fn unknown_characters_scroll_as_question_marks() {
+    assert_eq!(patterns("~"), patterns("?"));
+}
+
+#[test]
+fn empty_text_has_no_frames() {
+    assert!(patterns("").is_empty());
+}
+
+#[test]
+fn speed_sets_every_frame_duration() {
+    let frames: Vec<_> = Scroll::new("OK 42", 80).collect();
+    assert!(frames.iter().all(|f| f.duration_ms == 80));
+
+    let mut leds = RecordingMatrix::new();
+    let mut player = Player::new();
+    let mut steps = frames.iter().map(|&frame| Step::from(frame));
+    let mut now_ms = 0;
+    loop {
+        if player.wants_step() {
+            if let Some(step) = steps.next() {
+                player.queue(step);
+            }
+        }
+        player.poll(now_ms, &mut leds);
+        if player.is_idle() {
+            break;
+        }
+        now_ms += 10;
+    }
+
+    assert_eq!(now_ms, 80 * frames.len() as u32);
+    assert_eq!(leds.lit(), Pattern::BLANK);
+}
@@ -0,0 +1,114 @@
+//! Settings round-tripping through a flash page.
+
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+use microbit_oled::{
+    brightness::Level,
+    burnin::Saver,
+    settings::{Settings, MAGIC},
+    speed::Speed,
+};
+
+/// A page of NOR flash in RAM: erasing sets every bit, writing can only
+/// clear them.
+struct RamFlash {
+    bytes: Vec<u8>,
+    erases: usize,
+}
+
+impl RamFlash {
+    fn blank() -> Self {
+        Self {
+            bytes: vec![0xFF; Self::ERASE_SIZE],
+            erases: 0,
+        }
+    }
+}
+
+impl ReadNorFlash for RamFlash {
+    type Error = ();
+    const READ_SIZE: usize = 4;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), ()> {
+        let offset = offset as usize;
+        bytes.copy_from_slice(self.bytes.get(offset..offset + bytes.len()).ok_or(())?);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+impl NorFlash for RamFlash {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = 4096;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), ()> {
+        self.bytes[from as usize..to as usize].fill(0xFF);
+        self.erases += 1;
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), ()> {
+        assert_eq!(bytes.len() % Self::WRITE_SIZE, 0);
+        for (cell, byte) in self.bytes[offset as usize..].iter_mut().zip(bytes) {
+            *cell &= byte;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn blank_flash_loads_defaults() {
+    assert_eq!(Settings::load(&mut RamFlash::blank()), Settings::new());
+}
+
+#[test]
+fn saved_settings_load_back() {
+    let mut flash = RamFlash::blank();
+    let settings = Settings {
+        oled_address: Some(0x3D),
+        i2c_speed: Some(Speed::K400),
+        brightness: Some(Level::Highest),
+        night_mode: Some(false),
+        saver: Some(Saver::Blank),
+    };
+
+    settings.save(&mut flash).unwrap();
+    // Saving again over the old record has to erase first.
+    settings.save(&mut flash).unwrap();
+
+    assert_eq!(Settings::load(&mut flash), settings);
+    assert_eq!(flash.erases, 2);
+    assert_eq!(flash.bytes[..4], MAGIC);
+}
+
+#[test]
+fn foreign_data_is_ignored() {
+    let mut flash = RamFlash::blank();
+    flash.bytes[..8].copy_from_slice(b"NOTOURS!");
+    assert_eq!(Settings::load(&mut flash), Settings::new());
+
+    let mut record = Settings::new().to_bytes();
+    record[4] = 0xC3;
+    record[5] = 7;
+    record[6] = 5;
+    record[7] = 2;
+    record[8] = 4;
+    assert_eq!(Settings::from_bytes(&record), Some(Settings::new()));
+}
+
+#[test]
+fn older_records_load_newer_settings_unset() {
+    // What the firmware saved before brightness was added: the record
+    // stopped after the speed, and the flash after it was left erased.
+    let mut flash = RamFlash::blank();
+    flash.bytes[..6].copy_from_slice(b"MBO1\x3C\x02");
+
+    let settings = Settings::load(&mut flash);
+
+    assert_eq!(settings.i2c_speed, Some(Speed::K400));
+    assert_eq!(settings.brightness, None);
+    assert_eq!(settings.night_mode, None);
+    assert_eq!(settings.saver, None);
+}
@@ -0,0 +1,101 @@
+//! Bus speeds, stepping down when they don't hold up, and timing frames.
+#![cfg(feature = "std")]
+
+use std::{cell::Cell, cell::RefCell, rc::Rc};
+
+use embedded_graphics::prelude::*;
+use microbit_oled::{
+    boot::{Boot, State},
+    display::{Oled, Panel},
+    emulator::Framebuffer,
+    i2c_mock::MockSsd1306,
+    screens,
+    speed::{Fallback, Link, Metered, Speed, FALLBACK_AFTER},
+    timeout::{Deadline, Micros},
+};
+use ssd1306::prelude::*;
+
+/// A clock that moves on 10 µs every time it's read.
+#[derive(Clone, Default)]
+struct Ticking(Rc<Cell<u32>>);
+
+impl Micros for Ticking {
+    fn now_us(&mut self) -> u32 {
+        self.0.set(self.0.get().wrapping_add(10));
+        self.0.get()
+    }
+}
+
+#[test]
+fn speeds_round_trip_and_cycle() {
+    for speed in Speed::ALL {
+        assert_eq!(Speed::from_index(speed.index()), Some(speed));
+    }
+    assert_eq!(Speed::from_index(3), None);
+    assert_eq!(Speed::K400.next(), Speed::K100);
+    assert_eq!(Speed::K400.slower(), Some(Speed::K250));
+    assert_eq!(Speed::K100.slower(), None);
+}
+
+/// Run the boot sequence on `mock` for `steps` steps, starting at 400 kHz,
+/// and return each speed the fallback switched to and the failed attempts
+/// it had seen when it did.
+fn fall_back(mock: &MockSsd1306, steps: usize) -> Vec<(Speed, u32)> {
+    let bus = RefCell::new(Deadline::new(mock.clone(), Ticking::default()));
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    let mut boot = Boot::new();
+    let mut fallback = Fallback::new(Speed::K400);
+    let mut switches = Vec::new();
+    for _ in 0..steps {
+        boot.step(&mut display);
+        if let Some(speed) = fallback.observe(boot.state()) {
+            let State::Failed { retry, .. } = boot.state() else {
+                panic!("switched speed while {:?}", boot.state());
+            };
+            switches.push((speed, retry.failures));
+        }
+    }
+    switches
+}
+
+#[test]
+fn keeps_failing_steps_down_to_the_slowest() {
+    let mock = MockSsd1306::default();
+    mock.set_hung(true);
+
+    assert_eq!(
+        fall_back(&mock, 200),
+        [
+            (Speed::K250, FALLBACK_AFTER),
+            (Speed::K100, 2 * FALLBACK_AFTER)
+        ]
+    );
+}
+
+#[test]
+fn unplugged_module_keeps_its_speed() {
+    let mock = MockSsd1306::default();
+    mock.set_present(false);
+
+    assert_eq!(fall_back(&mock, 200), []);
+}
+
+#[test]
+fn only_whole_frames_are_timed() {
+    let clock = Ticking::default();
+    let mut display = Metered::new(Framebuffer::default(), clock.clone());
+    display.init().unwrap();
+    assert_eq!(display.frame_us(), None);
+
+    screens::hello(&mut display).unwrap();
+    display.flush().unwrap();
+    let frame_us = display.frame_us();
+    assert_eq!(frame_us, Some(10));
+
+    // The link line only redraws part of the screen.
+    clock.0.set(1_000);
+    screens::link(&mut display, Link::I2c(Speed::K100), 10).unwrap();
+    display.flush().unwrap();
+    assert_eq!(display.frame_us(), frame_us);
+    assert!(display.inner().pixel(Point::new(2, 25)));
+}
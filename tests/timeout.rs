@@ -0,0 +1,118 @@
+//! Transfers that hang are given up on, against the fake SSD1306.
+#![cfg(feature = "std")]
+
+use std::{cell::Cell, cell::RefCell, rc::Rc};
+
+use embedded_graphics::prelude::*;
+use embedded_hal::blocking::i2c::{Read, Write};
+use microbit_oled::{
+    boot::{Boot, State},
+    display::{Oled, Panel},
+    error::Error,
+    i2c_mock::{MockError, MockSsd1306},
+    timeout::{budget_us, Deadline, Micros, Timed},
+};
+use ssd1306::prelude::*;
+
+/// A clock that moves on 10 µs every time it's read.
+#[derive(Clone, Default)]
+struct Ticking(Rc<Cell<u32>>);
+
+impl Micros for Ticking {
+    fn now_us(&mut self) -> u32 {
+        self.0.set(self.0.get().wrapping_add(10));
+        self.0.get()
+    }
+}
+
+fn bus() -> (
+    MockSsd1306,
+    Ticking,
+    RefCell<Deadline<MockSsd1306, Ticking>>,
+) {
+    let mock = MockSsd1306::default();
+    let clock = Ticking::default();
+    let bus = RefCell::new(Deadline::new(mock.clone(), clock.clone()));
+    (mock, clock, bus)
+}
+
+#[test]
+fn transfers_go_through_when_the_bus_is_fine() {
+    let (mock, _, bus) = bus();
+    let mut bus = bus.into_inner();
+
+    bus.write(0x3C, &[0x00, 0xAF]).unwrap();
+    let mut status = [0];
+    bus.read(0x3C, &mut status).unwrap();
+
+    assert!(mock.state().is_display_on());
+    assert_eq!(status, [0x03]);
+    assert_eq!(
+        bus.write(0x3D, &[0x00]),
+        Err(Timed::Bus(MockError::Nack { address: 0x3D }))
+    );
+}
+
+#[test]
+fn hung_transfer_is_abandoned_after_its_budget() {
+    let (mock, clock, bus) = bus();
+    let mut bus = bus.into_inner();
+    mock.set_hung(true);
+
+    let bytes = [0x40; 17];
+    assert_eq!(bus.write(0x3C, &bytes), Err(Timed::Timeout));
+
+    let waited_us = clock.0.get();
+    assert!(waited_us > budget_us(bytes.len()));
+    assert!(waited_us <= budget_us(bytes.len()) + 20);
+
+    mock.set_hung(false);
+    assert_eq!(bus.write(0x3C, &[0x00, 0xAF]), Ok(()));
+}
+
+#[test]
+fn hung_bus_at_power_up_is_a_timeout() {
+    let (mock, _, bus) = bus();
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    mock.set_hung(true);
+
+    let error = display.init().unwrap_err();
+
+    assert!(matches!(error, Error::BusTimeout));
+    assert_eq!(error.code(), 4);
+}
+
+#[test]
+fn bus_hanging_while_running_is_recovered() {
+    let (mock, _, bus) = bus();
+    let mut display = Panel::new(&bus, 0x3C, DisplaySize128x32);
+    let mut boot = Boot::new();
+    for _ in 0..4 {
+        boot.step(&mut display);
+    }
+    assert!(matches!(boot.state(), State::Running));
+
+    mock.set_hung(true);
+    boot.step(&mut display);
+    assert!(matches!(
+        boot.state(),
+        State::Failed {
+            error: Error::BusTimeout,
+            ..
+        }
+    ));
+
+    mock.set_hung(false);
+    for _ in 0..20 {
+        boot.step(&mut display);
+        if matches!(boot.state(), State::Running) {
+            break;
+        }
+    }
+
+    assert!(matches!(boot.state(), State::Running));
+    assert_eq!(
+        mock.state().to_ascii(Size::new(128, 32)),
+        include_str!("golden/hello.txt")
+    );
+}